@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP TABLE funding_template_items;
+DROP TABLE funding_templates;
@@ -0,0 +1,18 @@
+-- Your SQL goes here
+CREATE TABLE funding_templates
+(
+    id   INTEGER NOT NULL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    UNIQUE (name)
+);
+
+CREATE TABLE funding_template_items
+(
+    id                  INTEGER          NOT NULL PRIMARY KEY,
+    funding_template_id INTEGER          NOT NULL,
+    category_id         INTEGER          NOT NULL,
+    amount              DOUBLE PRECISION NOT NULL,
+    is_percentage       BOOLEAN          NOT NULL DEFAULT 0,
+    FOREIGN KEY (funding_template_id) REFERENCES funding_templates (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
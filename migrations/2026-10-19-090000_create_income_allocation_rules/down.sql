@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+ALTER TABLE transactions DROP COLUMN linked_transaction_id;
+DROP TABLE income_allocation_rules;
@@ -0,0 +1,14 @@
+-- Your SQL goes here
+CREATE TABLE income_allocation_rules
+(
+    id                INTEGER          NOT NULL PRIMARY KEY,
+    budget_account_id INTEGER          NOT NULL,
+    category_id       INTEGER          NOT NULL,
+    percentage        DOUBLE PRECISION NOT NULL,
+    UNIQUE (budget_account_id, category_id),
+    FOREIGN KEY (budget_account_id) REFERENCES budget_accounts (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
+
+ALTER TABLE transactions
+    ADD COLUMN linked_transaction_id INTEGER DEFAULT NULL REFERENCES transactions (id) ON DELETE SET NULL;
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE transactions
+    ADD COLUMN overspending_cover BOOLEAN NOT NULL DEFAULT 0;
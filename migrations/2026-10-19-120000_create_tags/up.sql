@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE tags
+(
+    id   INTEGER NOT NULL PRIMARY KEY,
+    name VARCHAR NOT NULL,
+    UNIQUE (name)
+);
+
+CREATE TABLE transaction_tags
+(
+    transaction_id INTEGER NOT NULL,
+    tag_id         INTEGER NOT NULL,
+    PRIMARY KEY (transaction_id, tag_id),
+    FOREIGN KEY (transaction_id) REFERENCES transactions (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (tag_id) REFERENCES tags (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
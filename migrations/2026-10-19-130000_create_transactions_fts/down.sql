@@ -0,0 +1,5 @@
+-- This file should undo anything in `up.sql`
+DROP TRIGGER transactions_fts_after_update;
+DROP TRIGGER transactions_fts_after_delete;
+DROP TRIGGER transactions_fts_after_insert;
+DROP TABLE transactions_fts;
@@ -0,0 +1,34 @@
+-- Your SQL goes here
+CREATE VIRTUAL TABLE transactions_fts USING fts5
+(
+    payee,
+    note,
+    content = 'transactions',
+    content_rowid = 'id'
+);
+
+INSERT INTO transactions_fts (rowid, payee, note)
+SELECT id, payee, note
+FROM transactions;
+
+CREATE TRIGGER transactions_fts_after_insert
+    AFTER INSERT
+    ON transactions
+BEGIN
+    INSERT INTO transactions_fts (rowid, payee, note) VALUES (new.id, new.payee, new.note);
+END;
+
+CREATE TRIGGER transactions_fts_after_delete
+    AFTER DELETE
+    ON transactions
+BEGIN
+    INSERT INTO transactions_fts (transactions_fts, rowid, payee, note) VALUES ('delete', old.id, old.payee, old.note);
+END;
+
+CREATE TRIGGER transactions_fts_after_update
+    AFTER UPDATE OF payee, note
+    ON transactions
+BEGIN
+    INSERT INTO transactions_fts (transactions_fts, rowid, payee, note) VALUES ('delete', old.id, old.payee, old.note);
+    INSERT INTO transactions_fts (rowid, payee, note) VALUES (new.id, new.payee, new.note);
+END;
@@ -0,0 +1,10 @@
+-- Your SQL goes here
+CREATE TABLE period_allocations
+(
+    id          INTEGER          NOT NULL PRIMARY KEY,
+    category_id INTEGER          NOT NULL,
+    month       DATE             NOT NULL,
+    amount      DOUBLE PRECISION NOT NULL,
+    UNIQUE (category_id, month),
+    FOREIGN KEY (category_id) REFERENCES categories (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
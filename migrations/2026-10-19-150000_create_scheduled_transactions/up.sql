@@ -0,0 +1,17 @@
+-- Your SQL goes here
+CREATE TABLE scheduled_transactions
+(
+    id                  INTEGER          NOT NULL PRIMARY KEY,
+    budget_account_id   INTEGER          NOT NULL,
+    category_id         INTEGER          NOT NULL,
+    transaction_type_id INTEGER          NOT NULL,
+    payee               VARCHAR          NOT NULL,
+    note                VARCHAR          NOT NULL,
+    amount              DOUBLE PRECISION NOT NULL,
+    start_date          DATE             NOT NULL,
+    recurrence          VARCHAR          NOT NULL,
+    every               INTEGER          NOT NULL DEFAULT 1,
+    FOREIGN KEY (budget_account_id) REFERENCES budget_accounts (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (transaction_type_id) REFERENCES transaction_types (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
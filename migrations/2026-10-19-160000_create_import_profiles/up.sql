@@ -0,0 +1,20 @@
+-- Your SQL goes here
+CREATE TABLE import_profiles
+(
+    id                INTEGER NOT NULL PRIMARY KEY,
+    budget_account_id INTEGER NOT NULL,
+    name              VARCHAR NOT NULL,
+    delimiter         VARCHAR NOT NULL DEFAULT ',',
+    header_rows       INTEGER NOT NULL DEFAULT 1,
+    date_column       INTEGER NOT NULL,
+    date_format       VARCHAR NOT NULL,
+    amount_column     INTEGER,
+    debit_column      INTEGER,
+    credit_column     INTEGER,
+    payee_column      INTEGER NOT NULL,
+    memo_column       INTEGER,
+    category_column   INTEGER,
+    decimal_separator VARCHAR NOT NULL DEFAULT '.',
+    UNIQUE (budget_account_id, name),
+    FOREIGN KEY (budget_account_id) REFERENCES budget_accounts (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- This file should undo anything in `up.sql`
+DROP INDEX transactions_budget_account_id_fitid;
+ALTER TABLE transactions DROP COLUMN fitid;
@@ -0,0 +1,4 @@
+-- Your SQL goes here
+ALTER TABLE transactions
+    ADD COLUMN fitid VARCHAR;
+CREATE UNIQUE INDEX transactions_budget_account_id_fitid ON transactions (budget_account_id, fitid);
@@ -0,0 +1,3 @@
+-- Your SQL goes here
+ALTER TABLE transactions
+    ADD COLUMN duplicate_of_id INTEGER DEFAULT NULL REFERENCES transactions (id) ON DELETE SET NULL;
@@ -0,0 +1,16 @@
+-- Your SQL goes here
+CREATE TABLE categorization_rules
+(
+    id                INTEGER NOT NULL PRIMARY KEY,
+    budget_account_id INTEGER NOT NULL,
+    name              VARCHAR NOT NULL,
+    priority          INTEGER NOT NULL DEFAULT 0,
+    payee_pattern     VARCHAR,
+    note_pattern      VARCHAR,
+    payee             VARCHAR,
+    category_id       INTEGER,
+    tag               VARCHAR,
+    UNIQUE (budget_account_id, name),
+    FOREIGN KEY (budget_account_id) REFERENCES budget_accounts (id) ON UPDATE CASCADE ON DELETE CASCADE,
+    FOREIGN KEY (category_id) REFERENCES categories (id) ON UPDATE CASCADE ON DELETE CASCADE
+);
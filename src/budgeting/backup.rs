@@ -0,0 +1,655 @@
+//! Everything in the database as one JSON document, to move budgets between machines
+//! and to recover from losing the database. The records mirror the tables, ids included,
+//! so references between them survive; restoring gives every row a new id and rewrites
+//! the references. The document has a version, a document of another version is refused
+//! rather than guessed at.
+
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::duplicate::{DuplicateMode, DuplicateSettings};
+use crate::budgeting::transaction::OverspendingMode;
+use crate::budgeting::validation;
+use crate::schema::{
+    budget_accounts, categories, categorization_rules, funding_template_items, funding_templates,
+    import_profiles, income_allocation_rules, period_allocations, scheduled_transactions, tags,
+    transaction_tags, transactions,
+};
+use crate::{current_date, DEFAULT_CATEGORY};
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// version of the documents `Backup::build` writes, the only one `Backup::restore` reads
+pub const BACKUP_VERSION: u32 = 1;
+
+/// rows inserted with one statement, well below the number of parameters sqlite allows
+const CHUNK: usize = 50;
+
+/// Settings of `Budgeting` that are not stored in the database
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct BackupSettings {
+    pub overspending_mode: OverspendingMode,
+    pub duplicate_mode: DuplicateMode,
+    pub duplicate_settings: DuplicateSettings,
+    pub categorization_rules_on_entry: bool,
+    pub import_suggestion_confidence: Option<f64>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = budget_accounts)]
+pub struct BudgetAccountRecord {
+    pub id: i32,
+    pub filed_as: String,
+    pub date_created: NaiveDateTime,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = categories)]
+pub struct CategoryRecord {
+    pub id: i32,
+    pub name: String,
+    pub allocated: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = transactions)]
+pub struct TransactionRecord {
+    pub id: i32,
+    pub note: String,
+    pub payee: String,
+    pub date_created: NaiveDateTime,
+    pub amount: f64,
+    pub category_id: i32,
+    pub transaction_type_id: i32,
+    /// 0, the column default, when there is none
+    pub transfer_category_id: Option<i32>,
+    pub budget_account_id: i32,
+    pub linked_transaction_id: Option<i32>,
+    pub overspent: bool,
+    pub overspending_cover: bool,
+    pub fitid: Option<String>,
+    pub duplicate_of_id: Option<i32>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = tags)]
+pub struct TagRecord {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = transaction_tags)]
+pub struct TransactionTagRecord {
+    pub transaction_id: i32,
+    pub tag_id: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = funding_templates)]
+pub struct FundingTemplateRecord {
+    pub id: i32,
+    pub name: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = funding_template_items)]
+pub struct FundingTemplateItemRecord {
+    pub id: i32,
+    pub funding_template_id: i32,
+    pub category_id: i32,
+    pub amount: f64,
+    pub is_percentage: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = income_allocation_rules)]
+pub struct IncomeAllocationRuleRecord {
+    pub id: i32,
+    pub budget_account_id: i32,
+    pub category_id: i32,
+    pub percentage: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = period_allocations)]
+pub struct PeriodAllocationRecord {
+    pub id: i32,
+    pub category_id: i32,
+    pub month: NaiveDate,
+    pub amount: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = scheduled_transactions)]
+pub struct ScheduledTransactionRecord {
+    pub id: i32,
+    pub budget_account_id: i32,
+    pub category_id: i32,
+    pub transaction_type_id: i32,
+    pub payee: String,
+    pub note: String,
+    pub amount: f64,
+    pub start_date: NaiveDate,
+    pub recurrence: String,
+    pub every: i32,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = import_profiles)]
+pub struct ImportProfileRecord {
+    pub id: i32,
+    pub budget_account_id: i32,
+    pub name: String,
+    pub delimiter: String,
+    pub header_rows: i32,
+    pub date_column: i32,
+    pub date_format: String,
+    pub amount_column: Option<i32>,
+    pub debit_column: Option<i32>,
+    pub credit_column: Option<i32>,
+    pub payee_column: i32,
+    pub memo_column: Option<i32>,
+    pub category_column: Option<i32>,
+    pub decimal_separator: String,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Queryable, Insertable)]
+#[diesel(table_name = categorization_rules)]
+pub struct CategorizationRuleRecord {
+    pub id: i32,
+    pub budget_account_id: i32,
+    pub name: String,
+    pub priority: i32,
+    pub payee_pattern: Option<String>,
+    pub note_pattern: Option<String>,
+    pub payee: Option<String>,
+    pub category_id: Option<i32>,
+    pub tag: Option<String>,
+}
+
+/// What a budget account looked like when the backup was made, checked against the
+/// records before restoring and against the database after
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct AccountCheck {
+    pub filed_as: String,
+    pub transactions: usize,
+    pub balance: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct Backup {
+    pub version: u32,
+    pub created: NaiveDateTime,
+    pub settings: BackupSettings,
+    pub budget_accounts: Vec<BudgetAccountRecord>,
+    pub categories: Vec<CategoryRecord>,
+    pub transactions: Vec<TransactionRecord>,
+    pub tags: Vec<TagRecord>,
+    pub transaction_tags: Vec<TransactionTagRecord>,
+    pub funding_templates: Vec<FundingTemplateRecord>,
+    pub funding_template_items: Vec<FundingTemplateItemRecord>,
+    pub income_allocation_rules: Vec<IncomeAllocationRuleRecord>,
+    pub period_allocations: Vec<PeriodAllocationRecord>,
+    pub scheduled_transactions: Vec<ScheduledTransactionRecord>,
+    pub import_profiles: Vec<ImportProfileRecord>,
+    pub categorization_rules: Vec<CategorizationRuleRecord>,
+    pub checks: Vec<AccountCheck>,
+}
+
+fn invalid(reason: String) -> BudgetingErrors {
+    BudgetingErrors::InvalidBackup(reason)
+}
+
+fn db_error(e: diesel::result::Error) -> BudgetingErrors {
+    BudgetingErrors::UnspecifiedDatabaseError(e)
+}
+
+/// ids of the records, failing on the first id seen twice
+fn unique_ids<T>(
+    what: &str,
+    records: &[T],
+    id: impl Fn(&T) -> i32,
+) -> Result<HashSet<i32>, BudgetingErrors> {
+    let mut ids = HashSet::new();
+    for r in records {
+        if !ids.insert(id(r)) {
+            return Err(invalid(format!("{what} {} appears twice", id(r))));
+        }
+    }
+    Ok(ids)
+}
+
+fn refers(what: &str, id: i32, ids: &HashSet<i32>) -> Result<(), BudgetingErrors> {
+    if ids.contains(&id) {
+        Ok(())
+    } else {
+        Err(invalid(format!("{what} {id} does not exist")))
+    }
+}
+
+/// new ids for the records, in order, starting at `first`
+fn renumber<T>(records: &[T], id: impl Fn(&T) -> i32, first: i32) -> HashMap<i32, i32> {
+    records
+        .iter()
+        .enumerate()
+        .map(|(i, r)| (id(r), first + i as i32))
+        .collect()
+}
+
+fn account_checks(
+    accounts: &[BudgetAccountRecord],
+    records: &[TransactionRecord],
+) -> Vec<AccountCheck> {
+    accounts
+        .iter()
+        .map(|a| {
+            let owned = records.iter().filter(|t| t.budget_account_id == a.id);
+            AccountCheck {
+                filed_as: a.filed_as.clone(),
+                transactions: owned.clone().count(),
+                balance: owned.map(|t| t.amount).sum(),
+            }
+        })
+        .collect()
+}
+
+fn same_check(expected: &AccountCheck, found: &AccountCheck) -> bool {
+    expected.filed_as == found.filed_as
+        && expected.transactions == found.transactions
+        && (expected.balance - found.balance).abs() < 1e-6
+}
+
+impl Backup {
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        settings: BackupSettings,
+    ) -> Result<Backup, BudgetingErrors> {
+        let budget_accounts = budget_accounts::table
+            .order(budget_accounts::id.asc())
+            .load::<BudgetAccountRecord>(conn)
+            .map_err(db_error)?;
+        let transactions = transactions::table
+            .order(transactions::id.asc())
+            .load::<TransactionRecord>(conn)
+            .map_err(db_error)?;
+        Ok(Backup {
+            version: BACKUP_VERSION,
+            created: current_date(),
+            settings,
+            checks: account_checks(&budget_accounts, &transactions),
+            budget_accounts,
+            categories: categories::table
+                .order(categories::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            transactions,
+            tags: tags::table
+                .order(tags::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            transaction_tags: transaction_tags::table
+                .order((
+                    transaction_tags::transaction_id.asc(),
+                    transaction_tags::tag_id.asc(),
+                ))
+                .load(conn)
+                .map_err(db_error)?,
+            funding_templates: funding_templates::table
+                .order(funding_templates::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            funding_template_items: funding_template_items::table
+                .order(funding_template_items::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            income_allocation_rules: income_allocation_rules::table
+                .order(income_allocation_rules::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            period_allocations: period_allocations::table
+                .order(period_allocations::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            scheduled_transactions: scheduled_transactions::table
+                .order(scheduled_transactions::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            import_profiles: import_profiles::table
+                .order(import_profiles::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+            categorization_rules: categorization_rules::table
+                .order(categorization_rules::id.asc())
+                .load(conn)
+                .map_err(db_error)?,
+        })
+    }
+
+    /// the version is supported, the settings are valid, ids are unique, every reference
+    /// points at a record of the document and the account checks match the transactions
+    pub fn verify(&self) -> Result<(), BudgetingErrors> {
+        if self.version != BACKUP_VERSION {
+            return Err(BudgetingErrors::UnsupportedBackupVersion(self.version));
+        }
+        validation::duplicate_settings(&self.settings.duplicate_settings)
+            .map_err(|e| invalid(format!("settings: {e}")))?;
+        let accounts = unique_ids("budget account", &self.budget_accounts, |r| r.id)?;
+        let categories = unique_ids("category", &self.categories, |r| r.id)?;
+        let transactions = unique_ids("transaction", &self.transactions, |r| r.id)?;
+        let tags = unique_ids("tag", &self.tags, |r| r.id)?;
+        let templates = unique_ids("funding template", &self.funding_templates, |r| r.id)?;
+        unique_ids("funding template item", &self.funding_template_items, |r| {
+            r.id
+        })?;
+        unique_ids(
+            "income allocation rule",
+            &self.income_allocation_rules,
+            |r| r.id,
+        )?;
+        unique_ids("period allocation", &self.period_allocations, |r| r.id)?;
+        unique_ids("scheduled transaction", &self.scheduled_transactions, |r| {
+            r.id
+        })?;
+        unique_ids("import profile", &self.import_profiles, |r| r.id)?;
+        unique_ids("categorization rule", &self.categorization_rules, |r| r.id)?;
+        for t in &self.transactions {
+            refers("budget account", t.budget_account_id, &accounts)?;
+            refers("category", t.category_id, &categories)?;
+            if let Some(c) = t.transfer_category_id.filter(|c| *c != 0) {
+                refers("category", c, &categories)?;
+            }
+            if let Some(l) = t.linked_transaction_id {
+                refers("transaction", l, &transactions)?;
+            }
+            if let Some(d) = t.duplicate_of_id {
+                refers("transaction", d, &transactions)?;
+            }
+        }
+        for t in &self.transaction_tags {
+            refers("transaction", t.transaction_id, &transactions)?;
+            refers("tag", t.tag_id, &tags)?;
+        }
+        for i in &self.funding_template_items {
+            refers("funding template", i.funding_template_id, &templates)?;
+            refers("category", i.category_id, &categories)?;
+        }
+        for r in &self.income_allocation_rules {
+            refers("budget account", r.budget_account_id, &accounts)?;
+            refers("category", r.category_id, &categories)?;
+        }
+        for p in &self.period_allocations {
+            refers("category", p.category_id, &categories)?;
+        }
+        for s in &self.scheduled_transactions {
+            refers("budget account", s.budget_account_id, &accounts)?;
+            refers("category", s.category_id, &categories)?;
+        }
+        for p in &self.import_profiles {
+            refers("budget account", p.budget_account_id, &accounts)?;
+        }
+        for r in &self.categorization_rules {
+            refers("budget account", r.budget_account_id, &accounts)?;
+            if let Some(c) = r.category_id {
+                refers("category", c, &categories)?;
+            }
+            // a rule without patterns, or with one that does not compile, is refused by
+            // `CategorizationRuleBuilder`
+            if r.payee_pattern.is_none() && r.note_pattern.is_none() {
+                return Err(invalid(format!(
+                    "categorization rule {} has no pattern",
+                    r.id
+                )));
+            }
+            for (field, p) in [
+                ("payee_pattern", &r.payee_pattern),
+                ("note_pattern", &r.note_pattern),
+            ] {
+                if let Some(p) = p {
+                    validation::pattern(field, p)
+                        .map_err(|e| invalid(format!("categorization rule {}: {}", r.id, e)))?;
+                }
+            }
+        }
+        let found = account_checks(&self.budget_accounts, &self.transactions);
+        if found.len() != self.checks.len()
+            || !found
+                .iter()
+                .zip(&self.checks)
+                .all(|(f, e)| same_check(e, f))
+        {
+            return Err(invalid(
+                "account checks do not match the transactions".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Writes the document into a database without budget accounts, categories other than
+    /// the default one, tags or funding templates. The default category of the document
+    /// becomes the one of the database, everything else gets new ids.
+    pub(crate) fn restore(&self, conn: &mut SqliteConnection) -> Result<(), BudgetingErrors> {
+        self.verify()?;
+        let count = |n: QueryResult<i64>| n.map_err(db_error);
+        let used = count(budget_accounts::table.count().get_result(conn))?
+            + count(
+                categories::table
+                    .filter(categories::name.ne(DEFAULT_CATEGORY))
+                    .count()
+                    .get_result(conn),
+            )?
+            + count(transactions::table.count().get_result(conn))?
+            + count(tags::table.count().get_result(conn))?
+            + count(funding_templates::table.count().get_result(conn))?;
+        if used > 0 {
+            return Err(BudgetingErrors::RestoreTargetNotEmpty);
+        }
+        let default_id = categories::table
+            .filter(categories::name.eq(DEFAULT_CATEGORY))
+            .select(categories::id)
+            .first::<i32>(conn)
+            .map_err(db_error)?;
+
+        let accounts = renumber(&self.budget_accounts, |r| r.id, 1);
+        let (defaults, others): (Vec<&CategoryRecord>, Vec<&CategoryRecord>) = self
+            .categories
+            .iter()
+            .partition(|c| c.name == DEFAULT_CATEGORY);
+        let mut category_ids = renumber(&others, |r| r.id, default_id + 1);
+        for d in &defaults {
+            category_ids.insert(d.id, default_id);
+            diesel::update(categories::table.find(default_id))
+                .set(categories::allocated.eq(d.allocated))
+                .execute(conn)
+                .map_err(db_error)?;
+        }
+        let transaction_ids = renumber(&self.transactions, |r| r.id, 1);
+        let tag_ids = renumber(&self.tags, |r| r.id, 1);
+        let template_ids = renumber(&self.funding_templates, |r| r.id, 1);
+        // verify made sure every reference is in its map
+        let account = |id: i32| accounts[&id];
+        let category = |id: i32| category_ids[&id];
+        let transaction = |id: i32| transaction_ids[&id];
+
+        macro_rules! insert {
+            ($table: ident, $records: expr) => {
+                for chunk in $records.chunks(CHUNK) {
+                    diesel::insert_into($table::table)
+                        .values(chunk)
+                        .execute(conn)
+                        .map_err(db_error)?;
+                }
+            };
+        }
+        insert!(
+            budget_accounts,
+            self.budget_accounts
+                .iter()
+                .map(|r| BudgetAccountRecord {
+                    id: account(r.id),
+                    ..r.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            categories,
+            others
+                .iter()
+                .map(|r| CategoryRecord {
+                    id: category(r.id),
+                    ..(*r).clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            transactions,
+            self.transactions
+                .iter()
+                .map(|r| TransactionRecord {
+                    id: transaction(r.id),
+                    category_id: category(r.category_id),
+                    transfer_category_id: r
+                        .transfer_category_id
+                        .map(|c| if c == 0 { 0 } else { category(c) }),
+                    budget_account_id: account(r.budget_account_id),
+                    linked_transaction_id: r.linked_transaction_id.map(transaction),
+                    duplicate_of_id: r.duplicate_of_id.map(transaction),
+                    ..r.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            tags,
+            self.tags
+                .iter()
+                .map(|r| TagRecord {
+                    id: tag_ids[&r.id],
+                    ..r.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            transaction_tags,
+            self.transaction_tags
+                .iter()
+                .map(|r| TransactionTagRecord {
+                    transaction_id: transaction(r.transaction_id),
+                    tag_id: tag_ids[&r.tag_id],
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            funding_templates,
+            self.funding_templates
+                .iter()
+                .map(|r| FundingTemplateRecord {
+                    id: template_ids[&r.id],
+                    ..r.clone()
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            funding_template_items,
+            self.funding_template_items
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    FundingTemplateItemRecord {
+                        id: i as i32 + 1,
+                        funding_template_id: template_ids[&r.funding_template_id],
+                        category_id: category(r.category_id),
+                        ..r.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            income_allocation_rules,
+            self.income_allocation_rules
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    IncomeAllocationRuleRecord {
+                        id: i as i32 + 1,
+                        budget_account_id: account(r.budget_account_id),
+                        category_id: category(r.category_id),
+                        ..r.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            period_allocations,
+            self.period_allocations
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    PeriodAllocationRecord {
+                        id: i as i32 + 1,
+                        category_id: category(r.category_id),
+                        ..r.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            scheduled_transactions,
+            self.scheduled_transactions
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    ScheduledTransactionRecord {
+                        id: i as i32 + 1,
+                        budget_account_id: account(r.budget_account_id),
+                        category_id: category(r.category_id),
+                        ..r.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            import_profiles,
+            self.import_profiles
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    ImportProfileRecord {
+                        id: i as i32 + 1,
+                        budget_account_id: account(r.budget_account_id),
+                        ..r.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+        );
+        insert!(
+            categorization_rules,
+            self.categorization_rules
+                .iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    CategorizationRuleRecord {
+                        id: i as i32 + 1,
+                        budget_account_id: account(r.budget_account_id),
+                        category_id: r.category_id.map(category),
+                        ..r.clone()
+                    }
+                })
+                .collect::<Vec<_>>()
+        );
+
+        // what was written adds up to what was backed up
+        let restored = Backup::build(conn, self.settings.clone())?;
+        if restored.checks.len() != self.checks.len()
+            || !restored
+                .checks
+                .iter()
+                .zip(&self.checks)
+                .all(|(f, e)| same_check(e, f))
+        {
+            return Err(invalid(
+                "restored balances do not match the account checks".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
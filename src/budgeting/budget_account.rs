@@ -1,5 +1,5 @@
 use crate::schema::budget_accounts;
-use chrono::{NaiveDateTime};
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::prelude::*;
 use diesel::SqliteConnection;
 use diesel::{Insertable, Queryable};
@@ -9,6 +9,8 @@ use std::ops::DerefMut;
 use diesel::result::Error as DieselError;
 
 use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::transaction::TransactionModel;
+use crate::budgeting::validation;
 use crate::{current_date, DbConnection};
 
 /// Budget is used to store all the transaction categories and store their details in a file
@@ -46,6 +48,7 @@ impl BudgetAccountBuilder {
     }
 
     pub fn build(&mut self) -> Result<BudgetAccount, BudgetingErrors> {
+        validation::not_empty("filed_as", &self.filed_as)?;
         let new_budget = NewBudgetAccount {
             filed_as: &self.filed_as,
             date_created: self.date_created.unwrap_or_else(current_date),
@@ -97,6 +100,9 @@ impl BudgetAccountModel {
         budget_account_id: i32,
         _filed_as: Option<String>,
     ) -> Result<usize, BudgetingErrors> {
+        if let Some(n) = &_filed_as {
+            validation::not_empty("filed_as", n)?;
+        }
         imp_db!(budget_accounts);
         let r = diesel::update(budget_accounts.find(budget_account_id))
             .set(&BudgetAccountForm {
@@ -118,6 +124,31 @@ impl BudgetAccountModel {
         }
     }
 
+    pub fn budget_account(&self) -> &BudgetAccount {
+        &self.budget_account
+    }
+
+    /// real money in this budget account, income - expenses, transfers with other budget
+    /// accounts included
+    pub fn balance(&mut self) -> f64 {
+        BudgetAccountModel::_balance_as_of((*self.conn).borrow_mut().deref_mut(), self.budget_account.id, None)
+    }
+
+    /// real money in this budget account at the end of the given day
+    pub fn balance_as_of(&mut self, date: NaiveDate) -> f64 {
+        BudgetAccountModel::_balance_as_of((*self.conn).borrow_mut().deref_mut(), self.budget_account.id, Some(date))
+    }
+
+    pub(crate) fn _balance_as_of(
+        conn: &mut SqliteConnection,
+        bid: i32,
+        as_of: Option<NaiveDate>,
+    ) -> f64 {
+        // transfers between categories of the account add up to 0, only transfers with
+        // another budget account change its balance
+        TransactionModel::total_as_of(conn, None, None, Some(bid), as_of)
+    }
+
     pub(crate) fn find_all(
         conn: &mut SqliteConnection,
     ) -> Result<Vec<BudgetAccount>, BudgetingErrors> {
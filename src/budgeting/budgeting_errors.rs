@@ -36,6 +36,58 @@ pub enum BudgetingErrors {
     OnlyDefaultCategoryCanHaveIncome,
     #[error("You need to select a budget account for this action")]
     BudgetAccountNotSelected,
+    #[error("Funding template not found: {0}")]
+    FundingTemplateNotFound(String),
+    #[error("Funding template already exists: {0}")]
+    FundingTemplateAlreadyExists(String),
+    #[error("Allocation rules can not allocate more than 100% of the income")]
+    AllocationExceedsIncome,
+    #[error("Expense overdraws category {category} by {shortfall}")]
+    Overspending { category: String, shortfall: f64 },
+    #[error("Category is not overspent: {0}")]
+    CategoryNotOverspent(String),
+    #[error("Invalid {field}: {reason}")]
+    InvalidInput { field: String, reason: InvalidReason },
+    #[error("Import profile not found: {0}")]
+    ImportProfileNotFound(String),
+    #[error("Import profile already exists: {0}")]
+    ImportProfileAlreadyExists(String),
+    #[error("Categorization rule not found: {0}")]
+    CategorizationRuleNotFound(String),
+    #[error("Categorization rule already exists: {0}")]
+    CategorizationRuleAlreadyExists(String),
+    #[error("Transaction is a duplicate of transaction {0}")]
+    DuplicateTransaction(i32),
+    #[error("Invalid backup: {0}")]
+    InvalidBackup(String),
+    #[error("Unsupported backup version: {0}")]
+    UnsupportedBackupVersion(u32),
+    #[error("A backup can only be restored into an empty database")]
+    RestoreTargetNotEmpty,
+    #[error("Failed to read or write: {0}")]
+    IoError(String),
     #[error("Help: {0}")]
     ReturnWithHelpMessage(String),
-}
\ No newline at end of file
+}
+/// Why a value was rejected by validation
+#[derive(thiserror::Error, Debug, PartialEq, Clone)]
+pub enum InvalidReason {
+    #[error("not a number")]
+    NotANumber,
+    #[error("must be finite")]
+    Infinite,
+    #[error("can not be negative")]
+    Negative,
+    #[error("can not be zero")]
+    Zero,
+    #[error("can not be empty")]
+    Empty,
+    #[error("out of range")]
+    OutOfRange,
+    #[error("not a valid regular expression")]
+    NotAPattern,
+    #[error("not a valid date format")]
+    NotADateFormat,
+    #[error("has a character the file format gives a meaning to")]
+    ReservedCharacter,
+}
@@ -0,0 +1,316 @@
+use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+use crate::budgeting::category::CategoryModel;
+use crate::budgeting::validation;
+use crate::schema::categorization_rules;
+use crate::DbConnection;
+use diesel::prelude::*;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+
+/// "Payee matches `^AMZN` → payee Amazon, category Shopping, tag online". Rules of a
+/// budget account are tried from the highest priority down, the payee and the category
+/// come from the first matching rule setting them, tags from every matching rule.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable)]
+#[diesel(table_name = categorization_rules)]
+pub struct CategorizationRule {
+    id: i32,
+    budget_account_id: i32,
+    name: String,
+    priority: i32,
+    payee_pattern: Option<String>,
+    note_pattern: Option<String>,
+    payee: Option<String>,
+    category_id: Option<i32>,
+    tag: Option<String>,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = categorization_rules)]
+pub struct NewCategorizationRule<'a> {
+    budget_account_id: i32,
+    name: &'a str,
+    priority: i32,
+    payee_pattern: Option<&'a str>,
+    note_pattern: Option<&'a str>,
+    payee: Option<&'a str>,
+    category_id: Option<i32>,
+    tag: Option<&'a str>,
+}
+
+impl CategorizationRule {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn budget_account_id(&self) -> i32 {
+        self.budget_account_id
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    pub fn payee_pattern(&self) -> Option<String> {
+        self.payee_pattern.clone()
+    }
+
+    pub fn note_pattern(&self) -> Option<String> {
+        self.note_pattern.clone()
+    }
+
+    /// payee given to matching transactions
+    pub fn payee(&self) -> Option<String> {
+        self.payee.clone()
+    }
+
+    /// category given to matching expenses
+    pub fn category_id(&self) -> Option<i32> {
+        self.category_id
+    }
+
+    /// tag added to matching transactions
+    pub fn tag(&self) -> Option<String> {
+        self.tag.clone()
+    }
+}
+
+/// What the rules of a budget account make of a payee and a note
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone)]
+pub struct RuleOutcome {
+    /// names of the matching rules, highest priority first
+    pub rules: Vec<String>,
+    pub payee: Option<String>,
+    pub category_id: Option<i32>,
+    pub tags: Vec<String>,
+}
+
+/// A change the rules would make to a transaction already saved
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct RuleChange {
+    pub transaction_id: i32,
+    pub rules: Vec<String>,
+    /// current and new payee
+    pub payee: Option<(String, String)>,
+    /// current and new category name
+    pub category: Option<(String, String)>,
+    /// tags the transaction does not have yet
+    pub tags: Vec<String>,
+}
+
+/// The rules of a budget account with their patterns compiled, in the order they are tried
+pub struct RuleSet {
+    rules: Vec<(CategorizationRule, Option<Regex>, Option<Regex>)>,
+}
+
+impl RuleSet {
+    /// a rule whose stored pattern does not compile is left out, it would match nothing
+    pub(crate) fn load(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+    ) -> Result<RuleSet, BudgetingErrors> {
+        let compile = |p: &Option<String>| match p {
+            Some(p) => Regex::new(p).ok().map(Some),
+            None => Some(None),
+        };
+        let rules = CategorizationRuleModel::find_all(conn, _budget_account_id)?
+            .into_iter()
+            .filter_map(|r| {
+                let payee = compile(&r.payee_pattern)?;
+                let note = compile(&r.note_pattern)?;
+                Some((r, payee, note))
+            })
+            .collect();
+        Ok(RuleSet { rules })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    pub fn apply(&self, payee: &str, note: &str) -> RuleOutcome {
+        let mut outcome = RuleOutcome::default();
+        for (rule, payee_pattern, note_pattern) in &self.rules {
+            let payee_matches = payee_pattern.as_ref().is_none_or(|p| p.is_match(payee));
+            let note_matches = note_pattern.as_ref().is_none_or(|p| p.is_match(note));
+            if !payee_matches || !note_matches {
+                continue;
+            }
+            outcome.rules.push(rule.name());
+            if outcome.payee.is_none() {
+                outcome.payee = rule.payee();
+            }
+            if outcome.category_id.is_none() {
+                outcome.category_id = rule.category_id;
+            }
+            if let Some(tag) = &rule.tag {
+                if !outcome.tags.iter().any(|t| t.eq_ignore_ascii_case(tag)) {
+                    outcome.tags.push(tag.clone());
+                }
+            }
+        }
+        outcome
+    }
+}
+
+/// Only way to create a categorization rule, a rule belongs to one budget account
+pub struct CategorizationRuleBuilder {
+    name: String,
+    priority: i32,
+    payee_pattern: Option<String>,
+    note_pattern: Option<String>,
+    payee: Option<String>,
+    category: Option<String>,
+    tag: Option<String>,
+    budget_account_id: i32,
+    conn: DbConnection,
+}
+
+impl CategorizationRuleBuilder {
+    pub(crate) fn new(conn: DbConnection, budget_account_id: i32, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            priority: 0,
+            payee_pattern: None,
+            note_pattern: None,
+            payee: None,
+            category: None,
+            tag: None,
+            budget_account_id,
+            conn,
+        }
+    }
+
+    /// rules with a higher priority are tried first, 0 by default
+    pub fn priority(&mut self, priority: i32) -> &mut Self {
+        self.priority = priority;
+        self
+    }
+
+    /// regular expression the payee has to match, `(?i)` makes it case insensitive
+    pub fn payee_matches(&mut self, pattern: &str) -> &mut Self {
+        self.payee_pattern = Some(pattern.to_string());
+        self
+    }
+
+    /// regular expression the note has to match
+    pub fn note_matches(&mut self, pattern: &str) -> &mut Self {
+        self.note_pattern = Some(pattern.to_string());
+        self
+    }
+
+    pub fn set_payee(&mut self, payee: &str) -> &mut Self {
+        self.payee = Some(payee.to_string());
+        self
+    }
+
+    /// only expenses are moved, incomes always go to the default category
+    pub fn set_category(&mut self, category: &str) -> &mut Self {
+        self.category = Some(category.to_string());
+        self
+    }
+
+    /// a rule adds one tag, setting another one replaces it
+    pub fn set_tag(&mut self, tag: &str) -> &mut Self {
+        self.tag = Some(tag.to_string());
+        self
+    }
+
+    pub fn done(&self) -> Result<CategorizationRule, BudgetingErrors> {
+        let name = validation::not_empty("name", &self.name)?;
+        if self.payee_pattern.is_none() && self.note_pattern.is_none() {
+            return Err(BudgetingErrors::InvalidInput {
+                field: "payee_pattern".to_string(),
+                reason: InvalidReason::Empty,
+            });
+        }
+        if let Some(p) = &self.payee_pattern {
+            validation::pattern("payee_pattern", p)?;
+        }
+        if let Some(p) = &self.note_pattern {
+            validation::pattern("note_pattern", p)?;
+        }
+        if self.payee.is_none() && self.category.is_none() && self.tag.is_none() {
+            return Err(BudgetingErrors::InvalidInput {
+                field: "payee".to_string(),
+                reason: InvalidReason::Empty,
+            });
+        }
+        if let Some(p) = &self.payee {
+            validation::not_empty("payee", p)?;
+        }
+        if let Some(t) = &self.tag {
+            validation::not_empty("tag", t)?;
+        }
+        let mut _conn = (*self.conn).borrow_mut();
+        let conn = _conn.deref_mut();
+        if CategorizationRuleModel::load_by_name(conn, self.budget_account_id, name).is_ok() {
+            return Err(BudgetingErrors::CategorizationRuleAlreadyExists(name.to_string()));
+        }
+        let category_id = match &self.category {
+            Some(c) => Some(CategoryModel::find_by_name(conn, c)?.id()),
+            None => None,
+        };
+        let new_rule = NewCategorizationRule {
+            budget_account_id: self.budget_account_id,
+            name,
+            priority: self.priority,
+            payee_pattern: self.payee_pattern.as_deref(),
+            note_pattern: self.note_pattern.as_deref(),
+            payee: self.payee.as_deref().map(str::trim),
+            category_id,
+            tag: self.tag.as_deref().map(str::trim),
+        };
+        Ok(save_model!(conn, categorization_rules, new_rule, CategorizationRule)?)
+    }
+}
+
+pub struct CategorizationRuleModel;
+
+impl CategorizationRuleModel {
+    /// highest priority first, rules with the same priority in the order they were created
+    pub(crate) fn find_all(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+    ) -> Result<Vec<CategorizationRule>, BudgetingErrors> {
+        imp_db!(categorization_rules);
+        categorization_rules
+            .filter(budget_account_id.eq(_budget_account_id))
+            .order((priority.desc(), id.asc()))
+            .load::<CategorizationRule>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn load_by_name(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        rule_name: &str,
+    ) -> Result<CategorizationRule, BudgetingErrors> {
+        imp_db!(categorization_rules);
+        match categorization_rules
+            .filter(budget_account_id.eq(_budget_account_id))
+            .filter(name.eq(rule_name))
+            .first::<CategorizationRule>(conn)
+        {
+            Ok(r) => Ok(r),
+            Err(diesel::result::Error::NotFound) => {
+                Err(BudgetingErrors::CategorizationRuleNotFound(rule_name.to_string()))
+            }
+            Err(e) => Err(BudgetingErrors::UnspecifiedDatabaseError(e)),
+        }
+    }
+
+    pub(crate) fn delete(
+        conn: &mut SqliteConnection,
+        rule_id: i32,
+    ) -> Result<usize, BudgetingErrors> {
+        imp_db!(categorization_rules);
+        diesel::delete(categorization_rules.find(rule_id))
+            .execute(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+}
@@ -1,7 +1,9 @@
 use crate::budgeting::budgeting_errors::BudgetingErrors;
 use crate::budgeting::transaction::{Transaction, TransactionModel, TransactionType};
+use crate::budgeting::validation;
 use crate::schema::categories;
 use crate::DbConnection;
+use chrono::NaiveDate;
 use diesel::prelude::*;
 use diesel::result::DatabaseErrorKind;
 use diesel::result::Error::DatabaseError;
@@ -88,6 +90,8 @@ impl CategoryBuilder {
 
     // put the transaction category details together and save to database, returned the new category
     pub fn done(&self) -> Result<Category, BudgetingErrors> {
+        validation::not_empty("name", &self.name)?;
+        validation::non_negative("allocated", self.allocated)?;
         let mut t = NewTransactionCategory {
             name: self.name.as_str(),
             allocated: self.allocated,
@@ -116,12 +120,14 @@ impl CategoryModel {
         new_name: Option<String>,
         new_allocated: Option<f64>,
     ) -> Result<usize, BudgetingErrors> {
+        let change_set = CategoryForm {
+            name: new_name,
+            allocated: new_allocated,
+        };
+        validation::category_form(&change_set)?;
         imp_db!(categories);
         let r = diesel::update(categories.find(category_id))
-            .set(&CategoryForm {
-                name: new_name,
-                allocated: new_allocated,
-            })
+            .set(&change_set)
             .execute(conn);
         match r {
             Ok(a) => Ok(a),
@@ -141,6 +147,18 @@ impl CategoryModel {
         }
     }
 
+    pub(crate) fn load_by_id(
+        conn: &mut SqliteConnection,
+        cid: i32,
+    ) -> Result<Category, BudgetingErrors> {
+        imp_db!(categories);
+        match categories.find(cid).first::<Category>(conn) {
+            Ok(c) => Ok(c),
+            Err(diesel::result::Error::NotFound) => Err(BudgetingErrors::CategoryNotFound),
+            Err(e) => Err(BudgetingErrors::UnspecifiedDatabaseError(e)),
+        }
+    }
+
     pub(crate) fn delete(
         conn: &mut SqliteConnection,
         category_id: i32,
@@ -179,9 +197,18 @@ impl CategoryModel {
         conn: &mut SqliteConnection,
         _budget_account_id: Option<i32>,
         category: &str,
+    ) -> Result<f64, BudgetingErrors> {
+        CategoryModel::_balance_as_of(conn, _budget_account_id, category, None)
+    }
+
+    pub(crate) fn _balance_as_of(
+        conn: &mut SqliteConnection,
+        _budget_account_id: Option<i32>,
+        category: &str,
+        as_of: Option<NaiveDate>,
     ) -> Result<f64, BudgetingErrors> {
         let c = CategoryModel::find_by_name(conn, category)?;
-        Ok(TransactionModel::total(conn, None, Some(c.id), None))
+        Ok(TransactionModel::total_as_of(conn, None, Some(c.id), None, as_of))
     }
     pub fn category(&mut self) -> Category {
         imp_db!(categories);
@@ -231,6 +258,17 @@ impl CategoryModel {
         )
     }
 
+    /// balance at the end of the given day
+    pub fn balance_as_of(&mut self, date: NaiveDate) -> f64 {
+        TransactionModel::total_as_of(
+            gc!(self.conn),
+            None,
+            Some(self.category.id),
+            None,
+            Some(date),
+        )
+    }
+
     pub fn transactions(&mut self) -> Vec<Transaction> {
         TransactionModel::find_all(
             gc!(self.conn),
@@ -0,0 +1,121 @@
+//! Suggesting the category of an expense from the expenses already categorized, with a
+//! naive Bayes classifier over the words of the payee and the note and the size of the
+//! amount. Nothing is stored, the classifier is trained from the transactions every time.
+
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::category::CategoryModel;
+use crate::budgeting::transaction::{Transaction, TransactionType};
+use crate::DEFAULT_CATEGORY;
+use diesel::SqliteConnection;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::sync::LazyLock;
+
+static WORD: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[a-z0-9]+").unwrap());
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CategorySuggestion {
+    pub category_id: i32,
+    pub category: String,
+    /// from 0 to 1, the confidences of all the suggestions add up to 1
+    pub confidence: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct CategoryClassifier {
+    /// expenses seen per category
+    documents: HashMap<i32, usize>,
+    /// how many times a feature was seen per category
+    features: HashMap<i32, HashMap<String, usize>>,
+    /// all the features seen per category
+    totals: HashMap<i32, usize>,
+    vocabulary: HashSet<String>,
+}
+
+impl CategoryClassifier {
+    /// words of the payee and of the note, and the power of two the amount is in, so
+    /// 30 and 50 look alike but 30 and 3000 do not
+    fn features(payee: &str, note: &str, amount: f64) -> Vec<String> {
+        let mut features: Vec<String> = WORD
+            .find_iter(&payee.to_lowercase())
+            .map(|m| format!("payee:{}", m.as_str()))
+            .collect();
+        features.extend(
+            WORD.find_iter(&note.to_lowercase())
+                .filter(|m| m.as_str().len() > 1)
+                .map(|m| format!("note:{}", m.as_str())),
+        );
+        let bucket = amount.abs().max(1.).log2().floor() as i32;
+        features.push(format!("amount:{bucket}"));
+        features
+    }
+
+    pub fn train(&mut self, category_id: i32, payee: &str, note: &str, amount: f64) {
+        *self.documents.entry(category_id).or_default() += 1;
+        let counts = self.features.entry(category_id).or_default();
+        for feature in CategoryClassifier::features(payee, note, amount) {
+            *counts.entry(feature.clone()).or_default() += 1;
+            *self.totals.entry(category_id).or_default() += 1;
+            self.vocabulary.insert(feature);
+        }
+    }
+
+    /// trained with the expenses of the budget account that are not in the default category
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+    ) -> Result<CategoryClassifier, BudgetingErrors> {
+        imp_db!(transactions);
+        let default = CategoryModel::find_by_name(conn, DEFAULT_CATEGORY)?;
+        let expenses = transactions
+            .filter(budget_account_id.eq(_budget_account_id))
+            .filter(transaction_type_id.eq(i32::from(TransactionType::Expense)))
+            .filter(category_id.ne(default.id()))
+            .load::<Transaction>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)?;
+        let mut classifier = CategoryClassifier::default();
+        for t in expenses {
+            classifier.train(t.category_id(), &t.payee(), &t.note(), t.amount());
+        }
+        Ok(classifier)
+    }
+
+    /// category ids with their probability, most likely first. Empty when nothing was
+    /// learned yet or when no word of the payee or the note was ever seen, the amount
+    /// alone says too little and the probabilities only compare the categories learned.
+    pub fn rank(&self, payee: &str, note: &str, amount: f64) -> Vec<(i32, f64)> {
+        let all: usize = self.documents.values().sum();
+        if all == 0 {
+            return vec![];
+        }
+        let features = CategoryClassifier::features(payee, note, amount);
+        if !features
+            .iter()
+            .any(|f| !f.starts_with("amount:") && self.vocabulary.contains(f))
+        {
+            return vec![];
+        }
+        let vocabulary = self.vocabulary.len() as f64;
+        let mut scores: Vec<(i32, f64)> = self
+            .documents
+            .iter()
+            .map(|(category, documents)| {
+                let counts = &self.features[category];
+                let total = self.totals[category] as f64;
+                // Laplace smoothing, so a word never seen in a category does not rule it out
+                let likelihood: f64 = features
+                    .iter()
+                    .map(|f| ((*counts.get(f).unwrap_or(&0) as f64 + 1.) / (total + vocabulary)).ln())
+                    .sum();
+                (*category, (*documents as f64 / all as f64).ln() + likelihood)
+            })
+            .collect();
+        // log scores to probabilities, shifted by the best one so exp does not underflow
+        let best = scores.iter().map(|(_, s)| *s).fold(f64::NEG_INFINITY, f64::max);
+        let sum: f64 = scores.iter().map(|(_, s)| (s - best).exp()).sum();
+        scores.iter_mut().for_each(|(_, s)| *s = (*s - best).exp() / sum);
+        scores.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        scores
+    }
+}
@@ -0,0 +1,200 @@
+//! Finding transactions that were entered or imported twice, e.g. when two bank
+//! statements overlap or when an expense entered by hand shows up in a statement.
+//! A candidate is an income or expense of the same budget account with the same sign,
+//! a close amount and a date a few days apart, scored on how close date, amount and
+//! payee are.
+
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::import::ImportedTransaction;
+use crate::budgeting::reports::start_of_day;
+use crate::budgeting::transaction::{Transaction, TransactionType};
+use crate::speller::similarity;
+use chrono::{Days, NaiveDate, NaiveDateTime};
+use diesel::SqliteConnection;
+use serde::{Deserialize, Serialize};
+
+const AMOUNT_WEIGHT: f64 = 0.4;
+const DATE_WEIGHT: f64 = 0.3;
+const PAYEE_WEIGHT: f64 = 0.3;
+
+/// largest `max_days` of the settings, a year
+pub const MAX_DUPLICATE_DAYS: u32 = 366;
+
+/// What happens to a transaction that looks like one already saved
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum DuplicateMode {
+    /// no check, every transaction is saved
+    #[default]
+    Off,
+    /// the transaction is not saved, entering it fails with
+    /// `BudgetingErrors::DuplicateTransaction` and importing it skips the row
+    Skip,
+    /// the transaction is saved with `duplicate_of_id` set to the one it matches
+    Flag,
+    /// the transaction is not saved, its note and FITID fill the ones missing on the
+    /// transaction it matches
+    Merge,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct DuplicateSettings {
+    /// how many days apart two duplicates can be, up to `MAX_DUPLICATE_DAYS`
+    pub max_days: u32,
+    /// difference of the amounts relative to the amount, 0.01 is 1%, not negative
+    pub amount_tolerance: f64,
+    /// score from 0 to 1 a candidate needs to be taken as a duplicate
+    pub min_score: f64,
+}
+
+impl Default for DuplicateSettings {
+    fn default() -> Self {
+        DuplicateSettings {
+            max_days: 3,
+            amount_tolerance: 0.,
+            min_score: 0.75,
+        }
+    }
+}
+
+/// The transaction to look duplicates for, amount is signed, negative for expenses
+#[derive(Debug, PartialEq, Clone)]
+pub struct DuplicateProbe<'a> {
+    pub date_created: NaiveDateTime,
+    pub amount: f64,
+    pub payee: &'a str,
+    pub fitid: Option<&'a str>,
+}
+
+impl<'a> From<&'a ImportedTransaction> for DuplicateProbe<'a> {
+    fn from(t: &'a ImportedTransaction) -> Self {
+        DuplicateProbe {
+            date_created: t.date_created,
+            amount: t.amount,
+            payee: &t.payee,
+            fitid: t.fitid.as_deref(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct DuplicateCandidate {
+    pub transaction: Transaction,
+    /// from 0 to 1, 1 for the same amount and payee on the same day
+    pub score: f64,
+    pub days_apart: i64,
+    pub payee_similarity: f64,
+}
+
+pub struct DuplicateMatcher;
+
+impl DuplicateMatcher {
+    /// `None` when the transaction can not be a duplicate of the probe at all
+    pub(crate) fn score(
+        probe: &DuplicateProbe,
+        transaction: &Transaction,
+        settings: &DuplicateSettings,
+    ) -> Option<DuplicateCandidate> {
+        if probe.amount == 0. || probe.amount.signum() != transaction.amount().signum() {
+            return None;
+        }
+        // two different ids given by the bank are two different transactions
+        if let (Some(a), Some(b)) = (probe.fitid, transaction.fitid()) {
+            if a != b {
+                return None;
+            }
+        }
+        let days_apart = (transaction.date_created().date() - probe.date_created.date())
+            .num_days()
+            .abs();
+        if days_apart > settings.max_days as i64 {
+            return None;
+        }
+        let difference = (transaction.amount() - probe.amount).abs() / probe.amount.abs();
+        let amount_score = if difference < 1e-9 {
+            1.
+        } else if difference <= settings.amount_tolerance {
+            1. - difference / settings.amount_tolerance
+        } else {
+            return None;
+        };
+        let date_score = 1. - days_apart as f64 / (settings.max_days as f64 + 1.);
+        let payee_similarity = similarity(probe.payee, &transaction.payee());
+        Some(DuplicateCandidate {
+            transaction: transaction.clone(),
+            score: AMOUNT_WEIGHT * amount_score + DATE_WEIGHT * date_score + PAYEE_WEIGHT * payee_similarity,
+            days_apart,
+            payee_similarity,
+        })
+    }
+
+    /// candidates scoring at least `min_score`, best first
+    pub(crate) fn find(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        probe: &DuplicateProbe,
+        settings: &DuplicateSettings,
+    ) -> Result<Vec<DuplicateCandidate>, BudgetingErrors> {
+        imp_db!(transactions);
+        let transaction_type = if probe.amount < 0. {
+            TransactionType::Expense
+        } else {
+            TransactionType::Income
+        };
+        let day = probe.date_created.date();
+        let window = Days::new(settings.max_days as u64);
+        let first = day.checked_sub_days(window).unwrap_or(NaiveDate::MIN);
+        let mut query = transactions
+            .filter(budget_account_id.eq(_budget_account_id))
+            .filter(transaction_type_id.eq(i32::from(transaction_type)))
+            .filter(date_created.ge(start_of_day(first)))
+            .into_boxed();
+        // no upper bound when the window reaches the last date chrono knows
+        if let Some(after) = day.checked_add_days(window).and_then(|d| d.succ_opt()) {
+            query = query.filter(date_created.lt(start_of_day(after)));
+        }
+        let found = query
+            .order(id.asc())
+            .load::<Transaction>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)?;
+        let mut candidates: Vec<DuplicateCandidate> = found
+            .iter()
+            .filter_map(|t| DuplicateMatcher::score(probe, t, settings))
+            .filter(|c| c.score >= settings.min_score)
+            .collect();
+        candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+        Ok(candidates)
+    }
+
+    /// best candidate that is not one of `exclude`
+    pub(crate) fn best_match(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        probe: &DuplicateProbe,
+        settings: &DuplicateSettings,
+        exclude: &[i32],
+    ) -> Result<Option<DuplicateCandidate>, BudgetingErrors> {
+        Ok(DuplicateMatcher::find(conn, _budget_account_id, probe, settings)?
+            .into_iter()
+            .find(|c| !exclude.contains(&c.transaction.id())))
+    }
+
+    /// fills the note and the FITID of `existing` when it has none, returns it updated
+    pub(crate) fn merge(
+        conn: &mut SqliteConnection,
+        existing: &Transaction,
+        _note: &str,
+        _fitid: Option<&str>,
+    ) -> Result<Transaction, BudgetingErrors> {
+        imp_db!(transactions);
+        let merged_note = if existing.note().is_empty() { _note.to_string() } else { existing.note() };
+        let merged_fitid = existing.fitid().or(_fitid.map(|f| f.to_string()));
+        diesel::update(transactions.find(existing.id()))
+            .set((note.eq(merged_note), fitid.eq(merged_fitid)))
+            .execute(conn)
+            .map_err(|_| BudgetingErrors::TransactionUpdateFailed)?;
+        transactions
+            .find(existing.id())
+            .first::<Transaction>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+}
@@ -0,0 +1,356 @@
+use crate::budgeting::budget_account::BudgetAccount;
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::category::Category;
+use crate::budgeting::reports::budget_vs_actual::BudgetVsActualReport;
+use crate::budgeting::reports::category_monthly::MonthlyCategoryReport;
+use crate::budgeting::tag::TagModel;
+use crate::budgeting::transaction::{Transaction, TransactionType};
+use crate::budgeting::transaction_query::TransactionQuery;
+use crate::budgeting::validation;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::io::Write;
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+
+/// How amounts are written
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum AmountSign {
+    /// as stored, expenses and transfers out are negative
+    #[default]
+    AsStored,
+    /// expenses and transfers out are positive, like a credit card statement
+    Inverted,
+    /// always positive, the type column tells the direction
+    Absolute,
+}
+
+impl AmountSign {
+    pub fn apply(&self, amount: f64) -> f64 {
+        match self {
+            AmountSign::AsStored => amount,
+            AmountSign::Inverted => -amount,
+            AmountSign::Absolute => amount.abs(),
+        }
+    }
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum TransactionColumn {
+    Id,
+    Date,
+    Payee,
+    Note,
+    Amount,
+    Type,
+    Category,
+    BudgetAccount,
+    Tags,
+}
+
+impl TransactionColumn {
+    pub fn header(&self) -> &'static str {
+        match self {
+            TransactionColumn::Id => "id",
+            TransactionColumn::Date => "date",
+            TransactionColumn::Payee => "payee",
+            TransactionColumn::Note => "note",
+            TransactionColumn::Amount => "amount",
+            TransactionColumn::Type => "type",
+            TransactionColumn::Category => "category",
+            TransactionColumn::BudgetAccount => "budget_account",
+            TransactionColumn::Tags => "tags",
+        }
+    }
+}
+
+/// Columns, date format and sign convention of a transaction export
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ExportOptions {
+    pub format: ExportFormat,
+    /// written in this order
+    pub columns: Vec<TransactionColumn>,
+    /// chrono format string, e.g. `%d/%m/%Y`
+    pub date_format: String,
+    pub amount_sign: AmountSign,
+}
+
+impl Default for ExportOptions {
+    fn default() -> Self {
+        ExportOptions {
+            format: ExportFormat::Csv,
+            columns: vec![
+                TransactionColumn::Date,
+                TransactionColumn::Payee,
+                TransactionColumn::Note,
+                TransactionColumn::Amount,
+                TransactionColumn::Type,
+                TransactionColumn::Category,
+                TransactionColumn::BudgetAccount,
+            ],
+            date_format: "%Y-%m-%d".to_string(),
+            amount_sign: AmountSign::AsStored,
+        }
+    }
+}
+
+/// Totals of a category in one budget account, amounts signed as stored
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct CategorySummary {
+    pub category: String,
+    pub allocated: f64,
+    pub income: f64,
+    pub expense: f64,
+    pub transfer_in: f64,
+    pub transfer_out: f64,
+    pub balance: f64,
+}
+
+impl CategorySummary {
+    /// every category, with the totals of its transactions in the budget account
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        budget_account_id: i32,
+    ) -> Result<Vec<CategorySummary>, BudgetingErrors> {
+        let all_categories = {
+            imp_db!(categories);
+            categories.order(id.asc()).load::<Category>(conn)?
+        };
+        let mut query = TransactionQuery::new();
+        query.budget_account_id(budget_account_id);
+        let mut totals: HashMap<i32, CategorySummary> = HashMap::new();
+        for t in query.load(conn)? {
+            let s = totals.entry(t.category_id()).or_default();
+            match TransactionType::from(t.transfer_type_id()) {
+                TransactionType::Income => s.income += t.amount(),
+                TransactionType::Expense => s.expense += t.amount(),
+                TransactionType::TransferIn => s.transfer_in += t.amount(),
+                TransactionType::TransferOut => s.transfer_out += t.amount(),
+            }
+            s.balance += t.amount();
+        }
+        Ok(all_categories
+            .iter()
+            .map(|c| {
+                let mut s = totals.remove(&c.id()).unwrap_or_default();
+                s.category = c.name();
+                s.allocated = c.allocated();
+                s
+            })
+            .collect())
+    }
+}
+
+/// One cell of `MonthlyCategoryReport`, flat so it can be written as a CSV row
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CategoryMonthRecord {
+    pub category: String,
+    pub month: NaiveDate,
+    pub income: f64,
+    pub expense: f64,
+    pub transfer_in: f64,
+    pub transfer_out: f64,
+    pub ending_balance: f64,
+}
+
+/// One line of `BudgetVsActualReport`, flat so it can be written as a CSV row
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BudgetVsActualRecord {
+    pub month: NaiveDate,
+    pub category: String,
+    pub allocated: f64,
+    pub funded: f64,
+    pub spent: f64,
+    pub remaining: f64,
+    pub variance: f64,
+    pub variance_percentage: Option<f64>,
+}
+
+impl MonthlyCategoryReport {
+    pub fn records(&self) -> Vec<CategoryMonthRecord> {
+        self.rows
+            .iter()
+            .flat_map(|r| {
+                r.months.iter().map(|m| CategoryMonthRecord {
+                    category: r.category.clone(),
+                    month: m.month,
+                    income: m.income,
+                    expense: m.expense,
+                    transfer_in: m.transfer_in,
+                    transfer_out: m.transfer_out,
+                    ending_balance: m.ending_balance,
+                })
+            })
+            .collect()
+    }
+}
+
+impl BudgetVsActualReport {
+    pub fn records(&self) -> Vec<BudgetVsActualRecord> {
+        self.periods
+            .iter()
+            .flat_map(|p| {
+                p.lines.iter().map(|l| BudgetVsActualRecord {
+                    month: p.month,
+                    category: l.category.clone(),
+                    allocated: l.allocated,
+                    funded: l.funded,
+                    spent: l.spent,
+                    remaining: l.remaining,
+                    variance: l.variance,
+                    variance_percentage: l.variance_percentage,
+                })
+            })
+            .collect()
+    }
+}
+
+fn io_error(e: impl std::fmt::Display) -> BudgetingErrors {
+    BudgetingErrors::IoError(e.to_string())
+}
+
+/// Writes flat records, e.g. report lines or `CategorySummary`, one CSV row or JSON
+/// object each, returns the number of records written.
+pub fn write_records<T: Serialize, W: Write>(
+    records: &[T],
+    format: ExportFormat,
+    writer: W,
+) -> Result<usize, BudgetingErrors> {
+    match format {
+        ExportFormat::Csv => {
+            let mut csv_writer = csv::Writer::from_writer(writer);
+            for r in records {
+                csv_writer.serialize(r).map_err(io_error)?;
+            }
+            csv_writer.flush().map_err(io_error)?;
+        }
+        ExportFormat::Json => serde_json::to_writer_pretty(writer, records).map_err(io_error)?,
+    }
+    Ok(records.len())
+}
+
+/// Writes a whole report, or anything serializable, as JSON
+pub fn write_json<T: Serialize, W: Write>(value: &T, writer: W) -> Result<(), BudgetingErrors> {
+    serde_json::to_writer_pretty(writer, value).map_err(io_error)
+}
+
+pub struct TransactionExporter;
+
+impl TransactionExporter {
+    /// Writes the transactions with category and budget account names instead of ids,
+    /// returns the number of transactions written. Tags are joined with `;` in CSV.
+    pub(crate) fn export<W: Write>(
+        conn: &mut SqliteConnection,
+        transactions: &[Transaction],
+        options: &ExportOptions,
+        writer: W,
+    ) -> Result<usize, BudgetingErrors> {
+        validation::date_format("date_format", &options.date_format)?;
+        let category_names: HashMap<i32, String> = {
+            imp_db!(categories);
+            categories
+                .load::<Category>(conn)?
+                .into_iter()
+                .map(|c| (c.id(), c.name()))
+                .collect()
+        };
+        let account_names: HashMap<i32, String> = {
+            imp_db!(budget_accounts);
+            budget_accounts
+                .load::<BudgetAccount>(conn)
+                .map_err(BudgetingErrors::UnspecifiedDatabaseError)?
+                .into_iter()
+                .map(|b| (b.id(), b.filed_as()))
+                .collect()
+        };
+        let with_tags = options.columns.contains(&TransactionColumn::Tags);
+        let mut rows: Vec<Vec<Value>> = vec![];
+        for t in transactions {
+            let tags: Vec<Value> = if with_tags {
+                TagModel::tags_of(conn, t.id())?
+                    .iter()
+                    .map(|tag| Value::from(tag.name()))
+                    .collect()
+            } else {
+                vec![]
+            };
+            let row = options
+                .columns
+                .iter()
+                .map(|c| match c {
+                    TransactionColumn::Id => Value::from(t.id()),
+                    TransactionColumn::Date => {
+                        Value::from(t.date_created().format(&options.date_format).to_string())
+                    }
+                    TransactionColumn::Payee => Value::from(t.payee()),
+                    TransactionColumn::Note => Value::from(t.note()),
+                    TransactionColumn::Amount => Value::from(options.amount_sign.apply(t.amount())),
+                    TransactionColumn::Type => {
+                        Value::from(String::from(TransactionType::from(t.transfer_type_id())))
+                    }
+                    TransactionColumn::Category => Value::from(
+                        category_names.get(&t.category_id()).cloned().unwrap_or_default(),
+                    ),
+                    TransactionColumn::BudgetAccount => Value::from(
+                        account_names
+                            .get(&t.budget_account_id())
+                            .cloned()
+                            .unwrap_or_default(),
+                    ),
+                    TransactionColumn::Tags => Value::Array(tags.clone()),
+                })
+                .collect();
+            rows.push(row);
+        }
+        match options.format {
+            ExportFormat::Csv => {
+                let mut csv_writer = csv::Writer::from_writer(writer);
+                csv_writer
+                    .write_record(options.columns.iter().map(|c| c.header()))
+                    .map_err(io_error)?;
+                for row in &rows {
+                    csv_writer
+                        .write_record(row.iter().map(csv_cell))
+                        .map_err(io_error)?;
+                }
+                csv_writer.flush().map_err(io_error)?;
+            }
+            ExportFormat::Json => {
+                let objects: Vec<Map<String, Value>> = rows
+                    .into_iter()
+                    .map(|row| {
+                        options
+                            .columns
+                            .iter()
+                            .map(|c| c.header().to_string())
+                            .zip(row)
+                            .collect()
+                    })
+                    .collect();
+                serde_json::to_writer_pretty(writer, &objects).map_err(io_error)?;
+            }
+        }
+        Ok(transactions.len())
+    }
+}
+
+fn csv_cell(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() => format!("{:.2}", f),
+            _ => n.to_string(),
+        },
+        Value::Array(values) => values.iter().map(csv_cell).collect::<Vec<_>>().join(";"),
+        Value::Null => String::new(),
+        v => v.to_string(),
+    }
+}
@@ -0,0 +1,283 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::category::CategoryModel;
+use crate::budgeting::validation;
+use crate::schema::{funding_template_items, funding_templates};
+use crate::DbConnection;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+
+/// A named plan to fund a set of categories, re-applied every time money comes in
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable)]
+#[diesel(table_name = funding_templates)]
+pub struct FundingTemplate {
+    id: i32,
+    name: String,
+}
+
+#[derive(
+    Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable, Associations,
+)]
+#[diesel(belongs_to(FundingTemplate))]
+#[diesel(table_name = funding_template_items)]
+pub struct FundingTemplateItem {
+    id: i32,
+    funding_template_id: i32,
+    category_id: i32,
+    amount: f64,
+    is_percentage: bool,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = funding_templates)]
+pub struct NewFundingTemplate<'a> {
+    name: &'a str,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = funding_template_items)]
+pub struct NewFundingTemplateItem {
+    funding_template_id: i32,
+    category_id: i32,
+    amount: f64,
+    is_percentage: bool,
+}
+
+/// How much a template item moves into its category
+#[derive(Debug, PartialEq, Clone)]
+pub enum FundingAmount {
+    Fixed(f64),
+    /// percentage of the source category balance at the time the template is applied
+    Percentage(f64),
+}
+
+impl FundingTemplate {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn name(&self) -> String {
+        self.name.to_owned()
+    }
+}
+
+impl FundingTemplateItem {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn funding_template_id(&self) -> i32 {
+        self.funding_template_id
+    }
+
+    pub fn category_id(&self) -> i32 {
+        self.category_id
+    }
+
+    pub fn amount(&self) -> f64 {
+        self.amount
+    }
+
+    pub fn is_percentage(&self) -> bool {
+        self.is_percentage
+    }
+
+    pub fn funding_amount(&self) -> FundingAmount {
+        if self.is_percentage {
+            FundingAmount::Percentage(self.amount)
+        } else {
+            FundingAmount::Fixed(self.amount)
+        }
+    }
+
+    /// amount this item asks for, given the balance of the source category
+    pub fn requested(&self, source_balance: f64) -> f64 {
+        match self.funding_amount() {
+            FundingAmount::Fixed(n) => n,
+            FundingAmount::Percentage(p) => source_balance.max(0.) * p / 100.,
+        }
+    }
+}
+
+/// Only way to create a funding template, items are stored in the order they are added,
+/// and funded in that order when the template is applied
+pub struct FundingTemplateBuilder {
+    name: String,
+    items: Vec<(String, FundingAmount)>,
+    conn: DbConnection,
+}
+
+impl FundingTemplateBuilder {
+    pub(crate) fn new(conn: DbConnection, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            items: vec![],
+            conn,
+        }
+    }
+
+    pub fn fixed(&mut self, category: &str, amount: f64) -> &mut Self {
+        self.items
+            .push((category.to_string(), FundingAmount::Fixed(amount)));
+        self
+    }
+
+    pub fn percentage(&mut self, category: &str, percentage: f64) -> &mut Self {
+        self.items
+            .push((category.to_string(), FundingAmount::Percentage(percentage)));
+        self
+    }
+
+    pub fn done(&self) -> Result<FundingTemplate, BudgetingErrors> {
+        validation::not_empty("name", &self.name)?;
+        for (_, funding_amount) in &self.items {
+            match funding_amount {
+                FundingAmount::Fixed(n) => validation::positive("amount", *n)?,
+                FundingAmount::Percentage(p) => validation::percentage("percentage", *p)?,
+            };
+        }
+        let name = self.name.clone();
+        let items = self.items.clone();
+        gc!(self.conn).transaction(|conn| {
+            if FundingTemplateModel::load_by_name(conn, &name).is_ok() {
+                return Err(BudgetingErrors::FundingTemplateAlreadyExists(name.clone()));
+            }
+            let new_template = NewFundingTemplate {
+                name: name.as_str(),
+            };
+            let template = save_model!(conn, funding_templates, new_template, FundingTemplate)?;
+            for (category, funding_amount) in items {
+                let c = CategoryModel::find_by_name(conn, &category)?;
+                let (amount, is_percentage) = match funding_amount {
+                    FundingAmount::Fixed(n) => (n, false),
+                    FundingAmount::Percentage(p) => (p, true),
+                };
+                let item = NewFundingTemplateItem {
+                    funding_template_id: template.id,
+                    category_id: c.id(),
+                    amount,
+                    is_percentage,
+                };
+                diesel::insert_into(funding_template_items::table)
+                    .values(item)
+                    .execute(conn)?;
+            }
+            Ok(template)
+        })
+    }
+}
+
+pub struct FundingTemplateModel {
+    conn: DbConnection,
+    funding_template: FundingTemplate,
+}
+
+impl FundingTemplateModel {
+    pub fn new(conn: DbConnection, funding_template: FundingTemplate) -> Self {
+        Self {
+            conn,
+            funding_template,
+        }
+    }
+
+    pub(crate) fn find_all(
+        conn: &mut SqliteConnection,
+    ) -> Result<Vec<FundingTemplate>, BudgetingErrors> {
+        imp_db!(funding_templates);
+        funding_templates
+            .order(name.asc())
+            .load::<FundingTemplate>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn load_by_name(
+        conn: &mut SqliteConnection,
+        template_name: &str,
+    ) -> Result<FundingTemplate, BudgetingErrors> {
+        imp_db!(funding_templates);
+        match funding_templates
+            .filter(name.eq(template_name))
+            .first::<FundingTemplate>(conn)
+        {
+            Ok(t) => Ok(t),
+            Err(diesel::result::Error::NotFound) => Err(
+                BudgetingErrors::FundingTemplateNotFound(template_name.to_string()),
+            ),
+            Err(e) => Err(BudgetingErrors::UnspecifiedDatabaseError(e)),
+        }
+    }
+
+    pub(crate) fn items_of(
+        conn: &mut SqliteConnection,
+        template_id: i32,
+    ) -> Result<Vec<FundingTemplateItem>, BudgetingErrors> {
+        imp_db!(funding_template_items);
+        funding_template_items
+            .filter(funding_template_id.eq(template_id))
+            .order(id.asc())
+            .load::<FundingTemplateItem>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn delete(
+        conn: &mut SqliteConnection,
+        template_id: i32,
+    ) -> Result<usize, BudgetingErrors> {
+        conn.transaction(|conn| {
+            {
+                imp_db!(funding_template_items);
+                diesel::delete(funding_template_items.filter(funding_template_id.eq(template_id)))
+                    .execute(conn)?;
+            }
+            imp_db!(funding_templates);
+            diesel::delete(funding_templates.find(template_id)).execute(conn)
+        })
+        .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub fn funding_template(&self) -> &FundingTemplate {
+        &self.funding_template
+    }
+
+    pub fn items(&mut self) -> Vec<FundingTemplateItem> {
+        FundingTemplateModel::items_of(gc!(self.conn), self.funding_template.id).unwrap()
+    }
+}
+
+/// One line of the result of applying a funding template
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct FundingLine {
+    pub category: String,
+    pub requested: f64,
+    pub funded: f64,
+}
+
+impl FundingLine {
+    pub fn shortfall(&self) -> f64 {
+        self.requested - self.funded
+    }
+}
+
+/// What happened when a funding template was applied. Categories which got everything
+/// they asked for are in `funded`, the ones that were partially or not funded at all are in `short`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct FundingSummary {
+    pub template: String,
+    pub source: String,
+    pub funded: Vec<FundingLine>,
+    pub short: Vec<FundingLine>,
+}
+
+impl FundingSummary {
+    pub fn total_funded(&self) -> f64 {
+        self.funded
+            .iter()
+            .chain(self.short.iter())
+            .map(|l| l.funded)
+            .sum()
+    }
+
+    pub fn total_shortfall(&self) -> f64 {
+        self.short.iter().map(|l| l.shortfall()).sum()
+    }
+}
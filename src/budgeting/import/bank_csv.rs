@@ -0,0 +1,369 @@
+use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+use crate::budgeting::import::{parse_amount, parse_date_with_format, ImportedTransaction, ParsedRow};
+use crate::budgeting::validation;
+use crate::schema::import_profiles;
+use crate::DbConnection;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::io::Read;
+use std::ops::DerefMut;
+
+/// How the CSV export of a bank is laid out. Columns are counted from 0. Amounts come
+/// either from one signed column or from a debit and a credit column.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable)]
+#[diesel(table_name = import_profiles)]
+pub struct ImportProfile {
+    id: i32,
+    budget_account_id: i32,
+    name: String,
+    delimiter: String,
+    header_rows: i32,
+    date_column: i32,
+    date_format: String,
+    amount_column: Option<i32>,
+    debit_column: Option<i32>,
+    credit_column: Option<i32>,
+    payee_column: i32,
+    memo_column: Option<i32>,
+    category_column: Option<i32>,
+    decimal_separator: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = import_profiles)]
+pub struct NewImportProfile<'a> {
+    budget_account_id: i32,
+    name: &'a str,
+    delimiter: String,
+    header_rows: i32,
+    date_column: i32,
+    date_format: &'a str,
+    amount_column: Option<i32>,
+    debit_column: Option<i32>,
+    credit_column: Option<i32>,
+    payee_column: i32,
+    memo_column: Option<i32>,
+    category_column: Option<i32>,
+    decimal_separator: String,
+}
+
+impl ImportProfile {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn budget_account_id(&self) -> i32 {
+        self.budget_account_id
+    }
+
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn delimiter(&self) -> char {
+        self.delimiter.chars().next().unwrap_or(',')
+    }
+
+    pub fn header_rows(&self) -> usize {
+        self.header_rows as usize
+    }
+
+    pub fn date_format(&self) -> String {
+        self.date_format.clone()
+    }
+
+    pub fn decimal_separator(&self) -> char {
+        self.decimal_separator.chars().next().unwrap_or('.')
+    }
+
+    /// amount of the record, debits are negative
+    fn amount(&self, record: &csv::StringRecord) -> Result<Option<f64>, String> {
+        let cell = |column: i32| record.get(column as usize).unwrap_or("").trim();
+        let parse = |value: &str| {
+            parse_amount(value, self.decimal_separator())
+                .ok_or_else(|| format!("invalid amount: {value}"))
+        };
+        if let Some(column) = self.amount_column {
+            let value = cell(column);
+            return if value.is_empty() { Ok(None) } else { parse(value).map(Some) };
+        }
+        let debit = self.debit_column.map(cell).unwrap_or("");
+        let credit = self.credit_column.map(cell).unwrap_or("");
+        let mut amount = None;
+        if !debit.is_empty() {
+            amount = Some(-parse(debit)?.abs());
+        }
+        if !credit.is_empty() {
+            amount = Some(amount.unwrap_or(0.) + parse(credit)?.abs());
+        }
+        Ok(amount)
+    }
+
+    fn parse_record(&self, record: &csv::StringRecord) -> Result<Option<ImportedTransaction>, String> {
+        let cell = |column: i32| record.get(column as usize).unwrap_or("").trim().to_string();
+        let date = cell(self.date_column);
+        let date_created = parse_date_with_format(&date, &self.date_format)
+            .ok_or_else(|| format!("invalid date: {date}"))?;
+        let amount = match self.amount(record)? {
+            Some(a) if a != 0. => a,
+            _ => return Ok(None),
+        };
+        let note = self.memo_column.map(cell).unwrap_or_default();
+        let mut payee = cell(self.payee_column);
+        if payee.is_empty() {
+            payee = note.clone();
+        }
+        let category = self.category_column.map(cell).filter(|c| !c.is_empty());
+        Ok(Some(ImportedTransaction {
+            date_created,
+            amount,
+            payee,
+            note,
+            category,
+            fitid: None,
+        }))
+    }
+
+    /// Reads every line after the header rows. Empty lines and lines without an
+    /// amount are skipped, lines that can not be read are reported as failed.
+    pub fn parse<R: Read>(&self, mut reader: R) -> Result<Vec<ParsedRow>, BudgetingErrors> {
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| BudgetingErrors::IoError(e.to_string()))?;
+        // csv skips blank lines and reports the line after a blank one as the blank
+        // one, so lines are counted from the byte offset instead
+        let line_at = |position: Option<&csv::Position>, fallback: usize| {
+            position.map_or(fallback, |p| {
+                let start = p.byte() as usize;
+                let blank = data[start..]
+                    .iter()
+                    .take_while(|b| **b == b'\n' || **b == b'\r')
+                    .filter(|b| **b == b'\n')
+                    .count();
+                data[..start].iter().filter(|b| **b == b'\n').count() + blank + 1
+            })
+        };
+        let mut csv_reader = csv::ReaderBuilder::new()
+            .delimiter(self.delimiter() as u8)
+            .has_headers(false)
+            .flexible(true)
+            .from_reader(data.as_slice());
+        let mut rows = vec![];
+        for (i, record) in csv_reader.records().enumerate() {
+            if i < self.header_rows() {
+                continue;
+            }
+            let record = match record {
+                Ok(r) => r,
+                Err(e) => {
+                    rows.push(ParsedRow::Failed {
+                        line: line_at(e.position(), i + 1),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let line = line_at(record.position(), i + 1);
+            if record.iter().all(|c| c.trim().is_empty()) {
+                rows.push(ParsedRow::Skipped {
+                    line,
+                    reason: "empty line".to_string(),
+                });
+                continue;
+            }
+            rows.push(match self.parse_record(&record) {
+                Ok(Some(transaction)) => ParsedRow::Transaction { line, transaction },
+                Ok(None) => ParsedRow::Skipped {
+                    line,
+                    reason: "no amount".to_string(),
+                },
+                Err(error) => ParsedRow::Failed { line, error },
+            });
+        }
+        Ok(rows)
+    }
+}
+
+/// Only way to create an import profile, a profile belongs to one budget account
+pub struct ImportProfileBuilder {
+    name: String,
+    delimiter: char,
+    header_rows: usize,
+    date_column: Option<usize>,
+    date_format: String,
+    amount_column: Option<usize>,
+    debit_column: Option<usize>,
+    credit_column: Option<usize>,
+    payee_column: Option<usize>,
+    memo_column: Option<usize>,
+    category_column: Option<usize>,
+    decimal_separator: char,
+    budget_account_id: i32,
+    conn: DbConnection,
+}
+
+impl ImportProfileBuilder {
+    pub(crate) fn new(conn: DbConnection, budget_account_id: i32, name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            delimiter: ',',
+            header_rows: 1,
+            date_column: None,
+            date_format: "%Y-%m-%d".to_string(),
+            amount_column: None,
+            debit_column: None,
+            credit_column: None,
+            payee_column: None,
+            memo_column: None,
+            category_column: None,
+            decimal_separator: '.',
+            budget_account_id,
+            conn,
+        }
+    }
+
+    pub fn delimiter(&mut self, delimiter: char) -> &mut Self {
+        self.delimiter = delimiter;
+        self
+    }
+
+    /// lines to skip at the top of the file, 1 by default
+    pub fn header_rows(&mut self, header_rows: usize) -> &mut Self {
+        self.header_rows = header_rows;
+        self
+    }
+
+    /// column of the date and its chrono format, e.g. `%d/%m/%Y`
+    pub fn date(&mut self, column: usize, format: &str) -> &mut Self {
+        self.date_column = Some(column);
+        self.date_format = format.to_string();
+        self
+    }
+
+    /// one signed column, negative amounts are expenses
+    pub fn amount(&mut self, column: usize) -> &mut Self {
+        self.amount_column = Some(column);
+        self
+    }
+
+    /// separate columns for money going out and coming in
+    pub fn debit_credit(&mut self, debit_column: usize, credit_column: usize) -> &mut Self {
+        self.debit_column = Some(debit_column);
+        self.credit_column = Some(credit_column);
+        self
+    }
+
+    pub fn payee(&mut self, column: usize) -> &mut Self {
+        self.payee_column = Some(column);
+        self
+    }
+
+    /// becomes the note of the transaction, and its payee when the payee is empty
+    pub fn memo(&mut self, column: usize) -> &mut Self {
+        self.memo_column = Some(column);
+        self
+    }
+
+    /// name of the category of expenses, default category when empty
+    pub fn category(&mut self, column: usize) -> &mut Self {
+        self.category_column = Some(column);
+        self
+    }
+
+    pub fn decimal_separator(&mut self, decimal_separator: char) -> &mut Self {
+        self.decimal_separator = decimal_separator;
+        self
+    }
+
+    pub fn done(&self) -> Result<ImportProfile, BudgetingErrors> {
+        let invalid = |field: &str, reason: InvalidReason| BudgetingErrors::InvalidInput {
+            field: field.to_string(),
+            reason,
+        };
+        let name = validation::not_empty("name", &self.name)?;
+        validation::not_empty("date_format", &self.date_format)?;
+        let date_column = self
+            .date_column
+            .ok_or_else(|| invalid("date_column", InvalidReason::Empty))?;
+        let payee_column = self
+            .payee_column
+            .ok_or_else(|| invalid("payee_column", InvalidReason::Empty))?;
+        if self.amount_column.is_none() && self.debit_column.is_none() {
+            return Err(invalid("amount_column", InvalidReason::Empty));
+        }
+        if !['.', ','].contains(&self.decimal_separator) {
+            return Err(invalid("decimal_separator", InvalidReason::OutOfRange));
+        }
+        if !self.delimiter.is_ascii() || self.delimiter == self.decimal_separator {
+            return Err(invalid("delimiter", InvalidReason::OutOfRange));
+        }
+        let mut _conn = (*self.conn).borrow_mut();
+        let conn = _conn.deref_mut();
+        if ImportProfileModel::load_by_name(conn, self.budget_account_id, name).is_ok() {
+            return Err(BudgetingErrors::ImportProfileAlreadyExists(name.to_string()));
+        }
+        let column = |c: Option<usize>| c.map(|c| c as i32);
+        let new_profile = NewImportProfile {
+            budget_account_id: self.budget_account_id,
+            name,
+            delimiter: self.delimiter.to_string(),
+            header_rows: self.header_rows as i32,
+            date_column: date_column as i32,
+            date_format: &self.date_format,
+            amount_column: column(self.amount_column),
+            debit_column: column(self.debit_column),
+            credit_column: column(self.credit_column),
+            payee_column: payee_column as i32,
+            memo_column: column(self.memo_column),
+            category_column: column(self.category_column),
+            decimal_separator: self.decimal_separator.to_string(),
+        };
+        Ok(save_model!(conn, import_profiles, new_profile, ImportProfile)?)
+    }
+}
+
+pub struct ImportProfileModel;
+
+impl ImportProfileModel {
+    pub(crate) fn find_all(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+    ) -> Result<Vec<ImportProfile>, BudgetingErrors> {
+        imp_db!(import_profiles);
+        import_profiles
+            .filter(budget_account_id.eq(_budget_account_id))
+            .order(name.asc())
+            .load::<ImportProfile>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn load_by_name(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        profile_name: &str,
+    ) -> Result<ImportProfile, BudgetingErrors> {
+        imp_db!(import_profiles);
+        match import_profiles
+            .filter(budget_account_id.eq(_budget_account_id))
+            .filter(name.eq(profile_name))
+            .first::<ImportProfile>(conn)
+        {
+            Ok(p) => Ok(p),
+            Err(diesel::result::Error::NotFound) => {
+                Err(BudgetingErrors::ImportProfileNotFound(profile_name.to_string()))
+            }
+            Err(e) => Err(BudgetingErrors::UnspecifiedDatabaseError(e)),
+        }
+    }
+
+    pub(crate) fn delete(
+        conn: &mut SqliteConnection,
+        profile_id: i32,
+    ) -> Result<usize, BudgetingErrors> {
+        imp_db!(import_profiles);
+        diesel::delete(import_profiles.find(profile_id))
+            .execute(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+}
@@ -0,0 +1,365 @@
+//! Reading the exports of other envelope budgeting applications, to move a budget over:
+//! the register and the budget CSV files of YNAB4 and the transaction CSV export of
+//! Actual Budget. The kind of file is told from its header.
+//!
+//! Both applications name a category `Master: Sub`, after its master category or group,
+//! which is kept as the category name, subcategories with the same name in two master
+//! categories stay apart. Categories of the `Income` master category are the money to
+//! budget, i.e. the default category. A transfer between two accounts appears in both
+//! registers, only one side is kept.
+
+use crate::budgeting::import::{parse_amount, parse_date_with_format};
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+const INCOME_MASTER_CATEGORY: &str = "Income";
+const TRANSFER_PREFIXES: [&str; 2] = ["Transfer : ", "Transfer: "];
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BudgetAppSettings {
+    /// chrono format of the dates of a YNAB4 export, which follow the locale it ran in,
+    /// ISO dates like Actual Budget's are always read
+    pub date_format: String,
+    pub decimal_separator: char,
+}
+
+impl Default for BudgetAppSettings {
+    fn default() -> Self {
+        BudgetAppSettings {
+            date_format: "%m/%d/%Y".to_string(),
+            decimal_separator: '.',
+        }
+    }
+}
+
+/// A transaction of a register, amount is signed, negative when money leaves `account`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AppTransaction {
+    pub account: String,
+    pub date_created: NaiveDateTime,
+    pub payee: String,
+    pub note: String,
+    /// `Master: Sub`, `None` for income and for uncategorized transactions
+    pub category: Option<String>,
+    pub amount: f64,
+    /// the other account of a transfer between accounts
+    pub transfer_account: Option<String>,
+}
+
+/// What a budget file had budgeted for a category in a month
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct AppBudgeted {
+    /// first day of the month
+    pub month: NaiveDate,
+    pub category: String,
+    pub amount: f64,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum AppEntry {
+    Transaction { line: usize, transaction: AppTransaction },
+    Budgeted { line: usize, budgeted: AppBudgeted },
+    Skipped { line: usize, reason: String },
+    Failed { line: usize, error: String },
+}
+
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
+enum AppFile {
+    Ynab4Register,
+    Ynab4Budget,
+    ActualTransactions,
+}
+
+/// the account a transfer payee names, e.g. `Transfer : Savings`
+fn transfer_account(payee: &str) -> Option<String> {
+    TRANSFER_PREFIXES
+        .iter()
+        .find_map(|p| payee.strip_prefix(p))
+        .map(|a| a.trim().to_string())
+        .filter(|a| !a.is_empty())
+}
+
+/// `None` for the income master category, whose money goes to the default category
+fn category_name(category: &str) -> Option<String> {
+    let category = category.trim();
+    let master = category.split(':').next().unwrap_or_default().trim();
+    if category.is_empty() || master.eq_ignore_ascii_case(INCOME_MASTER_CATEGORY) {
+        None
+    } else {
+        Some(category.to_string())
+    }
+}
+
+/// `Jan 2016`, `January 2016` or `2016-01`
+fn parse_month(value: &str) -> Option<NaiveDate> {
+    let value = value.trim();
+    ["%d %b %Y", "%d %B %Y"]
+        .iter()
+        .find_map(|f| NaiveDate::parse_from_str(&format!("1 {value}"), f).ok())
+        .or_else(|| NaiveDate::parse_from_str(&format!("{value}-01"), "%Y-%m-%d").ok())
+}
+
+struct Columns {
+    headers: Vec<String>,
+}
+
+impl Columns {
+    fn get<'a>(&self, record: &'a csv::StringRecord, name: &str) -> &'a str {
+        self.headers
+            .iter()
+            .position(|h| h.eq_ignore_ascii_case(name))
+            .and_then(|i| record.get(i))
+            .unwrap_or_default()
+            .trim()
+    }
+
+    fn has(&self, name: &str) -> bool {
+        self.headers.iter().any(|h| h.eq_ignore_ascii_case(name))
+    }
+
+    fn file(&self) -> Option<AppFile> {
+        if self.has("Outflow") && self.has("Inflow") {
+            Some(AppFile::Ynab4Register)
+        } else if self.has("Budgeted") && self.has("Month") {
+            Some(AppFile::Ynab4Budget)
+        } else if self.has("Account") && self.has("Amount") {
+            Some(AppFile::ActualTransactions)
+        } else {
+            None
+        }
+    }
+}
+
+fn amount(value: &str, settings: &BudgetAppSettings) -> Result<f64, String> {
+    if value.is_empty() {
+        return Ok(0.);
+    }
+    parse_amount(value, settings.decimal_separator).ok_or(format!("invalid amount: {value}"))
+}
+
+fn parse_register_record(
+    file: AppFile,
+    columns: &Columns,
+    record: &csv::StringRecord,
+    settings: &BudgetAppSettings,
+) -> Result<Option<AppTransaction>, String> {
+    let account = columns.get(record, "Account");
+    if account.is_empty() {
+        return Err("no account".to_string());
+    }
+    let date = columns.get(record, "Date");
+    let date_created = parse_date_with_format(date, &settings.date_format)
+        .or_else(|| parse_date_with_format(date, "%Y-%m-%d"))
+        .ok_or(format!("invalid date: {date}"))?;
+    let (amount, note) = match file {
+        AppFile::Ynab4Register => {
+            let amount = amount(columns.get(record, "Inflow"), settings)?
+                - amount(columns.get(record, "Outflow"), settings)?;
+            // the lines of a split are listed one by one, with `(Split 1/2)` before the memo
+            let memo = columns.get(record, "Memo");
+            let note = match memo.strip_prefix("(Split ").and_then(|m| m.split_once(')')) {
+                Some((_, rest)) => rest.trim(),
+                None => memo,
+            };
+            (amount, note.to_string())
+        }
+        _ => {
+            // the lines of a split have their amount in `Split_Amount`
+            let split = amount(columns.get(record, "Split_Amount"), settings)?;
+            let amount = if split != 0. {
+                split
+            } else {
+                amount(columns.get(record, "Amount"), settings)?
+            };
+            (amount, columns.get(record, "Notes").to_string())
+        }
+    };
+    if amount == 0. {
+        return Ok(None);
+    }
+    let payee = columns.get(record, "Payee").to_string();
+    let category = category_name(columns.get(record, "Category"));
+    // a transfer to an off budget account has a category, it is spending
+    let transfer = if columns.get(record, "Category").is_empty() {
+        transfer_account(&payee)
+    } else {
+        None
+    };
+    Ok(Some(AppTransaction {
+        account: account.to_string(),
+        date_created,
+        payee,
+        note,
+        category,
+        amount,
+        transfer_account: transfer,
+    }))
+}
+
+fn parse_budget_record(
+    columns: &Columns,
+    record: &csv::StringRecord,
+    settings: &BudgetAppSettings,
+) -> Result<Option<AppBudgeted>, String> {
+    let month = columns.get(record, "Month");
+    let month = parse_month(month).ok_or(format!("invalid month: {month}"))?;
+    let category = columns.get(record, "Category");
+    let category = category_name(category).ok_or(format!("not a budget category: {category}"))?;
+    let amount = amount(columns.get(record, "Budgeted"), settings)?;
+    if amount == 0. {
+        return Ok(None);
+    }
+    Ok(Some(AppBudgeted { month, category, amount }))
+}
+
+/// Every row of a YNAB4 register or budget export, or of an Actual Budget transaction
+/// export, in file order. The side of a transfer coming in is skipped when the side
+/// going out is in the file, the parent line of an Actual Budget split is skipped
+/// for its lines.
+pub fn parse(data: &str, settings: &BudgetAppSettings) -> Vec<AppEntry> {
+    let data = data.trim_start_matches('\u{feff}');
+    let mut csv_reader = csv::ReaderBuilder::new()
+        .flexible(true)
+        .from_reader(data.as_bytes());
+    let columns = match csv_reader.headers() {
+        Ok(h) => Columns {
+            headers: h.iter().map(|h| h.trim().to_string()).collect(),
+        },
+        Err(e) => {
+            return vec![AppEntry::Failed {
+                line: 1,
+                error: e.to_string(),
+            }]
+        }
+    };
+    let Some(file) = columns.file() else {
+        return vec![AppEntry::Failed {
+            line: 1,
+            error: "not a YNAB4 or Actual Budget export".to_string(),
+        }];
+    };
+    let mut entries = vec![];
+    let mut records = csv_reader.records().peekable();
+    let mut i = 1;
+    while let Some(record) = records.next() {
+        i += 1;
+        let record = match record {
+            Ok(r) => r,
+            Err(e) => {
+                entries.push(AppEntry::Failed {
+                    line: e.position().map_or(i, |p| p.line() as usize),
+                    error: e.to_string(),
+                });
+                continue;
+            }
+        };
+        let line = record.position().map_or(i, |p| p.line() as usize);
+        if record.iter().all(|c| c.trim().is_empty()) {
+            entries.push(AppEntry::Skipped {
+                line,
+                reason: "empty line".to_string(),
+            });
+            continue;
+        }
+        if file == AppFile::ActualTransactions {
+            let is_split_line = |r: &csv::StringRecord| {
+                amount(columns.get(r, "Split_Amount"), settings).is_ok_and(|a| a != 0.)
+            };
+            let next_is_split_line = records
+                .peek()
+                .is_some_and(|r| r.as_ref().is_ok_and(is_split_line));
+            if !is_split_line(&record) && next_is_split_line {
+                entries.push(AppEntry::Skipped {
+                    line,
+                    reason: "split, its lines are imported".to_string(),
+                });
+                continue;
+            }
+        }
+        entries.push(match file {
+            AppFile::Ynab4Budget => match parse_budget_record(&columns, &record, settings) {
+                Ok(Some(budgeted)) => AppEntry::Budgeted { line, budgeted },
+                Ok(None) => AppEntry::Skipped {
+                    line,
+                    reason: "nothing budgeted".to_string(),
+                },
+                Err(error) => AppEntry::Failed { line, error },
+            },
+            _ => match parse_register_record(file, &columns, &record, settings) {
+                Ok(Some(transaction)) => AppEntry::Transaction { line, transaction },
+                Ok(None) => AppEntry::Skipped {
+                    line,
+                    reason: "no amount".to_string(),
+                },
+                Err(error) => AppEntry::Failed { line, error },
+            },
+        });
+    }
+    skip_incoming_transfer_sides(&mut entries);
+    entries
+}
+
+/// Actual Budget names transfers after the other account, without the `Transfer : ` of
+/// YNAB4, a payee that is the name of another account of the file is a transfer
+fn accounts_as_payees(entries: &mut [AppEntry]) {
+    let accounts: Vec<String> = entries
+        .iter()
+        .filter_map(|e| match e {
+            AppEntry::Transaction { transaction, .. } => Some(transaction.account.clone()),
+            _ => None,
+        })
+        .collect();
+    for e in entries.iter_mut() {
+        if let AppEntry::Transaction { transaction: t, .. } = e {
+            if t.transfer_account.is_none()
+                && t.category.is_none()
+                && t.payee != t.account
+                && accounts.contains(&t.payee)
+            {
+                t.transfer_account = Some(t.payee.clone());
+            }
+        }
+    }
+}
+
+fn skip_incoming_transfer_sides(entries: &mut [AppEntry]) {
+    accounts_as_payees(entries);
+    let outgoing: Vec<(usize, AppTransaction)> = entries
+        .iter()
+        .filter_map(|e| match e {
+            AppEntry::Transaction { line, transaction }
+                if transaction.amount < 0. && transaction.transfer_account.is_some() =>
+            {
+                Some((*line, transaction.clone()))
+            }
+            _ => None,
+        })
+        .collect();
+    let mut used = vec![];
+    let mut incoming = vec![];
+    for (i, e) in entries.iter().enumerate() {
+        let AppEntry::Transaction { line, transaction: t } = e else {
+            continue;
+        };
+        let Some(from) = t.transfer_account.as_ref().filter(|_| t.amount > 0.) else {
+            continue;
+        };
+        let other = outgoing.iter().find(|(l, o)| {
+            !used.contains(l)
+                && &o.account == from
+                && o.transfer_account.as_ref() == Some(&t.account)
+                && o.date_created.date() == t.date_created.date()
+                && (o.amount + t.amount).abs() < 0.005
+        });
+        if let Some((other_line, _)) = other {
+            used.push(*other_line);
+            incoming.push((i, *line, *other_line));
+        }
+    }
+    for (i, line, other_line) in incoming {
+        entries[i] = AppEntry::Skipped {
+            line,
+            reason: format!("other side of the transfer on line {other_line}"),
+        };
+    }
+}
@@ -0,0 +1,137 @@
+//! Reading transactions from files other applications and banks produce, and writing
+//! them back where the format is shared with other applications, like QIF.
+//! Parsers only turn lines into `ParsedRow`s, the transactions are created by
+//! `Budgeting` through `TransactionBuilder`, so validation, overspending and
+//! income allocation rules apply the same way as for transactions entered by hand.
+
+use chrono::{NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+
+pub mod bank_csv;
+pub mod budget_app;
+pub mod ofx;
+pub mod qif;
+
+/// A transaction read from a file, amount is signed, negative for expenses
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ImportedTransaction {
+    pub date_created: NaiveDateTime,
+    pub amount: f64,
+    pub payee: String,
+    pub note: String,
+    /// category name, the default category is used when none is given
+    pub category: Option<String>,
+    /// id given by the bank, a transaction with the same id is not imported twice
+    pub fitid: Option<String>,
+}
+
+/// What a parser made of one line of the file, `line` starts at 1
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParsedRow {
+    Transaction {
+        line: usize,
+        transaction: ImportedTransaction,
+    },
+    Skipped {
+        line: usize,
+        reason: String,
+    },
+    Failed {
+        line: usize,
+        error: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub enum RowOutcome {
+    /// id of the created transaction, or period allocation for a budget file
+    Created(i32),
+    /// id of the transaction already saved the row was merged into
+    Merged(i32),
+    Skipped(String),
+    Failed(String),
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct ImportRowReport {
+    pub line: usize,
+    pub outcome: RowOutcome,
+}
+
+/// Outcome of every line of an import, in file order
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct ImportReport {
+    pub rows: Vec<ImportRowReport>,
+}
+
+impl ImportReport {
+    pub(crate) fn push(&mut self, line: usize, outcome: RowOutcome) {
+        self.rows.push(ImportRowReport { line, outcome });
+    }
+
+    pub fn created(&self) -> Vec<i32> {
+        self.rows
+            .iter()
+            .filter_map(|r| match r.outcome {
+                RowOutcome::Created(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn merged(&self) -> Vec<i32> {
+        self.rows
+            .iter()
+            .filter_map(|r| match r.outcome {
+                RowOutcome::Merged(id) => Some(id),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|r| matches!(r.outcome, RowOutcome::Skipped(_)))
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.rows
+            .iter()
+            .filter(|r| matches!(r.outcome, RowOutcome::Failed(_)))
+            .count()
+    }
+}
+
+/// Parses amounts like `1,234.56`, `-12.5`, `1.234,56 €` or `(12.50)`, the
+/// separator not used for decimals is taken as thousands separator.
+pub(crate) fn parse_amount(value: &str, decimal_separator: char) -> Option<f64> {
+    let value = value.trim();
+    let (negative, value) = match value.strip_prefix('(').and_then(|v| v.strip_suffix(')')) {
+        Some(v) => (true, v),
+        None => (false, value),
+    };
+    let cleaned: String = value
+        .chars()
+        .filter(|c| c.is_ascii_digit() || *c == '-' || *c == '+' || *c == decimal_separator)
+        .map(|c| if c == decimal_separator { '.' } else { c })
+        .collect();
+    if cleaned.is_empty() {
+        return None;
+    }
+    let amount: f64 = cleaned.parse().ok()?;
+    Some(if negative { -amount } else { amount })
+}
+
+/// Parses a date or a date and time with a chrono format string
+pub(crate) fn parse_date_with_format(value: &str, format: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    NaiveDateTime::parse_from_str(value, format)
+        .ok()
+        .or_else(|| {
+            NaiveDate::parse_from_str(value, format)
+                .ok()
+                .and_then(|d| d.and_hms_opt(0, 0, 0))
+        })
+}
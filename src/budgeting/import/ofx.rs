@@ -0,0 +1,103 @@
+use crate::budgeting::import::{parse_amount, ImportedTransaction, ParsedRow};
+use chrono::{NaiveDate, NaiveDateTime, NaiveTime};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static LEAF_ELEMENT: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"<([A-Za-z0-9.]+)>([^<]*)").unwrap());
+
+/// `<TAG>value`, the closing tag of OFX 2.x is not needed and leaf elements of OFX 1.x have none
+fn leaf_elements(block: &str) -> Vec<(String, String)> {
+    LEAF_ELEMENT
+        .captures_iter(block)
+        .map(|c| (c[1].to_uppercase(), decode_entities(c[2].trim())))
+        .filter(|(_, v)| !v.is_empty())
+        .collect()
+}
+
+fn decode_entities(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&nbsp;", " ")
+        .replace("&amp;", "&")
+}
+
+/// `YYYYMMDD`, optionally followed by `HHMMSS`, milliseconds and a time zone like `[-5:EST]`,
+/// the time zone is ignored
+pub(crate) fn parse_ofx_date(value: &str) -> Option<NaiveDateTime> {
+    let digits: String = value.chars().take_while(|c| c.is_ascii_digit()).collect();
+    if digits.len() < 8 {
+        return None;
+    }
+    let date = NaiveDate::parse_from_str(&digits[..8], "%Y%m%d").ok()?;
+    let time = if digits.len() >= 14 {
+        NaiveTime::parse_from_str(&digits[8..14], "%H%M%S").ok()?
+    } else {
+        NaiveTime::MIN
+    };
+    Some(date.and_time(time))
+}
+
+fn parse_transaction(block: &str) -> Result<ImportedTransaction, String> {
+    let elements = leaf_elements(block);
+    let get = |tag: &str| {
+        elements
+            .iter()
+            .find(|(t, _)| t == tag)
+            .map(|(_, v)| v.clone())
+    };
+    let date = get("DTPOSTED").ok_or("missing DTPOSTED")?;
+    let date_created = parse_ofx_date(&date).ok_or_else(|| format!("invalid date: {date}"))?;
+    let amount = get("TRNAMT").ok_or("missing TRNAMT")?;
+    // some banks write a decimal comma even though the spec asks for a dot
+    let separator = if amount.contains(',') && !amount.contains('.') { ',' } else { '.' };
+    let amount = parse_amount(&amount, separator).ok_or_else(|| format!("invalid amount: {amount}"))?;
+    let note = get("MEMO").unwrap_or_default();
+    let payee = get("NAME").unwrap_or_else(|| note.clone());
+    Ok(ImportedTransaction {
+        date_created,
+        amount,
+        payee,
+        note,
+        category: None,
+        fitid: get("FITID"),
+    })
+}
+
+/// Reads every `STMTTRN` of an OFX 1.x (SGML) or 2.x (XML) statement, bank and credit
+/// card statements alike. The line of a row is the line its `STMTTRN` starts on.
+pub fn parse(text: &str) -> Vec<ParsedRow> {
+    // ascii only, so byte offsets stay the same as in `text`
+    let upper = text.to_ascii_uppercase();
+    let mut rows = vec![];
+    let mut from = 0;
+    while let Some(found) = upper[from..].find("<STMTTRN>") {
+        let start = from + found;
+        let end = upper[start..]
+            .find("</STMTTRN>")
+            .map(|e| start + e)
+            .or_else(|| {
+                // SGML statements may leave aggregates unclosed, the next one ends this one
+                upper[start + 1..]
+                    .find("<STMTTRN>")
+                    .or_else(|| upper[start + 1..].find("</BANKTRANLIST>"))
+                    .map(|e| start + 1 + e)
+            })
+            .unwrap_or(text.len());
+        let line = text[..start].matches('\n').count() + 1;
+        let parsed = parse_transaction(&text[start + "<STMTTRN>".len()..end]);
+        rows.push(match parsed {
+            Ok(t) if t.amount == 0. => ParsedRow::Skipped {
+                line,
+                reason: "no amount".to_string(),
+            },
+            Ok(transaction) => ParsedRow::Transaction { line, transaction },
+            Err(error) => ParsedRow::Failed { line, error },
+        });
+        from = end;
+    }
+    rows
+}
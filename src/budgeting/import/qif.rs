@@ -0,0 +1,286 @@
+use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+use crate::budgeting::import::parse_amount;
+use crate::budgeting::transaction::{Transaction, TransactionType};
+use crate::DEFAULT_CATEGORY;
+use chrono::{NaiveDate, NaiveDateTime};
+use std::collections::HashMap;
+use std::io::Write;
+
+/// Where the money of a split goes. `L[Name]` in QIF is a transfer, it is mapped to
+/// a transfer between the default category and the category `Name`.
+#[derive(Debug, PartialEq, Clone)]
+pub enum QifTarget {
+    Category(Option<String>),
+    Transfer(String),
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct QifSplit {
+    pub target: QifTarget,
+    /// signed, negative when money leaves the account
+    pub amount: f64,
+    pub memo: String,
+}
+
+/// One QIF transaction. A record without splits gets a single split made from its
+/// `L` and `T` fields, so `splits` is never empty.
+#[derive(Debug, PartialEq, Clone)]
+pub struct QifRecord {
+    pub date_created: NaiveDateTime,
+    pub payee: String,
+    pub memo: String,
+    pub amount: f64,
+    pub splits: Vec<QifSplit>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum QifEntry {
+    Record { line: usize, record: QifRecord },
+    Skipped { line: usize, reason: String },
+    Failed { line: usize, error: String },
+}
+
+const SUPPORTED_SECTIONS: [&str; 3] = ["bank", "cash", "ccard"];
+
+/// `M/D/YYYY`, `M/D'YY`, `M/D/YY` or `YYYY-MM-DD`. Two digit years after an apostrophe
+/// are in the 2000s, otherwise years below 70 are.
+pub(crate) fn parse_qif_date(value: &str) -> Option<NaiveDateTime> {
+    let value = value.trim();
+    let parts: Vec<&str> = value
+        .split(|c: char| !c.is_ascii_digit())
+        .filter(|p| !p.is_empty())
+        .collect();
+    if parts.len() != 3 {
+        return None;
+    }
+    let numbers: Vec<i32> = parts.iter().map(|p| p.parse().ok()).collect::<Option<_>>()?;
+    let (year, month, day) = if parts[0].len() == 4 {
+        (numbers[0], numbers[1], numbers[2])
+    } else {
+        let year = match numbers[2] {
+            y if y >= 100 => y,
+            y if value.contains('\'') || y < 70 => 2000 + y,
+            y => 1900 + y,
+        };
+        (year, numbers[0], numbers[1])
+    };
+    NaiveDate::from_ymd_opt(year, month as u32, day as u32)?.and_hms_opt(0, 0, 0)
+}
+
+fn target(value: &str) -> QifTarget {
+    let value = value.trim();
+    match value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+        Some(name) => QifTarget::Transfer(name.trim().to_string()),
+        None if value.is_empty() => QifTarget::Category(None),
+        // `Category/Class`, classes are not supported
+        None => QifTarget::Category(Some(value.split('/').next().unwrap().to_string())),
+    }
+}
+
+#[derive(Default)]
+struct Fields {
+    line: usize,
+    date: Option<String>,
+    amount: Option<String>,
+    payee: String,
+    memo: String,
+    category: String,
+    splits: Vec<(String, String, Option<String>)>,
+}
+
+impl Fields {
+    fn record(&self) -> Result<QifRecord, String> {
+        let date = self.date.as_deref().ok_or("missing date")?;
+        let date_created = parse_qif_date(date).ok_or_else(|| format!("invalid date: {date}"))?;
+        let parse = |v: &str| parse_amount(v, '.').ok_or_else(|| format!("invalid amount: {v}"));
+        let amount = match &self.amount {
+            Some(a) => parse(a)?,
+            None => return Err("missing amount".to_string()),
+        };
+        let splits = if self.splits.is_empty() {
+            vec![QifSplit {
+                target: target(&self.category),
+                amount,
+                memo: String::new(),
+            }]
+        } else {
+            self.splits
+                .iter()
+                .map(|(category, memo, amount)| {
+                    Ok(QifSplit {
+                        target: target(category),
+                        amount: parse(amount.as_deref().ok_or("missing split amount")?)?,
+                        memo: memo.clone(),
+                    })
+                })
+                .collect::<Result<Vec<_>, String>>()?
+        };
+        Ok(QifRecord {
+            date_created,
+            payee: self.payee.clone(),
+            memo: self.memo.clone(),
+            amount,
+            splits,
+        })
+    }
+}
+
+/// Reads the bank, cash and credit card sections of a QIF file. Records of other
+/// sections, like investments or the account list, are skipped.
+pub fn parse(text: &str) -> Vec<QifEntry> {
+    let mut entries = vec![];
+    let mut section: Option<String> = None;
+    let mut fields = Fields::default();
+    let mut started = false;
+    for (i, raw) in text.lines().enumerate() {
+        let line = raw.trim_end();
+        if line.is_empty() {
+            continue;
+        }
+        if let Some(header) = line.strip_prefix('!') {
+            if header.to_lowercase().starts_with("option") || header.to_lowercase().starts_with("clear") {
+                continue;
+            }
+            section = Some(
+                header
+                    .strip_prefix("Type:")
+                    .or_else(|| header.strip_prefix("type:"))
+                    .unwrap_or(header)
+                    .trim()
+                    .to_lowercase(),
+            );
+            continue;
+        }
+        if !started {
+            fields = Fields {
+                line: i + 1,
+                ..Fields::default()
+            };
+            started = true;
+        }
+        let mut chars = line.chars();
+        let code = chars.next();
+        let value = chars.as_str();
+        match code.unwrap_or_default() {
+            '^' => {
+                let supported = section
+                    .as_deref()
+                    .map(|s| SUPPORTED_SECTIONS.contains(&s))
+                    .unwrap_or(false);
+                entries.push(if !supported {
+                    QifEntry::Skipped {
+                        line: fields.line,
+                        reason: format!("unsupported section: {}", section.as_deref().unwrap_or("")),
+                    }
+                } else {
+                    match fields.record() {
+                        Ok(record) => QifEntry::Record {
+                            line: fields.line,
+                            record,
+                        },
+                        Err(error) => QifEntry::Failed {
+                            line: fields.line,
+                            error,
+                        },
+                    }
+                });
+                started = false;
+            }
+            'D' => fields.date = Some(value.to_string()),
+            'T' | 'U' => fields.amount = Some(value.to_string()),
+            'P' => fields.payee = value.trim().to_string(),
+            'M' => fields.memo = value.trim().to_string(),
+            'L' => fields.category = value.to_string(),
+            'S' => fields.splits.push((value.to_string(), String::new(), None)),
+            'E' => {
+                if let Some(split) = fields.splits.last_mut() {
+                    split.1 = value.trim().to_string();
+                }
+            }
+            '$' => {
+                if let Some(split) = fields.splits.last_mut() {
+                    split.2 = Some(value.to_string());
+                }
+            }
+            // cleared status, check number, address and the like
+            _ => {}
+        }
+    }
+    entries
+}
+
+/// a field ends at the end of its line
+fn one_line(value: &str) -> String {
+    value.replace("\r\n", " ").replace(['\r', '\n'], " ")
+}
+
+/// `/` starts a class and `[` a transfer, a category named with them would not be read back
+fn exported_name(name: String) -> Result<String, BudgetingErrors> {
+    if name.contains(['/', '[', '\r', '\n']) {
+        return Err(BudgetingErrors::InvalidInput {
+            field: "category".to_string(),
+            reason: InvalidReason::ReservedCharacter,
+        });
+    }
+    Ok(name)
+}
+
+/// Writes the transactions of one budget account as a `Bank` section. Transfers between
+/// the default category and another category become `L[Category]`, transfers between two
+/// other categories become a split moving the money through the default category, so
+/// importing the file again gives every category the same balance. Line breaks in payees
+/// and notes become spaces, category names with `/` or `[` can not be written.
+pub(crate) fn export<W: Write>(
+    transactions: &[Transaction],
+    category_names: &HashMap<i32, String>,
+    mut writer: W,
+) -> Result<usize, BudgetingErrors> {
+    let io_error = |e: std::io::Error| BudgetingErrors::IoError(e.to_string());
+    let name = |id: i32| exported_name(category_names.get(&id).cloned().unwrap_or_default());
+    let mut out = String::from("!Type:Bank\n");
+    let mut written = 0;
+    for t in transactions {
+        let transaction_type = TransactionType::from(t.transfer_type_id());
+        if transaction_type == TransactionType::TransferOut {
+            // the receiving side has both categories
+            continue;
+        }
+        out.push_str(&format!("D{}\n", t.date_created().format("%m/%d/%Y")));
+        match transaction_type {
+            TransactionType::TransferIn => {
+                let source = match t.transfer_category_id() {
+                    Some(id) => name(id)?,
+                    None => exported_name(one_line(&t.payee()))?,
+                };
+                let dest = name(t.category_id())?;
+                if source == DEFAULT_CATEGORY {
+                    out.push_str(&format!("T{:.2}\nL[{}]\n", -t.amount(), dest));
+                } else if dest == DEFAULT_CATEGORY {
+                    out.push_str(&format!("T{:.2}\nL[{}]\n", t.amount(), source));
+                } else {
+                    out.push_str(&format!(
+                        "T0.00\nS[{}]\n${:.2}\nS[{}]\n${:.2}\n",
+                        source,
+                        t.amount(),
+                        dest,
+                        -t.amount()
+                    ));
+                }
+            }
+            _ => {
+                out.push_str(&format!("T{:.2}\nP{}\n", t.amount(), one_line(&t.payee())));
+                let category = name(t.category_id())?;
+                if category != DEFAULT_CATEGORY {
+                    out.push_str(&format!("L{}\n", category));
+                }
+                if !t.note().is_empty() {
+                    out.push_str(&format!("M{}\n", one_line(&t.note())));
+                }
+            }
+        }
+        out.push_str("^\n");
+        written += 1;
+    }
+    writer.write_all(out.as_bytes()).map_err(io_error)?;
+    Ok(written)
+}
@@ -0,0 +1,161 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::category::CategoryModel;
+use crate::budgeting::validation;
+use crate::budgeting::transaction::{NewTransaction, Transaction, TransactionModel, TransactionType};
+use crate::schema::income_allocation_rules;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// "Pay yourself first" rule, a percentage of every income of a budget account is
+/// transferred to the category as soon as the income is recorded
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable)]
+#[diesel(table_name = income_allocation_rules)]
+pub struct IncomeAllocationRule {
+    id: i32,
+    budget_account_id: i32,
+    category_id: i32,
+    percentage: f64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = income_allocation_rules)]
+pub struct NewIncomeAllocationRule {
+    budget_account_id: i32,
+    category_id: i32,
+    percentage: f64,
+}
+
+impl IncomeAllocationRule {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn budget_account_id(&self) -> i32 {
+        self.budget_account_id
+    }
+
+    pub fn category_id(&self) -> i32 {
+        self.category_id
+    }
+
+    pub fn percentage(&self) -> f64 {
+        self.percentage
+    }
+}
+
+pub struct IncomeAllocationRuleModel;
+
+impl IncomeAllocationRuleModel {
+    pub(crate) fn find_all(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+    ) -> Result<Vec<IncomeAllocationRule>, BudgetingErrors> {
+        imp_db!(income_allocation_rules);
+        income_allocation_rules
+            .filter(budget_account_id.eq(_budget_account_id))
+            .order(id.asc())
+            .load::<IncomeAllocationRule>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    /// sets the percentage for the category, replacing the existing rule of the category if any.
+    /// All the rules of a budget account together can not allocate more than the income.
+    pub(crate) fn set(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        _category_id: i32,
+        _percentage: f64,
+    ) -> Result<IncomeAllocationRule, BudgetingErrors> {
+        validation::percentage("percentage", _percentage)?;
+        conn.transaction(|conn| {
+            IncomeAllocationRuleModel::delete(conn, _budget_account_id, _category_id)?;
+            let allocated: f64 = IncomeAllocationRuleModel::find_all(conn, _budget_account_id)?
+                .iter()
+                .map(|r| r.percentage)
+                .sum();
+            if allocated + _percentage > 100. {
+                return Err(BudgetingErrors::AllocationExceedsIncome);
+            }
+            let new_rule = NewIncomeAllocationRule {
+                budget_account_id: _budget_account_id,
+                category_id: _category_id,
+                percentage: _percentage,
+            };
+            let rule = save_model!(conn, income_allocation_rules, new_rule, IncomeAllocationRule)?;
+            Ok(rule)
+        })
+    }
+
+    pub(crate) fn delete(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        _category_id: i32,
+    ) -> Result<usize, BudgetingErrors> {
+        imp_db!(income_allocation_rules);
+        diesel::delete(
+            income_allocation_rules
+                .filter(budget_account_id.eq(_budget_account_id))
+                .filter(category_id.eq(_category_id)),
+        )
+        .execute(conn)
+        .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    /// Transfers the configured share of the income from the category it landed in to every
+    /// category having a rule. Both sides of the transfers are linked to the income.
+    pub(crate) fn apply(
+        conn: &mut SqliteConnection,
+        income: &Transaction,
+    ) -> Result<Vec<Transaction>, BudgetingErrors> {
+        let rules = IncomeAllocationRuleModel::find_all(conn, income.budget_account_id())?;
+        let mut transfers = vec![];
+        if rules.is_empty() {
+            return Ok(transfers);
+        }
+        let source = CategoryModel::load_by_id(conn, income.category_id())?;
+        for rule in rules {
+            let amount = income.amount() * rule.percentage / 100.;
+            if amount <= 0. {
+                continue;
+            }
+            let dest = CategoryModel::load_by_id(conn, rule.category_id)?;
+            transfers.push(TransactionModel::save(
+                conn,
+                NewTransaction {
+                    note: "Funded",
+                    payee: dest.name_c(),
+                    date_created: income.date_created(),
+                    amount: -amount,
+                    category_id: source.id(),
+                    transaction_type_id: i32::from(TransactionType::TransferOut),
+                    transfer_category_id: None,
+                    budget_account_id: income.budget_account_id(),
+                    linked_transaction_id: Some(income.id()),
+                    overspent: false,
+                    overspending_cover: false,
+                    fitid: None,
+                    duplicate_of_id: None,
+                },
+            )?);
+            transfers.push(TransactionModel::save(
+                conn,
+                NewTransaction {
+                    note: "Received",
+                    payee: source.name_c(),
+                    date_created: income.date_created(),
+                    amount,
+                    category_id: dest.id(),
+                    transaction_type_id: i32::from(TransactionType::TransferIn),
+                    transfer_category_id: Some(source.id()),
+                    budget_account_id: income.budget_account_id(),
+                    linked_transaction_id: Some(income.id()),
+                    overspent: false,
+                    overspending_cover: false,
+                    fitid: None,
+                    duplicate_of_id: None,
+                },
+            )?);
+        }
+        Ok(transfers)
+    }
+}
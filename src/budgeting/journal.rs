@@ -0,0 +1,431 @@
+//! Writing every budget account as a plain text accounting journal, for beancount or for
+//! hledger and ledger. A budget account is an asset, or a liability when listed in
+//! `JournalOptions::liabilities`, with one sub-account per category holding what the
+//! category has available, so a transfer between categories, or between budget
+//! accounts, is a balanced transaction between two sub-accounts. Expenses go to
+//! `Expenses:<category>`, incomes come from `Income`. The balance of every sub-account
+//! is asserted at the end of each period.
+
+use crate::budgeting::budget_account::BudgetAccount;
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::category::Category;
+use crate::budgeting::reports::income_expense::Granularity;
+use crate::budgeting::tag::TagModel;
+use crate::budgeting::transaction::{Transaction, TransactionType};
+use chrono::{Days, NaiveDate};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::Write;
+
+/// Transfers without the other side are balanced with this account
+const TRANSFERS_ACCOUNT: &str = "Equity:Transfers";
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum JournalFormat {
+    #[default]
+    Beancount,
+    /// read by both hledger and ledger
+    Ledger,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct JournalOptions {
+    pub format: JournalFormat,
+    /// commodity of every amount
+    pub currency: String,
+    /// budget accounts written under `Liabilities` instead of `Assets`, e.g. credit cards
+    pub liabilities: Vec<String>,
+    /// balances are asserted at the end of every period with transactions
+    pub assertions: Granularity,
+    /// account incomes come from
+    pub income_account: String,
+}
+
+impl Default for JournalOptions {
+    fn default() -> Self {
+        JournalOptions {
+            format: JournalFormat::Beancount,
+            currency: "USD".to_string(),
+            liabilities: vec![],
+            assertions: Granularity::Month,
+            income_account: "Income".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Posting {
+    pub account: String,
+    /// rounded to cents, the postings of an entry add up to 0
+    pub amount: f64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub date: NaiveDate,
+    pub payee: String,
+    pub note: String,
+    pub tags: Vec<String>,
+    pub postings: Vec<Posting>,
+}
+
+/// Balance of `account` at the end of `date`
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct BalanceAssertion {
+    pub date: NaiveDate,
+    pub account: String,
+    pub balance: f64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Journal {
+    pub options: JournalOptions,
+    /// every account used, sorted
+    pub accounts: Vec<String>,
+    /// oldest first
+    pub entries: Vec<JournalEntry>,
+    /// oldest first, sorted by account within a day
+    pub assertions: Vec<BalanceAssertion>,
+}
+
+/// `Eating out` becomes `Eating-Out`, account names in beancount only have letters,
+/// digits and dashes and start with a capital letter or a digit
+pub fn account_component(name: &str) -> String {
+    let words: Vec<String> = name
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(|w| {
+            let mut chars = w.chars();
+            let first = chars.next().unwrap();
+            first.to_uppercase().chain(chars).collect()
+        })
+        .collect();
+    if words.is_empty() {
+        "Unnamed".to_string()
+    } else {
+        words.join("-")
+    }
+}
+
+fn cents(amount: f64) -> f64 {
+    let rounded = (amount * 100.).round() / 100.;
+    // no -0.00 in the journal
+    if rounded == 0. {
+        0.
+    } else {
+        rounded
+    }
+}
+
+impl Journal {
+    /// every transaction of every budget account, oldest first
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        options: &JournalOptions,
+    ) -> Result<Journal, BudgetingErrors> {
+        let category_names: HashMap<i32, String> = {
+            imp_db!(categories);
+            categories
+                .load::<Category>(conn)?
+                .into_iter()
+                .map(|c| (c.id(), account_component(&c.name())))
+                .collect()
+        };
+        let account_roots: HashMap<i32, String> = {
+            imp_db!(budget_accounts);
+            budget_accounts
+                .load::<BudgetAccount>(conn)
+                .map_err(BudgetingErrors::UnspecifiedDatabaseError)?
+                .into_iter()
+                .map(|b| {
+                    let root = if options.liabilities.contains(&b.filed_as()) {
+                        "Liabilities"
+                    } else {
+                        "Assets"
+                    };
+                    (b.id(), format!("{}:{}", root, account_component(&b.filed_as())))
+                })
+                .collect()
+        };
+        let all = {
+            imp_db!(transactions);
+            transactions
+                .order((date_created.asc(), id.asc()))
+                .load::<Transaction>(conn)
+                .map_err(BudgetingErrors::UnspecifiedDatabaseError)?
+        };
+        let category = |id: i32| category_names.get(&id).cloned().unwrap_or_default();
+        let holding = |t: &Transaction, category_id: i32| {
+            format!(
+                "{}:{}",
+                account_roots.get(&t.budget_account_id()).cloned().unwrap_or_default(),
+                category(category_id)
+            )
+        };
+        let sending = Journal::pair_transfers(&all);
+        let paired: HashSet<i32> = sending.values().map(|o| Transaction::id(o)).collect();
+        let mut entries = vec![];
+        for t in &all {
+            let amount = cents(t.amount());
+            let postings = match TransactionType::from(t.transfer_type_id()) {
+                TransactionType::Expense => vec![
+                    (format!("Expenses:{}", category(t.category_id())), -amount),
+                    (holding(t, t.category_id()), amount),
+                ],
+                TransactionType::Income => vec![
+                    (holding(t, t.category_id()), amount),
+                    (options.income_account.clone(), -amount),
+                ],
+                TransactionType::TransferIn => match sending.get(&t.id()) {
+                    Some(o) => vec![
+                        (holding(o, o.category_id()), -amount),
+                        (holding(t, t.category_id()), amount),
+                    ],
+                    None => vec![
+                        (TRANSFERS_ACCOUNT.to_string(), -amount),
+                        (holding(t, t.category_id()), amount),
+                    ],
+                },
+                TransactionType::TransferOut => {
+                    if paired.contains(&t.id()) {
+                        continue;
+                    }
+                    vec![
+                        (holding(t, t.category_id()), amount),
+                        (TRANSFERS_ACCOUNT.to_string(), -amount),
+                    ]
+                }
+            };
+            // a transfer reads like its sending side, "Funded" to the category receiving it
+            let described = sending.get(&t.id()).copied().unwrap_or(t);
+            entries.push(JournalEntry {
+                date: t.date_created().date(),
+                payee: described.payee(),
+                note: described.note(),
+                tags: TagModel::tags_of(conn, t.id())?
+                    .iter()
+                    .map(|tag| tag.name())
+                    .collect(),
+                postings: postings
+                    .into_iter()
+                    .map(|(account, amount)| Posting { account, amount })
+                    .collect(),
+            });
+        }
+        let accounts: BTreeSet<String> = entries
+            .iter()
+            .flat_map(|e| e.postings.iter().map(|p| p.account.clone()))
+            .collect();
+        let assertions = Journal::assertions(&entries, options.assertions);
+        Ok(Journal {
+            options: options.clone(),
+            accounts: accounts.into_iter().collect(),
+            entries,
+            assertions,
+        })
+    }
+
+    /// The side going out of a transfer is written with the side coming in, which knows
+    /// both categories, returns the side going out keyed by the id of the side coming
+    /// in. The side coming in links the side going out, or for an income allocation the
+    /// income both sides were made for. Transfers saved before sides were linked are
+    /// matched on budget account, categories, amount and date, the nearest lower id
+    /// first. Both sides are in the same budget account unless linked directly.
+    fn pair_transfers(all: &[Transaction]) -> HashMap<i32, &Transaction> {
+        let amount_key = |t: &Transaction| (cents(t.amount()).abs() * 100.).round() as i64;
+        let by_id: HashMap<i32, &Transaction> = all.iter().map(|t| (Transaction::id(t), t)).collect();
+        let mut by_link: HashMap<i32, Vec<&Transaction>> = HashMap::new();
+        let mut unlinked: HashMap<_, Vec<&Transaction>> = HashMap::new();
+        for o in all
+            .iter()
+            .filter(|o| o.transfer_type_id() == i32::from(TransactionType::TransferOut))
+        {
+            match o.linked_transaction_id() {
+                Some(l) => by_link.entry(l).or_default().push(o),
+                None => unlinked
+                    .entry((o.budget_account_id(), o.category_id(), amount_key(o), o.date_created()))
+                    .or_default()
+                    .push(o),
+            }
+        }
+        let mut used: HashSet<i32> = HashSet::new();
+        let mut sending = HashMap::new();
+        for t in all
+            .iter()
+            .filter(|t| t.transfer_type_id() == i32::from(TransactionType::TransferIn))
+        {
+            let Some(source) = t.transfer_category_id().filter(|c| *c != 0) else {
+                continue;
+            };
+            let matches = |o: &Transaction| {
+                o.transfer_type_id() == i32::from(TransactionType::TransferOut)
+                    && !used.contains(&Transaction::id(o))
+                    && o.category_id() == source
+                    && amount_key(o) == amount_key(t)
+                    && o.amount().signum() != t.amount().signum()
+            };
+            let linked = t.linked_transaction_id().and_then(|l| {
+                by_id.get(&l).copied().filter(|o| matches(o)).or_else(|| {
+                    by_link.get(&l).and_then(|sides| {
+                        sides
+                            .iter()
+                            .copied()
+                            .find(|o| o.budget_account_id() == t.budget_account_id() && matches(o))
+                    })
+                })
+            });
+            let out = linked.or_else(|| {
+                let key = (t.budget_account_id(), source, amount_key(t), t.date_created());
+                let candidates = unlinked.get(&key)?;
+                let candidates = candidates.iter().copied().filter(|o| matches(o));
+                let id = Transaction::id(t);
+                candidates
+                    .clone()
+                    .filter(|o| Transaction::id(o) < id)
+                    .max_by_key(|o| Transaction::id(o))
+                    .or_else(|| candidates.min_by_key(|o| Transaction::id(o)))
+            });
+            if let Some(o) = out {
+                used.insert(Transaction::id(o));
+                sending.insert(Transaction::id(t), o);
+            }
+        }
+        sending
+    }
+
+    /// balance of every asset and liability account on the last day of each period
+    /// with entries
+    fn assertions(entries: &[JournalEntry], granularity: Granularity) -> Vec<BalanceAssertion> {
+        let mut balances: BTreeMap<String, f64> = BTreeMap::new();
+        let mut assertions = vec![];
+        let mut entries = entries.iter().peekable();
+        while let Some(e) = entries.next() {
+            for p in &e.postings {
+                if p.account.starts_with("Assets:") || p.account.starts_with("Liabilities:") {
+                    *balances.entry(p.account.clone()).or_default() += p.amount;
+                }
+            }
+            let end = granularity.next_bucket(e.date);
+            if entries.peek().is_none_or(|next| next.date >= end) {
+                let date = end - Days::new(1);
+                assertions.extend(balances.iter().map(|(account, balance)| BalanceAssertion {
+                    date,
+                    account: account.clone(),
+                    balance: cents(*balance),
+                }));
+            }
+        }
+        assertions
+    }
+
+    /// returns the number of entries written, assertions not counted
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<usize, BudgetingErrors> {
+        let out = match self.options.format {
+            JournalFormat::Beancount => self.beancount(),
+            JournalFormat::Ledger => self.ledger(),
+        };
+        writer
+            .write_all(out.as_bytes())
+            .map_err(|e| BudgetingErrors::IoError(e.to_string()))?;
+        Ok(self.entries.len())
+    }
+
+    fn amount(&self, amount: f64) -> String {
+        format!("{:.2} {}", amount, self.options.currency)
+    }
+
+    fn beancount(&self) -> String {
+        let quote = |s: &str| format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""));
+        let mut out = format!("option \"operating_currency\" \"{}\"\n\n", self.options.currency);
+        if let Some(first) = self.entries.first() {
+            for account in &self.accounts {
+                out.push_str(&format!("{} open {}\n", first.date, account));
+            }
+            out.push('\n');
+        }
+        let mut assertions = self.assertions.iter().peekable();
+        for e in &self.entries {
+            // a beancount balance is checked at the start of its day
+            let mut asserted = false;
+            while let Some(a) = assertions.next_if(|a| a.date < e.date) {
+                out.push_str(&format!(
+                    "{} balance {} {}\n",
+                    a.date + Days::new(1),
+                    a.account,
+                    self.amount(a.balance)
+                ));
+                asserted = true;
+            }
+            if asserted {
+                out.push('\n');
+            }
+            out.push_str(&format!("{} * {} {}", e.date, quote(&e.payee), quote(&e.note)));
+            for tag in &e.tags {
+                out.push_str(&format!(" #{}", account_component(tag)));
+            }
+            out.push('\n');
+            for p in &e.postings {
+                out.push_str(&format!("  {}  {}\n", p.account, self.amount(p.amount)));
+            }
+            out.push('\n');
+        }
+        for a in assertions {
+            out.push_str(&format!(
+                "{} balance {} {}\n",
+                a.date + Days::new(1),
+                a.account,
+                self.amount(a.balance)
+            ));
+        }
+        out
+    }
+
+    fn ledger(&self) -> String {
+        let mut out = String::new();
+        for account in &self.accounts {
+            out.push_str(&format!("account {}\n", account));
+        }
+        out.push('\n');
+        let mut assertions = self.assertions.iter().peekable();
+        let mut write_assertions = |out: &mut String, date: Option<NaiveDate>| {
+            let mut day = None;
+            while let Some(a) = assertions.next_if(|a| date.is_none_or(|d| a.date < d)) {
+                if day != Some(a.date) {
+                    if day.is_some() {
+                        out.push('\n');
+                    }
+                    out.push_str(&format!("{} * Balance assertions\n", a.date));
+                    day = Some(a.date);
+                }
+                // a zero posting only checks the balance
+                out.push_str(&format!(
+                    "    {}  {} = {}\n",
+                    a.account,
+                    self.amount(0.),
+                    self.amount(a.balance)
+                ));
+            }
+            if day.is_some() {
+                out.push('\n');
+            }
+        };
+        for e in &self.entries {
+            write_assertions(&mut out, Some(e.date));
+            out.push_str(&format!("{} * {}\n", e.date, e.payee.replace('\n', " ")));
+            if !e.note.is_empty() {
+                out.push_str(&format!("    ; {}\n", e.note.replace('\n', " ")));
+            }
+            if !e.tags.is_empty() {
+                let tags: Vec<String> = e.tags.iter().map(|t| account_component(t)).collect();
+                out.push_str(&format!("    ; :{}:\n", tags.join(":")));
+            }
+            for p in &e.postings {
+                out.push_str(&format!("    {}  {}\n", p.account, self.amount(p.amount)));
+            }
+            out.push('\n');
+        }
+        write_assertions(&mut out, None);
+        out
+    }
+}
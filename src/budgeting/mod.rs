@@ -1,15 +1,54 @@
+use crate::budgeting::backup::{Backup, BackupSettings};
 use crate::budgeting::budget_account::{
     BudgetAccount, BudgetAccountBuilder, BudgetAccountModel,
 };
+use crate::budgeting::categorization_rule::{
+    CategorizationRule, CategorizationRuleBuilder, CategorizationRuleModel, RuleChange, RuleOutcome,
+    RuleSet,
+};
 use crate::budgeting::category::{Category, CategoryBuilder, CategoryModel};
+use crate::budgeting::category_suggestion::{CategoryClassifier, CategorySuggestion};
+use crate::budgeting::duplicate::{
+    DuplicateCandidate, DuplicateMatcher, DuplicateMode, DuplicateProbe, DuplicateSettings,
+};
+use crate::budgeting::export::{write_json, CategorySummary, ExportOptions, TransactionExporter};
+use crate::budgeting::funding_template::{
+    FundingLine, FundingSummary, FundingTemplate, FundingTemplateBuilder, FundingTemplateModel,
+};
+use crate::budgeting::import::budget_app::{AppBudgeted, AppEntry, AppTransaction, BudgetAppSettings};
+use crate::budgeting::import::bank_csv::{ImportProfile, ImportProfileBuilder, ImportProfileModel};
+use crate::budgeting::import::qif::{QifEntry, QifRecord, QifTarget};
+use crate::budgeting::import::{budget_app, ofx, qif, ImportReport, ImportedTransaction, ParsedRow, RowOutcome};
+use crate::budgeting::journal::{Journal, JournalOptions};
+use crate::budgeting::income_allocation_rule::{IncomeAllocationRule, IncomeAllocationRuleModel};
+use crate::budgeting::overspending::{OverspendingCover, OverspentCategory};
+use crate::budgeting::period_allocation::{PeriodAllocation, PeriodAllocationModel};
+use crate::budgeting::reports::anomalies::{AnomalyReport, AnomalySettings};
+use crate::budgeting::reports::budget_vs_actual::BudgetVsActualReport;
+use crate::budgeting::reports::category_monthly::MonthlyCategoryReport;
+use crate::budgeting::reports::forecast::CashFlowForecast;
+use crate::budgeting::reports::income_expense::{Granularity, IncomeExpenseSeries};
+use crate::budgeting::reports::payees::TopPayeesReport;
+use crate::budgeting::reports::{month_start, start_of_day};
+use crate::budgeting::scheduled_transaction::{
+    ScheduledTransaction, ScheduledTransactionBuilder, ScheduledTransactionModel,
+};
+use crate::budgeting::search::SearchMatch;
+use crate::budgeting::tag::{Tag, TagModel};
+use crate::budgeting::transaction_query::{SortKey, SortOrder, TransactionQuery};
 use crate::budgeting::transaction::{
-    Transaction, TransactionBuilder, TransactionForm, TransactionModel, TransactionType,
+    OverspendingMode, Transaction, TransactionBuilder, TransactionForm, TransactionModel,
+    TransactionType, TransactionWithBalance,
 };
-use crate::{establish_connection, DEFAULT_CATEGORY};
+use crate::{current_date, establish_connection, DEFAULT_CATEGORY};
+use chrono::{Months, NaiveDate, NaiveDateTime};
 use budgeting_errors::BudgetingErrors;
 use diesel::connection::BoxableConnection;
 use diesel::dsl::sum;
-use diesel::{BoolExpressionMethods, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection};
+use diesel::connection::TransactionManager;
+use diesel::{
+    BoolExpressionMethods, Connection, QueryDsl, QueryResult, RunQueryDsl, SqliteConnection,
+};
 use std::borrow::BorrowMut;
 use std::cell::{RefCell};
 use std::collections::HashMap;
@@ -18,14 +57,36 @@ use std::ops::{Deref, DerefMut};
 use std::rc::Rc;
 use crate::speller::Speller;
 
+pub mod backup;
 pub mod budget_account;
 pub mod budgeting_errors;
+pub mod categorization_rule;
 pub mod category;
+pub mod category_suggestion;
+pub mod duplicate;
+pub mod export;
+pub mod funding_template;
+pub mod import;
+pub mod income_allocation_rule;
+pub mod journal;
+pub mod overspending;
+pub mod period_allocation;
+pub mod reports;
+pub mod scheduled_transaction;
+pub mod search;
+pub mod tag;
 pub mod transaction;
+pub mod transaction_query;
+mod validation;
 
 pub struct Budgeting {
     conn: Rc<RefCell<SqliteConnection>>,
     budget: Option<BudgetAccount>,
+    overspending_mode: OverspendingMode,
+    duplicate_mode: DuplicateMode,
+    duplicate_settings: DuplicateSettings,
+    categorization_rules_on_entry: bool,
+    import_suggestion_confidence: Option<f64>,
 }
 
 impl Budgeting {
@@ -36,21 +97,35 @@ impl Budgeting {
 
     pub fn new(conn: SqliteConnection) -> Self {
         let mut conn = Rc::new(RefCell::new(conn));
-        Budgeting { conn, budget: None }
+        Budgeting {
+            conn,
+            budget: None,
+            overspending_mode: OverspendingMode::default(),
+            duplicate_mode: DuplicateMode::default(),
+            duplicate_settings: DuplicateSettings::default(),
+            categorization_rules_on_entry: false,
+            import_suggestion_confidence: None,
+        }
     }
 
-    /// creates a new budget and set as current budget
+    /// creates a new budget and set as current budget, a non zero `amount`
+    /// is recorded as the initial balance
     pub fn new_budget(
         &mut self,
         filed_as: &str,
         amount: f64,
     ) -> Result<BudgetAccount, BudgetingErrors> {
+        validation::not_empty("filed_as", filed_as)?;
+        validation::non_negative("amount", amount)?;
         let budget_account = self.find_budget(filed_as);
         if budget_account.is_ok() {
             return Err(BudgetingErrors::FailedToCreateBudget(filed_as.to_string()));
         }
         let b = BudgetAccountBuilder::new(Rc::clone(&self.conn), filed_as).build()?;
         self.budget = Some(b.clone());
+        if amount == 0. {
+            return Ok(b);
+        }
         self.new_transaction_to_category(DEFAULT_CATEGORY)?
             .income(amount)
             .payee("Self")
@@ -71,6 +146,94 @@ impl Budgeting {
         }
     }
 
+    /// In strict mode expenses that would drive their category negative are rejected,
+    /// in permissive mode (default) they are saved and flagged as overspent
+    pub fn set_overspending_mode(&mut self, mode: OverspendingMode) {
+        self.overspending_mode = mode;
+    }
+
+    pub fn overspending_mode(&self) -> OverspendingMode {
+        self.overspending_mode
+    }
+
+    /// How incomes and expenses that look like one already saved are handled, both when
+    /// entered with `new_transaction_to_category` and when imported from a bank CSV or
+    /// OFX statement. Off by default.
+    pub fn set_duplicate_detection(
+        &mut self,
+        mode: DuplicateMode,
+        settings: DuplicateSettings,
+    ) -> Result<(), BudgetingErrors> {
+        validation::duplicate_settings(&settings)?;
+        self.duplicate_mode = mode;
+        self.duplicate_settings = settings;
+        Ok(())
+    }
+
+    pub fn duplicate_mode(&self) -> DuplicateMode {
+        self.duplicate_mode
+    }
+
+    /// Categorization rules always run on imported transactions, with this they also run
+    /// on transactions entered with `new_transaction_to_category`. Off by default.
+    pub fn set_categorization_rules_on_entry(&mut self, apply: bool) {
+        self.categorization_rules_on_entry = apply;
+    }
+
+    /// Imported expenses without a category, from the file or from categorization rules,
+    /// go to the suggested category when `suggest_category` is at least `min_confidence`
+    /// sure about it. `None`, the default, leaves them in the default category.
+    pub fn set_import_category_suggestions(&mut self, min_confidence: Option<f64>) {
+        self.import_suggestion_confidence = min_confidence;
+    }
+
+    /// Categories an expense with this payee, note and amount probably belongs to, most
+    /// likely first, learned from the categorized expenses of the current budget account.
+    /// The name of the first one can be given to `new_transaction_to_category`.
+    pub fn suggest_category(
+        &mut self,
+        payee: &str,
+        note: &str,
+        amount: f64,
+    ) -> Result<Vec<CategorySuggestion>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let classifier = CategoryClassifier::build(gc!(*self.conn), b.id())?;
+        let names: HashMap<i32, String> = self
+            .all_categories()
+            .into_iter()
+            .map(|c| (c.id(), c.name()))
+            .collect();
+        Ok(classifier
+            .rank(payee, note, amount)
+            .into_iter()
+            .map(|(id, confidence)| CategorySuggestion {
+                category_id: id,
+                category: names.get(&id).cloned().unwrap_or_default(),
+                confidence,
+            })
+            .collect())
+    }
+
+    /// transactions of the current budget account the given income (positive amount) or
+    /// expense (negative amount) would duplicate, best match first
+    pub fn find_duplicates(
+        &self,
+        date_created: NaiveDateTime,
+        amount: f64,
+        payee: &str,
+    ) -> Result<Vec<DuplicateCandidate>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let probe = DuplicateProbe {
+            date_created,
+            amount,
+            payee,
+            fitid: None,
+        };
+        DuplicateMatcher::find(gc!(*self.conn), b.id(), &probe, &self.duplicate_settings)
+    }
+
     /// Starts a new transaction belonging to given category.
     /// it's not completed until `done` method is called
     pub fn new_transaction_to_category(&self, category: &str) -> Result<TransactionBuilder, BudgetingErrors> {
@@ -78,12 +241,15 @@ impl Budgeting {
             .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
         match self.find_category(category) {
             Ok(_category) => {
-                Ok(
-                    TransactionBuilder::new(
-                        Rc::clone(&self.conn),
-                        b.id(),
-                        _category.id())
-                )
+                let mut builder = TransactionBuilder::new(
+                    Rc::clone(&self.conn),
+                    b.id(),
+                    _category.id());
+                builder
+                    .overspending_mode(self.overspending_mode)
+                    .duplicate_detection(self.duplicate_mode, self.duplicate_settings)
+                    .categorization_rules(self.categorization_rules_on_entry);
+                Ok(builder)
             }
             Err(e) => {
                 if let BudgetingErrors::CategoryNotFound = e {
@@ -116,19 +282,228 @@ impl Budgeting {
         dest: &str,
         amount: f64,
     ) -> Result<(), BudgetingErrors> {
-        let k = self
-            .new_transaction_to_category(src)?
-            .transfer_from(amount)
+        self.transfer(src, dest, amount, false, None)?;
+        Ok(())
+    }
+
+    /// creates both sides of a transfer, the receiving side is linked to the sending side
+    fn transfer(
+        &mut self,
+        src: &str,
+        dest: &str,
+        amount: f64,
+        overspending_cover: bool,
+        date_created: Option<NaiveDateTime>,
+    ) -> Result<(Transaction, Transaction), BudgetingErrors> {
+        let mut from = self.new_transaction_to_category(src)?;
+        from.transfer_from(amount)
             .payee(dest)
-            .note(&format!("Funded"))
-            .done()?;
-        self.new_transaction_to_category(dest)?
-            .transfer_to(amount)
+            .note("Funded");
+        if overspending_cover {
+            from.overspending_cover();
+        }
+        if let Some(d) = date_created {
+            from.date_created(d);
+        }
+        let k = from.done()?;
+        let mut to = self.new_transaction_to_category(dest)?;
+        to.transfer_to(amount)
             .transfer_category_id(k.category_id())
+            .linked_transaction_id(k.id())
             .payee(src)
-            .note(&format!("Received"))
-            .done()?;
-        Ok(())
+            .note("Received");
+        if overspending_cover {
+            to.overspending_cover();
+        }
+        if let Some(d) = date_created {
+            to.date_created(d);
+        }
+        let l = to.done()?;
+        Ok((k, l))
+    }
+
+    /// Categories, the default category included, with a negative balance in the current
+    /// budget account at the end of the month `month` is in. Like period allocations a
+    /// period is a month, and what was left or overspent in earlier months carries over.
+    pub fn overspent_categories(&mut self, month: NaiveDate) -> Result<Vec<OverspentCategory>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        // no end for the last month chrono knows
+        let end = month_start(month)
+            .checked_add_months(Months::new(1))
+            .and_then(|d| d.pred_opt());
+        let mut overspent = vec![];
+        for category in self.all_categories() {
+            let balance =
+                TransactionModel::total_as_of(gc!(*self.conn), None, Some(category.id()), Some(b.id()), end);
+            if balance < 0. {
+                overspent.push(OverspentCategory { category, balance });
+            }
+        }
+        Ok(overspent)
+    }
+
+    /// Covers the shortfall of an overspent category by pulling money from the given
+    /// categories, in order, as much as each of them has. The transfers are marked
+    /// as overspending cover, see `overspending_covers`. If the sources do not have enough
+    /// the category is covered partially, check `OverspendingCover::remaining`
+    pub fn cover_overspending(
+        &mut self,
+        category: &str,
+        sources: &[&str],
+    ) -> Result<OverspendingCover, BudgetingErrors> {
+        let overspent = self.find_category(category)?;
+        let balance = self.category_balance(overspent.name_c())?;
+        if balance >= 0. {
+            return Err(BudgetingErrors::CategoryNotOverspent(overspent.name()));
+        }
+        self.in_transaction(|budgeting| {
+            let mut cover = OverspendingCover {
+                category: overspent.name(),
+                shortfall: -balance,
+                covered_from: vec![],
+            };
+            for source in sources {
+                let remaining = cover.remaining();
+                if remaining <= 0. {
+                    break;
+                }
+                let src = budgeting.find_category(source)?;
+                let available = budgeting.category_balance(src.name_c())?;
+                let amount = remaining.min(available);
+                if amount <= 0. {
+                    continue;
+                }
+                budgeting.transfer(src.name_c(), overspent.name_c(), amount, true, None)?;
+                cover.covered_from.push((src.name(), amount));
+            }
+            Ok(cover)
+        })
+    }
+
+    /// transfers done to cover overspending in the current budget account,
+    /// only the receiving side of the transfers is returned
+    pub fn overspending_covers(&mut self) -> Result<Vec<Transaction>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        Ok(TransactionModel::find_overspending_covers(gc!(*self.conn), Some(b.id())))
+    }
+
+    /// Runs `f` in a single database transaction, everything done by `f` is rolled back
+    /// if it returns an error. The connection is borrowed only while starting and finishing
+    /// the transaction, so `f` is free to use any other method of `Budgeting`
+    fn in_transaction<T, F>(&mut self, f: F) -> Result<T, BudgetingErrors>
+    where
+        F: FnOnce(&mut Self) -> Result<T, BudgetingErrors>,
+    {
+        type Tm = <SqliteConnection as Connection>::TransactionManager;
+        Tm::begin_transaction(gc!(*self.conn))?;
+        match f(self) {
+            Ok(value) => {
+                Tm::commit_transaction(gc!(*self.conn))?;
+                Ok(value)
+            }
+            Err(e) => {
+                Tm::rollback_transaction(gc!(*self.conn))?;
+                Err(e)
+            }
+        }
+    }
+
+    /// Starts a new funding template, it's not saved until `done` method is called
+    pub fn funding_template_builder(&mut self, name: &str) -> FundingTemplateBuilder {
+        FundingTemplateBuilder::new(Rc::clone(&self.conn), name)
+    }
+
+    pub fn funding_templates(&mut self) -> Result<Vec<FundingTemplate>, BudgetingErrors> {
+        FundingTemplateModel::find_all(gc!(*self.conn))
+    }
+
+    pub fn funding_template_model(&mut self, name: &str) -> Result<FundingTemplateModel, BudgetingErrors> {
+        let t = FundingTemplateModel::load_by_name(gc!(*self.conn), name)?;
+        Ok(FundingTemplateModel::new(Rc::clone(&self.conn), t))
+    }
+
+    pub fn delete_funding_template(&mut self, name: &str) -> Result<usize, BudgetingErrors> {
+        let t = FundingTemplateModel::load_by_name(gc!(*self.conn), name)?;
+        FundingTemplateModel::delete(gc!(*self.conn), t.id())
+    }
+
+    /// Funds every category of the template from `source_category`, in the order the items
+    /// were added. Percentages are calculated from the balance of the source category before
+    /// anything is transferred. When the source runs out, the remaining categories are funded
+    /// as much as possible and reported as short. All the transfers are done in one database
+    /// transaction, so either the whole template is applied or nothing is.
+    pub fn apply_template(
+        &mut self,
+        name: &str,
+        source_category: &str,
+    ) -> Result<FundingSummary, BudgetingErrors> {
+        let template = FundingTemplateModel::load_by_name(gc!(*self.conn), name)?;
+        let items = FundingTemplateModel::items_of(gc!(*self.conn), template.id())?;
+        let source = self.find_category(source_category)?;
+        self.in_transaction(|budgeting| {
+            let source_balance = budgeting.category_balance(source.name_c())?;
+            let mut available = source_balance;
+            let mut summary = FundingSummary {
+                template: template.name(),
+                source: source.name(),
+                ..Default::default()
+            };
+            for item in items {
+                let category = CategoryModel::load_by_id(gc!(*budgeting.conn), item.category_id())?;
+                let requested = item.requested(source_balance);
+                let funded = requested.min(available.max(0.));
+                if funded > 0. {
+                    budgeting.transfer_fund(source.name_c(), category.name_c(), funded)?;
+                    available -= funded;
+                }
+                let line = FundingLine {
+                    category: category.name(),
+                    requested,
+                    funded,
+                };
+                if funded < requested {
+                    summary.short.push(line);
+                } else {
+                    summary.funded.push(line);
+                }
+            }
+            Ok(summary)
+        })
+    }
+
+    /// Every income of the current budget account will transfer `percentage` of it
+    /// to the category, replaces the existing rule of the category
+    pub fn set_income_allocation_rule(
+        &mut self,
+        category: &str,
+        percentage: f64,
+    ) -> Result<IncomeAllocationRule, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let c = self.find_category(category)?;
+        IncomeAllocationRuleModel::set(gc!(*self.conn), b.id(), c.id(), percentage)
+    }
+
+    pub fn remove_income_allocation_rule(&mut self, category: &str) -> Result<usize, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let c = self.find_category(category)?;
+        IncomeAllocationRuleModel::delete(gc!(*self.conn), b.id(), c.id())
+    }
+
+    /// allocation rules of the current budget account
+    pub fn income_allocation_rules(&mut self) -> Result<Vec<IncomeAllocationRule>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        IncomeAllocationRuleModel::find_all(gc!(*self.conn), b.id())
+    }
+
+    /// transactions created because of the given transaction, i.e. the transfers
+    /// done by allocation rules for an income
+    pub fn linked_transactions(&mut self, transaction_id: i32) -> Vec<Transaction> {
+        TransactionModel::find_linked(gc!(*self.conn), transaction_id)
     }
 
     pub fn update_budget_account(
@@ -223,7 +598,7 @@ impl Budgeting {
         transfer: bool,
     ) -> Result<Category, BudgetingErrors> {
         let c = self.category_builder(category).allocated(allocate).done()?;
-        if transfer {
+        if transfer && allocate > 0. {
             self.transfer_fund(DEFAULT_CATEGORY, category, allocate)?;
         }
         Ok(c)
@@ -274,6 +649,21 @@ impl Budgeting {
         CategoryModel::_balance(gc!(*self.conn), None, category)
     }
 
+    /// balance of the category at the end of the given day
+    pub fn category_balance_as_of(&self, category: &str, date: NaiveDate) -> Result<f64, BudgetingErrors> {
+        CategoryModel::_balance_as_of(gc!(*self.conn), None, category, Some(date))
+    }
+
+    /// real money in the budget account at the end of the given day
+    pub fn budget_account_balance_as_of(
+        &self,
+        budget_account: &str,
+        date: NaiveDate,
+    ) -> Result<f64, BudgetingErrors> {
+        let b = BudgetAccountModel::load_by_name(gc!(*self.conn), budget_account)?;
+        Ok(BudgetAccountModel::_balance_as_of(gc!(*self.conn), b.id(), Some(date)))
+    }
+
     pub fn find_budget(&self, _filed_as: &str) -> Result<BudgetAccount, BudgetingErrors> {
         imp_db!(budget_accounts);
         let res: QueryResult<BudgetAccount> = budget_accounts
@@ -396,6 +786,31 @@ impl Budgeting {
         i + e
     }
 
+    /// `actual_total_balance` at the end of the given day
+    pub fn actual_total_balance_as_of(&mut self, date: NaiveDate) -> f64 {
+        let i = TransactionModel::total_as_of(
+            gc!(*self.conn),
+            Some(TransactionType::Income),
+            None,
+            None,
+            Some(date),
+        );
+        let e = TransactionModel::total_as_of(
+            gc!(*self.conn),
+            Some(TransactionType::Expense),
+            None,
+            None,
+            Some(date),
+        );
+        i + e
+    }
+
+    /// `uncategorized_balance` at the end of the given day
+    pub fn uncategorized_balance_as_of(&mut self, date: NaiveDate) -> f64 {
+        let c = self.default_category();
+        TransactionModel::total_as_of(gc!(*self.conn), None, Some(c.id()), None, Some(date))
+    }
+
     /// returns the total unallocated balance
     pub fn uncategorized_balance(&mut self) -> f64 {
         let c = self.default_category();
@@ -438,6 +853,781 @@ impl Budgeting {
         let bid = Some(self.current_budget().unwrap().id());
         TransactionModel::find_all(gc!(*self.conn), _category_id, bid)
     }
+
+    /// transactions of the current budget account, or only of the given category in it,
+    /// oldest first with the balance after each transaction
+    pub fn transactions_with_running_balance(
+        &mut self,
+        category: Option<&str>,
+    ) -> Result<Vec<TransactionWithBalance>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let cid = match category {
+            Some(c) => Some(self.find_category(c)?.id()),
+            None => None,
+        };
+        TransactionModel::with_running_balance(gc!(*self.conn), cid, Some(b.id()))
+    }
+
+    /// a query limited to the current budget account
+    pub fn transaction_query(&self) -> Result<TransactionQuery, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let mut query = TransactionQuery::new();
+        query.budget_account_id(b.id());
+        Ok(query)
+    }
+
+    pub fn find_transactions(&mut self, query: &TransactionQuery) -> Result<Vec<Transaction>, BudgetingErrors> {
+        query.load(gc!(*self.conn))
+    }
+
+    /// number of transactions matching the query, limit and offset are ignored
+    pub fn count_transactions(&mut self, query: &TransactionQuery) -> Result<i64, BudgetingErrors> {
+        query.count(gc!(*self.conn))
+    }
+
+    /// sum of the amounts of transactions matching the query, limit and offset are ignored
+    pub fn sum_transactions(&mut self, query: &TransactionQuery) -> Result<f64, BudgetingErrors> {
+        query.sum(gc!(*self.conn))
+    }
+
+    /// Full text search over payee and note of the transactions in the current
+    /// budget account, best matches first
+    pub fn search_transactions(
+        &mut self,
+        text: &str,
+        limit: i64,
+    ) -> Result<Vec<SearchMatch>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        search::search(gc!(*self.conn), text, b.id(), limit)
+    }
+
+    /// income, expense and transfers of every category for every month from `from`
+    /// to `to`, both days included, in the current budget account
+    pub fn monthly_category_report(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<MonthlyCategoryReport, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        MonthlyCategoryReport::build(gc!(*self.conn), b.id(), from, to)
+    }
+
+    /// incomes and expenses of the current budget account from `from` to `to`, both days
+    /// included, bucketed by `granularity`. `filter` can limit it to categories or tags
+    pub fn income_expense_series(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        granularity: Granularity,
+        filter: &TransactionQuery,
+    ) -> Result<IncomeExpenseSeries, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        IncomeExpenseSeries::build(gc!(*self.conn), b.id(), from, to, granularity, filter)
+    }
+
+    /// the `top` payees the most was spent on from `from` to `to`, both days included,
+    /// in the current budget account
+    pub fn top_payees_report(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        top: i64,
+    ) -> Result<TopPayeesReport, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        TopPayeesReport::build(gc!(*self.conn), b.id(), from, to, top)
+    }
+
+    /// unusual expenses and months of spending from `from` to `to`, both days included,
+    /// in the current budget account
+    pub fn anomaly_report(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+        settings: AnomalySettings,
+    ) -> Result<AnomalyReport, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        AnomalyReport::build(gc!(*self.conn), b.id(), from, to, settings)
+    }
+
+    /// allocated, funded and spent of every category for every month from `from`
+    /// to `to`, both days included, in the current budget account
+    pub fn budget_vs_actual_report(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<BudgetVsActualReport, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        BudgetVsActualReport::build(gc!(*self.conn), b.id(), from, to)
+    }
+
+    /// allocation of the category for the month `month` is in, instead of `Category::allocated`
+    pub fn set_period_allocation(
+        &mut self,
+        category: &str,
+        month: NaiveDate,
+        amount: f64,
+    ) -> Result<PeriodAllocation, BudgetingErrors> {
+        let c = self.find_category(category)?;
+        PeriodAllocationModel::set(gc!(*self.conn), c.id(), month, amount)
+    }
+
+    pub fn period_allocations(&mut self, category: &str) -> Result<Vec<PeriodAllocation>, BudgetingErrors> {
+        let c = self.find_category(category)?;
+        PeriodAllocationModel::find_all(gc!(*self.conn), c.id())
+    }
+
+    /// Starts a new scheduled transaction in the current budget account,
+    /// it's not saved until `done` method is called
+    pub fn new_scheduled_transaction_to_category(
+        &self,
+        category: &str,
+    ) -> Result<ScheduledTransactionBuilder<'_>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let c = self.find_category(category)?;
+        Ok(ScheduledTransactionBuilder::new(Rc::clone(&self.conn), b.id(), c.id()))
+    }
+
+    /// scheduled transactions of the current budget account
+    pub fn scheduled_transactions(&mut self) -> Result<Vec<ScheduledTransaction>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        ScheduledTransactionModel::find_all(gc!(*self.conn), b.id())
+    }
+
+    pub fn delete_scheduled_transaction(&mut self, scheduled_id: i32) -> Result<usize, BudgetingErrors> {
+        ScheduledTransactionModel::delete(gc!(*self.conn), scheduled_id)
+    }
+
+    /// balance of the current budget account for each of the next `days` days, based on
+    /// scheduled transactions and the average spending of the last `lookback_days` days,
+    /// both up to `MAX_FORECAST_DAYS`
+    pub fn cash_flow_forecast(
+        &mut self,
+        days: u32,
+        lookback_days: u32,
+    ) -> Result<CashFlowForecast, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        CashFlowForecast::build(gc!(*self.conn), b.id(), current_date().date(), days, lookback_days)
+    }
+
+    /// writes the transactions matching `query` with the columns, date format and sign
+    /// convention of `options`, returns the number of transactions written
+    pub fn export_transactions<W: std::io::Write>(
+        &mut self,
+        query: &TransactionQuery,
+        options: &ExportOptions,
+        writer: W,
+    ) -> Result<usize, BudgetingErrors> {
+        let found = query.load(gc!(*self.conn))?;
+        TransactionExporter::export(gc!(*self.conn), &found, options, writer)
+    }
+
+    /// every budget account, category, transaction and the settings of `Budgeting`, see
+    /// `backup` for the format
+    pub fn backup(&mut self) -> Result<Backup, BudgetingErrors> {
+        let settings = BackupSettings {
+            overspending_mode: self.overspending_mode,
+            duplicate_mode: self.duplicate_mode,
+            duplicate_settings: self.duplicate_settings,
+            categorization_rules_on_entry: self.categorization_rules_on_entry,
+            import_suggestion_confidence: self.import_suggestion_confidence,
+        };
+        Backup::build(gc!(*self.conn), settings)
+    }
+
+    /// writes `backup` as one JSON document
+    pub fn export_all<W: std::io::Write>(&mut self, writer: W) -> Result<(), BudgetingErrors> {
+        write_json(&self.backup()?, writer)
+    }
+
+    /// Restores a backup into an empty database, all or nothing, and takes over its
+    /// settings. No budget account is selected afterwards.
+    pub fn restore(&mut self, backup: &Backup) -> Result<(), BudgetingErrors> {
+        self.in_transaction(|b| backup.restore(gc!(*b.conn)))?;
+        let settings = backup.settings.clone();
+        self.overspending_mode = settings.overspending_mode;
+        self.duplicate_mode = settings.duplicate_mode;
+        self.duplicate_settings = settings.duplicate_settings;
+        self.categorization_rules_on_entry = settings.categorization_rules_on_entry;
+        self.import_suggestion_confidence = settings.import_suggestion_confidence;
+        self.budget = None;
+        Ok(())
+    }
+
+    /// reads a document written by `export_all` and restores it
+    pub fn import_all<R: std::io::Read>(&mut self, reader: R) -> Result<(), BudgetingErrors> {
+        let backup: Backup = serde_json::from_reader(reader)
+            .map_err(|e| BudgetingErrors::InvalidBackup(e.to_string()))?;
+        self.restore(&backup)
+    }
+
+    /// every budget account as a beancount or hledger/ledger journal, see `journal`,
+    /// returns the number of entries written
+    pub fn export_journal<W: std::io::Write>(
+        &mut self,
+        options: &JournalOptions,
+        writer: W,
+    ) -> Result<usize, BudgetingErrors> {
+        Journal::build(gc!(*self.conn), options)?.write(writer)
+    }
+
+    /// totals of every category in the current budget account
+    pub fn category_summaries(&mut self) -> Result<Vec<CategorySummary>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        CategorySummary::build(gc!(*self.conn), b.id())
+    }
+
+    /// new CSV mapping profile for the current budget account
+    pub fn import_profile_builder(&mut self, name: &str) -> Result<ImportProfileBuilder, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        Ok(ImportProfileBuilder::new(Rc::clone(&self.conn), b.id(), name))
+    }
+
+    pub fn import_profiles(&mut self) -> Result<Vec<ImportProfile>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        ImportProfileModel::find_all(gc!(*self.conn), b.id())
+    }
+
+    pub fn delete_import_profile(&mut self, name: &str) -> Result<usize, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let p = ImportProfileModel::load_by_name(gc!(*self.conn), b.id(), name)?;
+        ImportProfileModel::delete(gc!(*self.conn), p.id())
+    }
+
+    /// Imports a bank statement into the current budget account using one of its profiles.
+    /// Every line is imported on its own, a failing line does not stop the others.
+    pub fn import_csv<R: std::io::Read>(
+        &mut self,
+        profile: &str,
+        reader: R,
+    ) -> Result<ImportReport, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let p = ImportProfileModel::load_by_name(gc!(*self.conn), b.id(), profile)?;
+        let rows = p.parse(reader)?;
+        Ok(self.import_rows(rows))
+    }
+
+    /// Imports an OFX or QFX statement into the current budget account. Transactions
+    /// whose FITID was already imported into the account are skipped.
+    pub fn import_ofx<R: std::io::Read>(&mut self, mut reader: R) -> Result<ImportReport, BudgetingErrors> {
+        self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| BudgetingErrors::IoError(e.to_string()))?;
+        let rows = ofx::parse(&String::from_utf8_lossy(&data));
+        Ok(self.import_rows(rows))
+    }
+
+    /// Imports the bank, cash and credit card sections of a QIF file into the current
+    /// budget account. Missing categories are created when `create_categories` is set,
+    /// otherwise their records fail. Every record is imported in one database transaction,
+    /// the report has a row for every transaction created, so a split record has several.
+    pub fn import_qif<R: std::io::Read>(
+        &mut self,
+        mut reader: R,
+        create_categories: bool,
+    ) -> Result<ImportReport, BudgetingErrors> {
+        self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| BudgetingErrors::IoError(e.to_string()))?;
+        let mut report = ImportReport::default();
+        for entry in qif::parse(&String::from_utf8_lossy(&data)) {
+            match entry {
+                QifEntry::Record { line, record } => {
+                    match self.in_transaction(|b| b.import_qif_record(&record, create_categories)) {
+                        Ok(ids) if ids.is_empty() => {
+                            report.push(line, RowOutcome::Skipped("no amount".to_string()))
+                        }
+                        Ok(ids) => ids
+                            .into_iter()
+                            .for_each(|id| report.push(line, RowOutcome::Created(id))),
+                        Err(e) => report.push(line, RowOutcome::Failed(e.to_string())),
+                    }
+                }
+                QifEntry::Skipped { line, reason } => report.push(line, RowOutcome::Skipped(reason)),
+                QifEntry::Failed { line, error } => report.push(line, RowOutcome::Failed(error)),
+            }
+        }
+        Ok(report)
+    }
+
+    fn import_qif_record(
+        &mut self,
+        record: &QifRecord,
+        create_categories: bool,
+    ) -> Result<Vec<i32>, BudgetingErrors> {
+        let mut ids = vec![];
+        for split in &record.splits {
+            if split.amount == 0. {
+                continue;
+            }
+            let note = if split.memo.is_empty() { &record.memo } else { &split.memo };
+            match &split.target {
+                QifTarget::Transfer(category) => {
+                    if create_categories && self.find_category(category).is_err() {
+                        self.create_category(category, 0., false)?;
+                    }
+                    let (from, to) = if split.amount < 0. {
+                        (DEFAULT_CATEGORY, category.as_str())
+                    } else {
+                        (category.as_str(), DEFAULT_CATEGORY)
+                    };
+                    let (k, l) =
+                        self.transfer(from, to, split.amount.abs(), false, Some(record.date_created))?;
+                    ids.push(k.id());
+                    ids.push(l.id());
+                }
+                QifTarget::Category(category) => {
+                    if let Some(c) = category {
+                        if create_categories && self.find_category(c).is_err() {
+                            self.create_category(c, 0., false)?;
+                        }
+                    }
+                    let payee = if record.payee.is_empty() { note } else { &record.payee };
+                    let imported = ImportedTransaction {
+                        date_created: record.date_created,
+                        amount: split.amount,
+                        payee: payee.clone(),
+                        note: note.clone(),
+                        category: category.clone(),
+                        fitid: None,
+                    };
+                    ids.push(self.create_imported(&imported, None)?.id());
+                }
+            }
+        }
+        Ok(ids)
+    }
+
+    /// writes every transaction of the current budget account as QIF, oldest first,
+    /// returns the number of records written. Nothing is written when a category name
+    /// has a `/` or a `[`, QIF would read it back as another category or a transfer
+    pub fn export_qif<W: std::io::Write>(&mut self, writer: W) -> Result<usize, BudgetingErrors> {
+        let mut query = self.transaction_query()?;
+        query.sort_by(SortKey::DateCreated, SortOrder::Ascending);
+        let found = query.load(gc!(*self.conn))?;
+        let names: HashMap<i32, String> = self
+            .all_categories()
+            .into_iter()
+            .map(|c| (c.id(), c.name()))
+            .collect();
+        qif::export(&found, &names, writer)
+    }
+
+    /// Imports a YNAB4 register or budget export, or an Actual Budget transaction export,
+    /// see `import::budget_app`. Missing budget accounts and categories are created, a
+    /// transfer between two accounts moves money between their default categories and
+    /// budgeted amounts become period allocations. Every row is imported in one database
+    /// transaction, the budget account selected before, or none, is selected again
+    /// afterwards.
+    pub fn import_budget_app<R: std::io::Read>(
+        &mut self,
+        mut reader: R,
+        settings: &BudgetAppSettings,
+    ) -> Result<ImportReport, BudgetingErrors> {
+        let mut data = vec![];
+        reader
+            .read_to_end(&mut data)
+            .map_err(|e| BudgetingErrors::IoError(e.to_string()))?;
+        let selected = self.current_budget();
+        let mut report = ImportReport::default();
+        for entry in budget_app::parse(&String::from_utf8_lossy(&data), settings) {
+            match entry {
+                AppEntry::Transaction { line, transaction } => {
+                    match self.in_transaction(|b| b.import_app_transaction(&transaction)) {
+                        Ok(ids) => ids
+                            .into_iter()
+                            .for_each(|id| report.push(line, RowOutcome::Created(id))),
+                        Err(e) => {
+                            // the budget account selected for the row may have been rolled back
+                            self.budget = selected.clone();
+                            report.push(line, RowOutcome::Failed(e.to_string()))
+                        }
+                    }
+                }
+                AppEntry::Budgeted { line, budgeted } => {
+                    match self.in_transaction(|b| b.import_app_budgeted(&budgeted)) {
+                        Ok(a) => report.push(line, RowOutcome::Created(a.id())),
+                        Err(e) => {
+                            self.budget = selected.clone();
+                            report.push(line, RowOutcome::Failed(e.to_string()))
+                        }
+                    }
+                }
+                AppEntry::Skipped { line, reason } => report.push(line, RowOutcome::Skipped(reason)),
+                AppEntry::Failed { line, error } => report.push(line, RowOutcome::Failed(error)),
+            }
+        }
+        self.budget = selected;
+        Ok(report)
+    }
+
+    /// selects the budget account, created without initial balance when missing
+    fn import_budget_account(&mut self, filed_as: &str) -> Result<BudgetAccount, BudgetingErrors> {
+        match self.find_budget(filed_as) {
+            Ok(b) => {
+                self.budget = Some(b.clone());
+                Ok(b)
+            }
+            Err(_) => self.new_budget(filed_as, 0.),
+        }
+    }
+
+    fn import_app_transaction(&mut self, transaction: &AppTransaction) -> Result<Vec<i32>, BudgetingErrors> {
+        if let Some(other) = &transaction.transfer_account {
+            let (from, to) = if transaction.amount < 0. {
+                (transaction.account.as_str(), other.as_str())
+            } else {
+                (other.as_str(), transaction.account.as_str())
+            };
+            let (k, l) = self.transfer_between_accounts(
+                from,
+                to,
+                transaction.amount.abs(),
+                transaction.date_created,
+                &transaction.note,
+            )?;
+            return Ok(vec![k.id(), l.id()]);
+        }
+        self.import_budget_account(&transaction.account)?;
+        if let Some(c) = &transaction.category {
+            if self.find_category(c).is_err() {
+                self.create_category(c, 0., false)?;
+            }
+        }
+        let payee = if transaction.payee.is_empty() { &transaction.note } else { &transaction.payee };
+        let imported = ImportedTransaction {
+            date_created: transaction.date_created,
+            amount: transaction.amount,
+            payee: payee.clone(),
+            note: transaction.note.clone(),
+            category: transaction.category.clone(),
+            fitid: None,
+        };
+        Ok(vec![self.create_imported(&imported, None)?.id()])
+    }
+
+    fn import_app_budgeted(&mut self, budgeted: &AppBudgeted) -> Result<PeriodAllocation, BudgetingErrors> {
+        if self.find_category(&budgeted.category).is_err() {
+            self.create_category(&budgeted.category, 0., false)?;
+        }
+        self.set_period_allocation(&budgeted.category, budgeted.month, budgeted.amount)
+    }
+
+    /// moves money from the default category of one budget account to the default
+    /// category of another, the receiving side is linked to the sending side
+    fn transfer_between_accounts(
+        &mut self,
+        from: &str,
+        to: &str,
+        amount: f64,
+        date_created: NaiveDateTime,
+        note: &str,
+    ) -> Result<(Transaction, Transaction), BudgetingErrors> {
+        self.import_budget_account(from)?;
+        let k = self
+            .new_transaction_to_category(DEFAULT_CATEGORY)?
+            .transfer_from(amount)
+            .payee(&format!("Transfer : {to}"))
+            .note(note)
+            .date_created(date_created)
+            .done()?;
+        self.import_budget_account(to)?;
+        let l = self
+            .new_transaction_to_category(DEFAULT_CATEGORY)?
+            .transfer_to(amount)
+            .transfer_category_id(k.category_id())
+            .linked_transaction_id(k.id())
+            .payee(&format!("Transfer : {from}"))
+            .note(note)
+            .date_created(date_created)
+            .done()?;
+        Ok((k, l))
+    }
+
+    fn import_rows(&mut self, rows: Vec<ParsedRow>) -> ImportReport {
+        let bid = self.current_budget().map(|b| b.id()).unwrap_or_default();
+        let rules = RuleSet::load(gc!(*self.conn), bid);
+        let suggestions = match self.import_suggestion_confidence {
+            Some(min) => CategoryClassifier::build(gc!(*self.conn), bid).ok().map(|c| (c, min)),
+            None => None,
+        };
+        let names: HashMap<i32, String> = self
+            .all_categories()
+            .into_iter()
+            .map(|c| (c.id(), c.name()))
+            .collect();
+        let mut report = ImportReport::default();
+        for row in rows {
+            match row {
+                ParsedRow::Transaction { line, mut transaction } => {
+                    if let Some(fitid) = &transaction.fitid {
+                        if TransactionModel::find_by_fitid(gc!(*self.conn), bid, fitid).is_some() {
+                            report.push(line, RowOutcome::Skipped(format!("already imported: {fitid}")));
+                            continue;
+                        }
+                    }
+                    let ruled = match &rules {
+                        Ok(r) => r.apply(&transaction.payee, &transaction.note),
+                        Err(_) => RuleOutcome::default(),
+                    };
+                    // saved transactions have the payee the rules gave them
+                    let payee = ruled.payee.unwrap_or_else(|| transaction.payee.clone());
+                    if let Some((classifier, min)) = &suggestions {
+                        if transaction.amount < 0.
+                            && transaction.category.is_none()
+                            && ruled.category_id.is_none()
+                        {
+                            transaction.category = classifier
+                                .rank(&payee, &transaction.note, transaction.amount)
+                                .first()
+                                .filter(|(_, confidence)| confidence >= min)
+                                .and_then(|(id, _)| names.get(id).cloned());
+                        }
+                    }
+                    let mut probe = DuplicateProbe::from(&transaction);
+                    probe.payee = &payee;
+                    let outcome = match self.import_duplicate_of(bid, &probe, &report) {
+                        Ok(Some(existing)) => match self.duplicate_mode {
+                            DuplicateMode::Skip => {
+                                RowOutcome::Skipped(format!("duplicate of transaction {}", existing.id()))
+                            }
+                            DuplicateMode::Merge => match DuplicateMatcher::merge(
+                                gc!(*self.conn),
+                                &existing,
+                                &transaction.note,
+                                transaction.fitid.as_deref(),
+                            ) {
+                                Ok(t) => RowOutcome::Merged(t.id()),
+                                Err(e) => RowOutcome::Failed(e.to_string()),
+                            },
+                            _ => match self.create_imported(&transaction, Some(existing.id())) {
+                                Ok(t) => RowOutcome::Created(t.id()),
+                                Err(e) => RowOutcome::Failed(e.to_string()),
+                            },
+                        },
+                        Ok(None) => match self.create_imported(&transaction, None) {
+                            Ok(t) => RowOutcome::Created(t.id()),
+                            Err(e) => RowOutcome::Failed(e.to_string()),
+                        },
+                        Err(e) => RowOutcome::Failed(e.to_string()),
+                    };
+                    report.push(line, outcome);
+                }
+                ParsedRow::Skipped { line, reason } => report.push(line, RowOutcome::Skipped(reason)),
+                ParsedRow::Failed { line, error } => report.push(line, RowOutcome::Failed(error)),
+            }
+        }
+        report
+    }
+
+    /// transaction saved before this import the imported one duplicates, transactions
+    /// created by the import itself are left out, a statement can list the same expense
+    /// twice on the same day
+    fn import_duplicate_of(
+        &mut self,
+        bid: i32,
+        probe: &DuplicateProbe,
+        report: &ImportReport,
+    ) -> Result<Option<Transaction>, BudgetingErrors> {
+        if self.duplicate_mode == DuplicateMode::Off {
+            return Ok(None);
+        }
+        let found = DuplicateMatcher::best_match(
+            gc!(*self.conn),
+            bid,
+            probe,
+            &self.duplicate_settings,
+            &report.created(),
+        )?;
+        Ok(found.map(|c| c.transaction))
+    }
+
+    /// incomes always go to the default category, expenses to their category if any or to
+    /// the one categorization rules give them, duplicates are looked for by the caller
+    fn create_imported(
+        &mut self,
+        imported: &ImportedTransaction,
+        duplicate_of: Option<i32>,
+    ) -> Result<Transaction, BudgetingErrors> {
+        let category = match imported.category.as_deref() {
+            Some(c) if imported.amount < 0. => c,
+            _ => DEFAULT_CATEGORY,
+        };
+        let mut builder = self.new_transaction_to_category(category)?;
+        builder
+            .duplicate_detection(DuplicateMode::Off, self.duplicate_settings)
+            .categorization_rules(true);
+        if let Some(fitid) = &imported.fitid {
+            builder.fitid(fitid);
+        }
+        if let Some(id) = duplicate_of {
+            builder.duplicate_of_id(id);
+        }
+        if imported.amount > 0. {
+            builder.income(imported.amount);
+        } else {
+            builder.expense(-imported.amount);
+        }
+        builder
+            .payee(&imported.payee)
+            .note(&imported.note)
+            .date_created(imported.date_created)
+            .done()
+    }
+
+    pub fn categorization_rule_builder(&mut self, name: &str) -> Result<CategorizationRuleBuilder, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        Ok(CategorizationRuleBuilder::new(Rc::clone(&self.conn), b.id(), name))
+    }
+
+    /// rules of the current budget account in the order they are tried
+    pub fn categorization_rules(&mut self) -> Result<Vec<CategorizationRule>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        CategorizationRuleModel::find_all(gc!(*self.conn), b.id())
+    }
+
+    pub fn delete_categorization_rule(&mut self, name: &str) -> Result<usize, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let r = CategorizationRuleModel::load_by_name(gc!(*self.conn), b.id(), name)?;
+        CategorizationRuleModel::delete(gc!(*self.conn), r.id())
+    }
+
+    /// what running the categorization rules on the incomes and expenses of the current
+    /// budget account from `from` to `to`, both days included, would change. Only
+    /// expenses of the default category are moved to another category.
+    pub fn preview_categorization_rules(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<RuleChange>, BudgetingErrors> {
+        let b = self.current_budget()
+            .ok_or(BudgetingErrors::BudgetAccountNotSelected)?;
+        let rules = RuleSet::load(gc!(*self.conn), b.id())?;
+        let mut query = self.transaction_query()?;
+        query
+            .from(start_of_day(from))
+            .only_transaction_types(&[TransactionType::Income, TransactionType::Expense])
+            .sort_by(SortKey::DateCreated, SortOrder::Ascending)
+            .unpaginated();
+        if let Some(after) = to.succ_opt() {
+            query.before(start_of_day(after));
+        }
+        let found = query.load(gc!(*self.conn))?;
+        let names: HashMap<i32, String> = self
+            .all_categories()
+            .into_iter()
+            .map(|c| (c.id(), c.name()))
+            .collect();
+        let name = |id: i32| names.get(&id).cloned().unwrap_or_default();
+        let mut changes = vec![];
+        for t in found {
+            let outcome = rules.apply(&t.payee(), &t.note());
+            if outcome.rules.is_empty() {
+                continue;
+            }
+            let payee = outcome.payee.filter(|p| *p != t.payee()).map(|p| (t.payee(), p));
+            let category = outcome
+                .category_id
+                .filter(|c| {
+                    TransactionType::from(t.transfer_type_id()) == TransactionType::Expense
+                        && name(t.category_id()) == DEFAULT_CATEGORY
+                        && *c != t.category_id()
+                })
+                .map(|c| (name(t.category_id()), name(c)));
+            let current: Vec<String> = TagModel::tags_of(gc!(*self.conn), t.id())?
+                .iter()
+                .map(|tag| tag.name().to_lowercase())
+                .collect();
+            let tags: Vec<String> = outcome
+                .tags
+                .into_iter()
+                .filter(|tag| !current.contains(&tag.to_lowercase()))
+                .collect();
+            if payee.is_none() && category.is_none() && tags.is_empty() {
+                continue;
+            }
+            changes.push(RuleChange {
+                transaction_id: t.id(),
+                rules: outcome.rules,
+                payee,
+                category,
+                tags,
+            });
+        }
+        Ok(changes)
+    }
+
+    /// makes the changes `preview_categorization_rules` lists, all or none of them
+    pub fn apply_categorization_rules(
+        &mut self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<RuleChange>, BudgetingErrors> {
+        self.in_transaction(|b| {
+            let changes = b.preview_categorization_rules(from, to)?;
+            for change in &changes {
+                let category_id = match &change.category {
+                    Some((_, new)) => Some(b.find_category(new)?.id()),
+                    None => None,
+                };
+                let form = TransactionForm {
+                    note: None,
+                    payee: change.payee.as_ref().map(|(_, new)| new.clone()),
+                    date_created: None,
+                    amount: None,
+                    category_id,
+                };
+                if form.payee.is_some() || form.category_id.is_some() {
+                    TransactionModel::update(gc!(*b.conn), change.transaction_id, form)?;
+                }
+                for tag in &change.tags {
+                    TagModel::tag(gc!(*b.conn), change.transaction_id, tag)?;
+                }
+            }
+            Ok(changes)
+        })
+    }
+
+    pub fn tags(&mut self) -> Result<Vec<Tag>, BudgetingErrors> {
+        TagModel::find_all(gc!(*self.conn))
+    }
+
+    /// adds the tag to the transaction, the tag is created if it does not exist
+    pub fn tag_transaction(&mut self, transaction_id: i32, tag: &str) -> Result<Tag, BudgetingErrors> {
+        TagModel::tag(gc!(*self.conn), transaction_id, tag)
+    }
+
+    pub fn untag_transaction(&mut self, transaction_id: i32, tag: &str) -> Result<usize, BudgetingErrors> {
+        TagModel::untag(gc!(*self.conn), transaction_id, tag)
+    }
+
+    pub fn transaction_tags(&mut self, transaction_id: i32) -> Result<Vec<Tag>, BudgetingErrors> {
+        TagModel::tags_of(gc!(*self.conn), transaction_id)
+    }
 }
 
 
@@ -0,0 +1,35 @@
+use crate::budgeting::category::Category;
+use serde::{Deserialize, Serialize};
+
+/// A category which has spent more than what was funded to it
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct OverspentCategory {
+    pub category: Category,
+    pub balance: f64,
+}
+
+impl OverspentCategory {
+    pub fn shortfall(&self) -> f64 {
+        -self.balance
+    }
+}
+
+/// Result of covering an overspent category, `covered_from` lists the source
+/// categories in the order they were used, with the amount taken from each
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct OverspendingCover {
+    pub category: String,
+    pub shortfall: f64,
+    pub covered_from: Vec<(String, f64)>,
+}
+
+impl OverspendingCover {
+    pub fn covered(&self) -> f64 {
+        self.covered_from.iter().map(|(_, amount)| amount).sum()
+    }
+
+    /// shortfall left after all the source categories were used
+    pub fn remaining(&self) -> f64 {
+        self.shortfall - self.covered()
+    }
+}
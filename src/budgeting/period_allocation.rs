@@ -0,0 +1,104 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::reports::month_start;
+use crate::budgeting::validation;
+use crate::schema::period_allocations;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Allocation of a category for one month, overrides `Category::allocated` for that month
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable)]
+#[diesel(table_name = period_allocations)]
+pub struct PeriodAllocation {
+    id: i32,
+    category_id: i32,
+    month: NaiveDate,
+    amount: f64,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = period_allocations)]
+pub struct NewPeriodAllocation {
+    category_id: i32,
+    month: NaiveDate,
+    amount: f64,
+}
+
+impl PeriodAllocation {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn category_id(&self) -> i32 {
+        self.category_id
+    }
+
+    /// first day of the month
+    pub fn month(&self) -> NaiveDate {
+        self.month
+    }
+
+    pub fn amount(&self) -> f64 {
+        self.amount
+    }
+}
+
+pub struct PeriodAllocationModel;
+
+impl PeriodAllocationModel {
+    /// sets the allocation of the category for the month `month` is in,
+    /// replacing the existing one
+    pub(crate) fn set(
+        conn: &mut SqliteConnection,
+        _category_id: i32,
+        _month: NaiveDate,
+        _amount: f64,
+    ) -> Result<PeriodAllocation, BudgetingErrors> {
+        validation::non_negative("amount", _amount)?;
+        let _month = month_start(_month);
+        conn.transaction(|conn| {
+            {
+                imp_db!(period_allocations);
+                diesel::delete(
+                    period_allocations
+                        .filter(category_id.eq(_category_id))
+                        .filter(month.eq(_month)),
+                )
+                .execute(conn)?;
+            }
+            let new_allocation = NewPeriodAllocation {
+                category_id: _category_id,
+                month: _month,
+                amount: _amount,
+            };
+            Ok(save_model!(conn, period_allocations, new_allocation, PeriodAllocation)?)
+        })
+    }
+
+    /// allocations of all the categories for months from `from` to `to`, both included
+    pub(crate) fn find_between(
+        conn: &mut SqliteConnection,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<PeriodAllocation>, BudgetingErrors> {
+        imp_db!(period_allocations);
+        period_allocations
+            .filter(month.ge(month_start(from)))
+            .filter(month.le(to))
+            .order((month.asc(), category_id.asc()))
+            .load::<PeriodAllocation>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn find_all(
+        conn: &mut SqliteConnection,
+        _category_id: i32,
+    ) -> Result<Vec<PeriodAllocation>, BudgetingErrors> {
+        imp_db!(period_allocations);
+        period_allocations
+            .filter(category_id.eq(_category_id))
+            .order(month.asc())
+            .load::<PeriodAllocation>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+}
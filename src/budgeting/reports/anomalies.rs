@@ -0,0 +1,217 @@
+use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+use crate::budgeting::category::Category;
+use crate::budgeting::reports::{month_start, months_between, start_of_day};
+use crate::budgeting::transaction::TransactionType;
+use crate::budgeting::transaction_query::{SortKey, SortOrder, TransactionQuery};
+use chrono::{Days, Months, NaiveDate, NaiveDateTime};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Thresholds used to decide what is unusual
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct AnomalySettings {
+    /// an expense is flagged when it is more than this many standard deviations
+    /// above the mean of the earlier expenses of its category
+    pub std_devs: f64,
+    /// earlier expenses a category needs before its expenses are checked
+    pub min_history: usize,
+    /// number of months the spending of a month is compared to
+    pub trailing_months: usize,
+    /// a month is flagged when its spending is this many percent above or below
+    /// the trailing average
+    pub month_deviation: f64,
+}
+
+impl Default for AnomalySettings {
+    fn default() -> Self {
+        AnomalySettings {
+            std_devs: 3.,
+            min_history: 5,
+            trailing_months: 3,
+            month_deviation: 50.,
+        }
+    }
+}
+
+/// Something unusual, with the statistics it was found with. Amounts are positive.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+#[serde(tag = "kind")]
+pub enum Anomaly {
+    Transaction {
+        transaction_id: i32,
+        category: String,
+        date_created: NaiveDateTime,
+        payee: String,
+        amount: f64,
+        /// mean and standard deviation of the earlier expenses of the category
+        mean: f64,
+        std_dev: f64,
+        history: usize,
+        z_score: f64,
+    },
+    Month {
+        category: String,
+        month: NaiveDate,
+        spent: f64,
+        /// mean and standard deviation of the spending of the trailing months
+        trailing_average: f64,
+        std_dev: f64,
+        /// (spent - trailing_average) / trailing_average, in percent
+        deviation: f64,
+    },
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct AnomalyReport {
+    pub budget_account_id: i32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub settings: AnomalySettings,
+    /// unusual transactions by date, then unusual months by month
+    pub findings: Vec<Anomaly>,
+}
+
+/// running mean and variance, Welford's algorithm
+#[derive(Default)]
+struct Stats {
+    n: usize,
+    mean: f64,
+    m2: f64,
+}
+
+impl Stats {
+    fn push(&mut self, x: f64) {
+        self.n += 1;
+        let delta = x - self.mean;
+        self.mean += delta / self.n as f64;
+        self.m2 += delta * (x - self.mean);
+    }
+
+    /// population standard deviation
+    fn std_dev(&self) -> f64 {
+        if self.n == 0 {
+            0.
+        } else {
+            (self.m2 / self.n as f64).sqrt()
+        }
+    }
+}
+
+impl AnomalyReport {
+    /// Checks the expenses and the months from `from` to `to`, both days included,
+    /// against the history of their category, which can go back before `from`.
+    /// A month is only checked when the category had spending in its first trailing
+    /// month or earlier, so new categories are not flagged.
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        budget_account_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+        settings: AnomalySettings,
+    ) -> Result<AnomalyReport, BudgetingErrors> {
+        let out_of_range = || BudgetingErrors::InvalidInput {
+            field: "trailing_months".to_string(),
+            reason: InvalidReason::OutOfRange,
+        };
+        let trailing = u32::try_from(settings.trailing_months).map_err(|_| out_of_range())?;
+        let names: HashMap<i32, String> = {
+            imp_db!(categories);
+            categories
+                .load::<Category>(conn)?
+                .into_iter()
+                .map(|c| (c.id(), c.name()))
+                .collect()
+        };
+        let start = start_of_day(from);
+        let mut query = TransactionQuery::new();
+        query
+            .budget_account_id(budget_account_id)
+            .transaction_type(TransactionType::Expense)
+            .before(start_of_day(to + Days::new(1)))
+            .sort_by(SortKey::DateCreated, SortOrder::Ascending);
+
+        let mut findings = vec![];
+        let mut stats: HashMap<i32, Stats> = HashMap::new();
+        let mut monthly: HashMap<(i32, NaiveDate), f64> = HashMap::new();
+        let mut first_month: HashMap<i32, NaiveDate> = HashMap::new();
+        for t in query.load(conn)? {
+            let x = -t.amount();
+            let month = month_start(t.date_created().date());
+            *monthly.entry((t.category_id(), month)).or_default() += x;
+            first_month.entry(t.category_id()).or_insert(month);
+            let s = stats.entry(t.category_id()).or_default();
+            let std_dev = s.std_dev();
+            if t.date_created() >= start && s.n >= settings.min_history && std_dev > 0. {
+                let z_score = (x - s.mean) / std_dev;
+                if z_score > settings.std_devs {
+                    findings.push(Anomaly::Transaction {
+                        transaction_id: t.id(),
+                        category: names.get(&t.category_id()).cloned().unwrap_or_default(),
+                        date_created: t.date_created(),
+                        payee: t.payee(),
+                        amount: x,
+                        mean: s.mean,
+                        std_dev,
+                        history: s.n,
+                        z_score,
+                    });
+                }
+            }
+            s.push(x);
+        }
+
+        let mut category_ids: Vec<i32> = first_month.keys().copied().collect();
+        category_ids.sort();
+        for month in months_between(from, to) {
+            let window_start = month
+                .checked_sub_months(Months::new(trailing))
+                .ok_or_else(out_of_range)?;
+            for cid in &category_ids {
+                if trailing == 0 || first_month[cid] > window_start {
+                    continue;
+                }
+                let mut s = Stats::default();
+                for m in months_between(window_start, month - Days::new(1)) {
+                    s.push(monthly.get(&(*cid, m)).copied().unwrap_or(0.));
+                }
+                if s.mean <= 0. {
+                    continue;
+                }
+                let spent = monthly.get(&(*cid, month)).copied().unwrap_or(0.);
+                let deviation = (spent - s.mean) / s.mean * 100.;
+                if deviation.abs() >= settings.month_deviation {
+                    findings.push(Anomaly::Month {
+                        category: names.get(cid).cloned().unwrap_or_default(),
+                        month,
+                        spent,
+                        trailing_average: s.mean,
+                        std_dev: s.std_dev(),
+                        deviation,
+                    });
+                }
+            }
+        }
+        Ok(AnomalyReport {
+            budget_account_id,
+            from,
+            to,
+            settings,
+            findings,
+        })
+    }
+
+    pub fn unusual_transactions(&self) -> Vec<&Anomaly> {
+        self.findings
+            .iter()
+            .filter(|a| matches!(a, Anomaly::Transaction { .. }))
+            .collect()
+    }
+
+    pub fn unusual_months(&self) -> Vec<&Anomaly> {
+        self.findings
+            .iter()
+            .filter(|a| matches!(a, Anomaly::Month { .. }))
+            .collect()
+    }
+}
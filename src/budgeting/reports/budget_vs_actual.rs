@@ -0,0 +1,125 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::category::Category;
+use crate::budgeting::period_allocation::PeriodAllocationModel;
+use crate::budgeting::reports::category_monthly::MonthlyCategoryReport;
+use crate::DEFAULT_CATEGORY;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Plan and outcome of a category in a month. `spent` is positive,
+/// positive variance means less was spent than allocated
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct BudgetVsActualLine {
+    pub category: String,
+    pub allocated: f64,
+    /// net transfers into the category
+    pub funded: f64,
+    pub spent: f64,
+    /// funded - spent
+    pub remaining: f64,
+    /// allocated - spent
+    pub variance: f64,
+    /// variance as percentage of allocated, none when nothing was allocated
+    pub variance_percentage: Option<f64>,
+}
+
+impl BudgetVsActualLine {
+    fn new(category: String, allocated: f64, funded: f64, spent: f64) -> Self {
+        let variance = allocated - spent;
+        BudgetVsActualLine {
+            category,
+            allocated,
+            funded,
+            spent,
+            remaining: funded - spent,
+            variance,
+            variance_percentage: if allocated != 0. {
+                Some(variance / allocated * 100.)
+            } else {
+                None
+            },
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BudgetVsActualPeriod {
+    pub month: NaiveDate,
+    pub lines: Vec<BudgetVsActualLine>,
+    pub totals: BudgetVsActualLine,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct BudgetVsActualReport {
+    pub budget_account_id: i32,
+    pub periods: Vec<BudgetVsActualPeriod>,
+}
+
+impl BudgetVsActualReport {
+    /// One period per month from `from` to `to`. The default category is not
+    /// budgeted, so it is left out.
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        budget_account_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<BudgetVsActualReport, BudgetingErrors> {
+        let monthly = MonthlyCategoryReport::build(conn, budget_account_id, from, to)?;
+        let allocations: HashMap<(i32, NaiveDate), f64> =
+            PeriodAllocationModel::find_between(conn, from, to)?
+                .iter()
+                .map(|a| ((a.category_id(), a.month()), a.amount()))
+                .collect();
+        let all_categories: HashMap<i32, Category> = {
+            imp_db!(categories);
+            categories
+                .load::<Category>(conn)?
+                .into_iter()
+                .map(|c| (c.id(), c))
+                .collect()
+        };
+        let periods = monthly
+            .months
+            .iter()
+            .enumerate()
+            .map(|(i, month)| {
+                let lines: Vec<BudgetVsActualLine> = monthly
+                    .rows
+                    .iter()
+                    .filter(|r| r.category != DEFAULT_CATEGORY)
+                    .map(|r| {
+                        let cell = &r.months[i];
+                        let allocated = allocations
+                            .get(&(r.category_id, *month))
+                            .copied()
+                            .or_else(|| all_categories.get(&r.category_id).map(|c| c.allocated()))
+                            .unwrap_or(0.);
+                        BudgetVsActualLine::new(
+                            r.category.clone(),
+                            allocated,
+                            cell.transfer_in + cell.transfer_out,
+                            -cell.expense,
+                        )
+                    })
+                    .collect();
+                let totals = BudgetVsActualLine::new(
+                    "Total".to_string(),
+                    lines.iter().map(|l| l.allocated).sum(),
+                    lines.iter().map(|l| l.funded).sum(),
+                    lines.iter().map(|l| l.spent).sum(),
+                );
+                BudgetVsActualPeriod {
+                    month: *month,
+                    lines,
+                    totals,
+                }
+            })
+            .collect();
+        Ok(BudgetVsActualReport {
+            budget_account_id,
+            periods,
+        })
+    }
+}
@@ -0,0 +1,112 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::category::Category;
+use crate::budgeting::reports::{month_start, months_between, start_of_day};
+use crate::budgeting::transaction::TransactionType;
+use crate::budgeting::transaction_query::TransactionQuery;
+use chrono::NaiveDate;
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Activity of one category in one month, amounts are signed the same way
+/// `CategoryModel::income/expense/transfer_in/transfer_out` are
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct CategoryMonth {
+    pub month: NaiveDate,
+    pub income: f64,
+    pub expense: f64,
+    pub transfer_in: f64,
+    pub transfer_out: f64,
+    /// balance of the category in this budget account at the end of the month
+    pub ending_balance: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CategoryMonthlyRow {
+    pub category_id: i32,
+    pub category: String,
+    pub months: Vec<CategoryMonth>,
+}
+
+/// Category x month matrix, every row has a cell for each of `months`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct MonthlyCategoryReport {
+    pub budget_account_id: i32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub months: Vec<NaiveDate>,
+    pub rows: Vec<CategoryMonthlyRow>,
+}
+
+impl MonthlyCategoryReport {
+    /// Transactions from `from` to `to`, both days included, are counted in the month
+    /// they were created. The ending balance includes everything before `from` too.
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        budget_account_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<MonthlyCategoryReport, BudgetingErrors> {
+        let months = months_between(from, to);
+        let all_categories = {
+            imp_db!(categories);
+            categories.order(id.asc()).load::<Category>(conn)?
+        };
+        let start = start_of_day(from);
+        let mut query = TransactionQuery::new();
+        query.budget_account_id(budget_account_id);
+        if let Some(after) = to.succ_opt() {
+            query.before(start_of_day(after));
+        }
+        let mut opening: HashMap<i32, f64> = HashMap::new();
+        let mut cells: HashMap<(i32, NaiveDate), CategoryMonth> = HashMap::new();
+        for t in query.load(conn)? {
+            if t.date_created() < start {
+                *opening.entry(t.category_id()).or_default() += t.amount();
+                continue;
+            }
+            let month = month_start(t.date_created().date());
+            let cell = cells.entry((t.category_id(), month)).or_default();
+            match TransactionType::from(t.transfer_type_id()) {
+                TransactionType::Income => cell.income += t.amount(),
+                TransactionType::Expense => cell.expense += t.amount(),
+                TransactionType::TransferIn => cell.transfer_in += t.amount(),
+                TransactionType::TransferOut => cell.transfer_out += t.amount(),
+            }
+        }
+        let rows = all_categories
+            .iter()
+            .map(|c| {
+                let mut balance = opening.get(&c.id()).copied().unwrap_or(0.);
+                let months = months
+                    .iter()
+                    .map(|m| {
+                        let mut cell = cells.remove(&(c.id(), *m)).unwrap_or_default();
+                        cell.month = *m;
+                        balance += cell.income + cell.expense + cell.transfer_in + cell.transfer_out;
+                        cell.ending_balance = balance;
+                        cell
+                    })
+                    .collect();
+                CategoryMonthlyRow {
+                    category_id: c.id(),
+                    category: c.name(),
+                    months,
+                }
+            })
+            .collect();
+        Ok(MonthlyCategoryReport {
+            budget_account_id,
+            from,
+            to,
+            months,
+            rows,
+        })
+    }
+
+    pub fn row(&self, category: &str) -> Option<&CategoryMonthlyRow> {
+        self.rows
+            .iter()
+            .find(|r| r.category.to_lowercase() == category.to_lowercase())
+    }
+}
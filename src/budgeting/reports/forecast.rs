@@ -0,0 +1,162 @@
+use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+use crate::budgeting::category::Category;
+use crate::budgeting::reports::start_of_day;
+use crate::budgeting::scheduled_transaction::ScheduledTransactionModel;
+use crate::budgeting::transaction::TransactionType;
+use crate::budgeting::transaction_query::TransactionQuery;
+use chrono::{Days, NaiveDate};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// largest number of days forecast or looked back, ten years
+pub const MAX_FORECAST_DAYS: u32 = 3660;
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct ForecastDay {
+    pub date: NaiveDate,
+    /// scheduled incomes due this day
+    pub income: f64,
+    /// scheduled expenses due this day plus the average daily spending, negative
+    pub expense: f64,
+    /// projected balance at the end of the day
+    pub balance: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CategorySpendingRate {
+    pub category: String,
+    /// average expense per day during the look back period, negative
+    pub daily_average: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct CashFlowForecast {
+    pub budget_account_id: i32,
+    /// income minus expenses of the budget account until today
+    pub starting_balance: f64,
+    pub days: Vec<ForecastDay>,
+    pub lowest_balance: f64,
+    pub lowest_balance_date: NaiveDate,
+    pub spending_rates: Vec<CategorySpendingRate>,
+}
+
+impl CashFlowForecast {
+    /// Projects the balance for the `days` days after `today`. Scheduled transactions are
+    /// placed on the days they are due. Categories without any scheduled expense are
+    /// expected to keep spending their average of the last `lookback_days` days, the
+    /// ones with scheduled expenses are projected from their schedule only, so bills are
+    /// not counted twice. Both numbers of days go up to `MAX_FORECAST_DAYS`.
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        budget_account_id: i32,
+        today: NaiveDate,
+        days: u32,
+        lookback_days: u32,
+    ) -> Result<CashFlowForecast, BudgetingErrors> {
+        let out_of_range = |field: &str| BudgetingErrors::InvalidInput {
+            field: field.to_string(),
+            reason: InvalidReason::OutOfRange,
+        };
+        let first = today.succ_opt().ok_or_else(|| out_of_range("days"))?;
+        let last = Some(today)
+            .filter(|_| days <= MAX_FORECAST_DAYS)
+            .and_then(|d| d.checked_add_days(Days::new(days as u64)))
+            .ok_or_else(|| out_of_range("days"))?;
+        let lookback_start = Some(today)
+            .filter(|_| lookback_days <= MAX_FORECAST_DAYS)
+            .and_then(|d| d.checked_sub_days(Days::new(lookback_days as u64)))
+            .ok_or_else(|| out_of_range("lookback_days"))?;
+        let tomorrow = start_of_day(first);
+        let mut query = TransactionQuery::new();
+        query
+            .budget_account_id(budget_account_id)
+            .transaction_type(TransactionType::Income)
+            .transaction_type(TransactionType::Expense)
+            .before(tomorrow);
+        let starting_balance = query.sum(conn)?;
+
+        let scheduled = ScheduledTransactionModel::find_all(conn, budget_account_id)?;
+        let scheduled_categories: HashSet<i32> = scheduled
+            .iter()
+            .filter(|s| s.transaction_type() == TransactionType::Expense)
+            .map(|s| s.category_id())
+            .collect();
+
+        let mut spent: HashMap<i32, f64> = HashMap::new();
+        let mut history = TransactionQuery::new();
+        history
+            .budget_account_id(budget_account_id)
+            .transaction_type(TransactionType::Expense)
+            .from(start_of_day(lookback_start))
+            .before(tomorrow);
+        for t in history.load(conn)? {
+            if !scheduled_categories.contains(&t.category_id()) {
+                *spent.entry(t.category_id()).or_default() += t.amount();
+            }
+        }
+        let names: HashMap<i32, String> = {
+            imp_db!(categories);
+            categories
+                .load::<Category>(conn)?
+                .into_iter()
+                .map(|c| (c.id(), c.name()))
+                .collect()
+        };
+        let mut spending_rates: Vec<CategorySpendingRate> = spent
+            .iter()
+            .map(|(cid, total)| CategorySpendingRate {
+                category: names.get(cid).cloned().unwrap_or_default(),
+                daily_average: total / lookback_days.max(1) as f64,
+            })
+            .collect();
+        spending_rates.sort_by(|a, b| a.category.cmp(&b.category));
+        let daily_spending: f64 = spending_rates.iter().map(|r| r.daily_average).sum();
+
+        let mut due: HashMap<NaiveDate, (f64, f64)> = HashMap::new();
+        for s in &scheduled {
+            for d in s.occurrences(first, last) {
+                let entry = due.entry(d).or_default();
+                if s.amount() >= 0. {
+                    entry.0 += s.amount();
+                } else {
+                    entry.1 += s.amount();
+                }
+            }
+        }
+
+        let mut balance = starting_balance;
+        let mut lowest_balance = starting_balance;
+        let mut lowest_balance_date = today;
+        let mut forecast_days = vec![];
+        for n in 1..=days as u64 {
+            let date = today + Days::new(n);
+            let (income, scheduled_expense) = due.get(&date).copied().unwrap_or_default();
+            let expense = scheduled_expense + daily_spending;
+            balance += income + expense;
+            if balance < lowest_balance {
+                lowest_balance = balance;
+                lowest_balance_date = date;
+            }
+            forecast_days.push(ForecastDay {
+                date,
+                income,
+                expense,
+                balance,
+            });
+        }
+        Ok(CashFlowForecast {
+            budget_account_id,
+            starting_balance,
+            days: forecast_days,
+            lowest_balance,
+            lowest_balance_date,
+            spending_rates,
+        })
+    }
+
+    /// true if the balance is projected to go below zero
+    pub fn overdraft_expected(&self) -> bool {
+        self.lowest_balance < 0.
+    }
+}
@@ -0,0 +1,131 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::reports::{month_start, next_month, start_of_day};
+use crate::budgeting::transaction::TransactionType;
+use crate::budgeting::transaction_query::TransactionQuery;
+use chrono::{Datelike, Days, Months, NaiveDate};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// Size of the buckets of a time series, weeks start on monday
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Granularity {
+    Day,
+    Week,
+    #[default]
+    Month,
+    Year,
+}
+
+impl Granularity {
+    /// first day of the bucket `date` is in
+    pub fn bucket_start(&self, date: NaiveDate) -> NaiveDate {
+        match self {
+            Granularity::Day => date,
+            Granularity::Week => date - Days::new(date.weekday().num_days_from_monday() as u64),
+            Granularity::Month => month_start(date),
+            Granularity::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+        }
+    }
+
+    /// first day of the bucket after the one `date` is in
+    pub fn next_bucket(&self, date: NaiveDate) -> NaiveDate {
+        let start = self.bucket_start(date);
+        match self {
+            Granularity::Day => start + Days::new(1),
+            Granularity::Week => start + Days::new(7),
+            Granularity::Month => next_month(start),
+            Granularity::Year => start + Months::new(12),
+        }
+    }
+}
+
+/// savings rate is `None` when there was no income
+fn savings_rate(income: f64, expense: f64) -> Option<f64> {
+    if income > 0. {
+        Some((income + expense) / income * 100.)
+    } else {
+        None
+    }
+}
+
+/// Income and expense of one bucket, expense is negative
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, Default)]
+pub struct IncomeExpensePoint {
+    /// first day of the bucket
+    pub start: NaiveDate,
+    pub income: f64,
+    pub expense: f64,
+    /// income + expense
+    pub net: f64,
+    /// share of the income not spent, in percent
+    pub savings_rate: Option<f64>,
+}
+
+/// Incomes and expenses bucketed by day, week, month or year. Transfers between
+/// categories are not counted. Every bucket from `from` to `to` is present, even empty.
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct IncomeExpenseSeries {
+    pub budget_account_id: i32,
+    pub granularity: Granularity,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub points: Vec<IncomeExpensePoint>,
+    pub total_income: f64,
+    pub total_expense: f64,
+    pub savings_rate: Option<f64>,
+}
+
+impl IncomeExpenseSeries {
+    /// Transactions from `from` to `to`, both days included. `filter` narrows the
+    /// transactions, e.g. to categories or tags, its budget account, dates, types,
+    /// sorting and pagination are replaced.
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        budget_account_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+        granularity: Granularity,
+        filter: &TransactionQuery,
+    ) -> Result<IncomeExpenseSeries, BudgetingErrors> {
+        let mut query = filter.clone();
+        query
+            .budget_account_id(budget_account_id)
+            .from(start_of_day(from))
+            .before(start_of_day(to + Days::new(1)))
+            .only_transaction_types(&[TransactionType::Income, TransactionType::Expense])
+            .unpaginated();
+        let mut buckets: HashMap<NaiveDate, IncomeExpensePoint> = HashMap::new();
+        for t in query.load(conn)? {
+            let point = buckets
+                .entry(granularity.bucket_start(t.date_created().date()))
+                .or_default();
+            match TransactionType::from(t.transfer_type_id()) {
+                TransactionType::Income => point.income += t.amount(),
+                _ => point.expense += t.amount(),
+            }
+        }
+        let mut points = vec![];
+        let mut start = granularity.bucket_start(from);
+        while start <= to {
+            let mut point = buckets.remove(&start).unwrap_or_default();
+            point.start = start;
+            point.net = point.income + point.expense;
+            point.savings_rate = savings_rate(point.income, point.expense);
+            points.push(point);
+            start = granularity.next_bucket(start);
+        }
+        let total_income = points.iter().map(|p| p.income).sum();
+        let total_expense = points.iter().map(|p| p.expense).sum();
+        Ok(IncomeExpenseSeries {
+            budget_account_id,
+            granularity,
+            from,
+            to,
+            points,
+            total_income,
+            total_expense,
+            savings_rate: savings_rate(total_income, total_expense),
+        })
+    }
+}
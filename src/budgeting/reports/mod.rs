@@ -0,0 +1,46 @@
+//! Read only summaries of the transactions. Every report is calculated for
+//! one budget account and derives `Serialize`, so it can be handed to a UI as JSON.
+
+use chrono::{Datelike, Months, NaiveDate, NaiveDateTime};
+
+pub mod anomalies;
+pub mod budget_vs_actual;
+pub mod category_monthly;
+pub mod forecast;
+pub mod income_expense;
+pub mod payees;
+
+/// first day of the month `date` is in
+pub(crate) fn month_start(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap()
+}
+
+/// first day of the month after the one `date` is in
+pub(crate) fn next_month(date: NaiveDate) -> NaiveDate {
+    if date.month() == 12 {
+        NaiveDate::from_ymd_opt(date.year() + 1, 1, 1).unwrap()
+    } else {
+        NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1).unwrap()
+    }
+}
+
+/// first day of every month from the month of `from` to the month of `to`, both included
+pub(crate) fn months_between(from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+    let mut months = vec![];
+    let mut m = month_start(from);
+    while m <= to {
+        months.push(m);
+        match m.checked_add_months(Months::new(1)) {
+            Some(next) => m = next,
+            None => break,
+        }
+    }
+    months
+}
+
+pub(crate) fn start_of_day(date: NaiveDate) -> NaiveDateTime {
+    date.and_hms_opt(0, 0, 0).unwrap()
+}
+
+#[cfg(test)]
+mod tests;
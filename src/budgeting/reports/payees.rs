@@ -0,0 +1,110 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::reports::start_of_day;
+use crate::budgeting::transaction::TransactionType;
+use chrono::{NaiveDate, NaiveDateTime};
+use diesel::dsl::{count, max, min, sum};
+use diesel::prelude::*;
+use diesel::sql_types::Bool;
+use serde::{Deserialize, Serialize};
+
+/// Expenses paid to one payee, amounts are positive
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct PayeeSpending {
+    pub payee: String,
+    pub spent: f64,
+    pub transactions: i64,
+    /// average amount of a transaction
+    pub average: f64,
+    pub first_seen: NaiveDateTime,
+    pub last_seen: NaiveDateTime,
+    /// share of all the expenses of the period, in percent
+    pub share: f64,
+}
+
+/// Payees the most money was spent on, from `from` to `to`, both days included
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone)]
+pub struct TopPayeesReport {
+    pub budget_account_id: i32,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    /// all the expenses of the period, not only the ones of the top payees
+    pub total_spent: f64,
+    pub payees: Vec<PayeeSpending>,
+}
+
+impl TopPayeesReport {
+    /// Only expenses are counted, payees are grouped as written. Ties are ordered by payee.
+    pub(crate) fn build(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        from: NaiveDate,
+        to: NaiveDate,
+        top: i64,
+    ) -> Result<TopPayeesReport, BudgetingErrors> {
+        use crate::schema::transactions::dsl::*;
+        let start = start_of_day(from);
+        // no upper bound when `to` is the last date chrono knows
+        let until = to.succ_opt().map(start_of_day);
+        let expenses = transactions
+            .filter(budget_account_id.eq(_budget_account_id))
+            .filter(transaction_type_id.eq(i32::from(TransactionType::Expense)))
+            .filter(date_created.ge(start))
+            .filter(
+                date_created
+                    .lt(until.unwrap_or(start))
+                    .or(until.is_none().into_sql::<Bool>()),
+            );
+        let total: Option<f64> = expenses.select(sum(amount)).first(conn)?;
+        let total_spent = -total.unwrap_or(0.);
+        let rows = expenses
+            .group_by(payee)
+            .select((
+                payee,
+                sum(amount),
+                count(id),
+                min(date_created),
+                max(date_created),
+            ))
+            .order((sum(amount).asc(), payee.asc()))
+            .limit(top)
+            .load::<(
+                String,
+                Option<f64>,
+                i64,
+                Option<NaiveDateTime>,
+                Option<NaiveDateTime>,
+            )>(conn)?;
+        let payees = rows
+            .into_iter()
+            .map(|(p, s, c, first, last)| {
+                let spent = -s.unwrap_or(0.);
+                PayeeSpending {
+                    payee: p,
+                    spent,
+                    transactions: c,
+                    average: spent / c as f64,
+                    first_seen: first.unwrap_or(start),
+                    last_seen: last.unwrap_or(start),
+                    share: if total_spent > 0. {
+                        spent / total_spent * 100.
+                    } else {
+                        0.
+                    },
+                }
+            })
+            .collect();
+        Ok(TopPayeesReport {
+            budget_account_id: _budget_account_id,
+            from,
+            to,
+            total_spent,
+            payees,
+        })
+    }
+
+    pub fn payee(&self, payee: &str) -> Option<&PayeeSpending> {
+        self.payees
+            .iter()
+            .find(|p| p.payee.to_lowercase() == payee.to_lowercase())
+    }
+}
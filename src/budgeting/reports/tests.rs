@@ -0,0 +1,501 @@
+use crate::budgeting::Budgeting;
+use crate::test_helpers::{memory_db, new_budget_using_budgeting, BILLS, TRAVEL};
+use crate::{parse_date, DEFAULT_CATEGORY};
+use chrono::NaiveDate;
+
+fn date(d: &str) -> NaiveDate {
+    parse_date(d).date()
+}
+
+/// main account: Bills and Travel funded now, then some dated activity
+fn budgeting_with_history() -> Budgeting {
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    let entries = [
+        ("Bills", 100., "Water Co", "2023-01-10"),
+        ("Bills", 150., "Power Co", "2023-01-20"),
+        ("Travel", 400., "Airline", "2023-02-03"),
+        ("Bills", 120., "Water Co", "2023-03-11"),
+    ];
+    for (category, amount, payee, d) in entries {
+        budgeting
+            .new_transaction_to_category(category).unwrap()
+            .expense(amount)
+            .payee(payee)
+            .note("")
+            .date_created(parse_date(d))
+            .done()
+            .unwrap();
+    }
+    budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(2000.)
+        .payee("Employer")
+        .note("Salary")
+        .date_created(parse_date("2023-02-01"))
+        .done()
+        .unwrap();
+    budgeting
+}
+
+#[test]
+fn monthly_category_report() {
+    let mut budgeting = budgeting_with_history();
+    let report = budgeting
+        .monthly_category_report(date("2023-02-01"), date("2023-03-31"))
+        .unwrap();
+    assert_eq!(report.months, vec![date("2023-02-01"), date("2023-03-01")]);
+    assert_eq!(report.rows.len(), 3);
+
+    let bills = report.row("bills").unwrap();
+    assert_eq!(bills.months[0].expense, 0.);
+    assert_eq!(bills.months[1].expense, -120.);
+    // january expenses are before the report but part of the balance,
+    // funding of the categories happened today, after the report
+    assert_eq!(bills.months[0].ending_balance, -250.);
+    assert_eq!(bills.months[1].ending_balance, -370.);
+
+    let default = report.row(DEFAULT_CATEGORY).unwrap();
+    assert_eq!(default.months[0].income, 2000.);
+    assert_eq!(default.months[1].ending_balance, 2000.);
+
+    let report = budgeting
+        .monthly_category_report(date("2023-01-01"), parse_date("2100-01-01").date())
+        .unwrap();
+    let travel = report.row("Travel").unwrap();
+    assert_eq!(travel.months.last().unwrap().ending_balance, TRAVEL - 400.);
+    let bills = report.row("Bills").unwrap();
+    assert_eq!(bills.months.last().unwrap().ending_balance, BILLS - 370.);
+
+    // the last month chrono knows has no month after it
+    let last = budgeting.monthly_category_report(NaiveDate::MAX, NaiveDate::MAX).unwrap();
+    assert_eq!(last.months.len(), 1);
+    assert_eq!(last.row("Bills").unwrap().months[0].ending_balance, BILLS - 370.);
+
+    let json = serde_json::to_value(&report).unwrap();
+    assert_eq!(json["rows"][1]["category"], "Bills");
+    assert_eq!(json["rows"][1]["months"][0]["expense"], -250.);
+}
+
+#[test]
+fn budget_vs_actual_report() {
+    let mut budgeting = budgeting_with_history();
+    budgeting
+        .set_period_allocation("Bills", date("2023-01-15"), 200.)
+        .unwrap();
+    budgeting
+        .set_period_allocation("Bills", date("2023-01-01"), 300.)
+        .unwrap();
+    assert_eq!(budgeting.period_allocations("Bills").unwrap().len(), 1);
+    budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .transfer_from(500.)
+        .payee("Travel")
+        .note("Funded")
+        .date_created(parse_date("2023-01-02"))
+        .done()
+        .unwrap();
+    budgeting
+        .new_transaction_to_category("Travel").unwrap()
+        .transfer_to(500.)
+        .payee(DEFAULT_CATEGORY)
+        .note("Received")
+        .date_created(parse_date("2023-01-02"))
+        .done()
+        .unwrap();
+    let report = budgeting
+        .budget_vs_actual_report(date("2023-01-01"), date("2023-02-28"))
+        .unwrap();
+    assert_eq!(report.periods.len(), 2);
+
+    let january = &report.periods[0];
+    assert_eq!(january.lines.len(), 2);
+    let bills = &january.lines[0];
+    assert_eq!(bills.allocated, 300.);
+    assert_eq!(bills.spent, 250.);
+    assert_eq!(bills.variance, 50.);
+    assert_eq!(bills.variance_percentage, Some(50. / 300. * 100.));
+    let travel = &january.lines[1];
+    assert_eq!(travel.allocated, TRAVEL);
+    assert_eq!(travel.funded, 500.);
+    assert_eq!(travel.remaining, 500.);
+    assert_eq!(january.totals.allocated, 300. + TRAVEL);
+    assert_eq!(january.totals.spent, 250.);
+
+    // no period allocation in february, category allocation is used
+    let february = &report.periods[1];
+    assert_eq!(february.lines[0].allocated, BILLS);
+    assert_eq!(february.lines[1].spent, 400.);
+    assert_eq!(february.lines[1].variance, TRAVEL - 400.);
+}
+
+#[test]
+fn cash_flow_forecast() {
+    use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+    use crate::budgeting::reports::forecast::{CashFlowForecast, MAX_FORECAST_DAYS};
+    use crate::budgeting::scheduled_transaction::{Recurrence, MAX_EVERY};
+    use std::ops::DerefMut;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    budgeting.new_budget("main", 0.).unwrap();
+    budgeting.create_category("Rent", 0., false).unwrap();
+    budgeting.create_category("Food", 0., false).unwrap();
+    budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(1000.)
+        .payee("Employer")
+        .note("")
+        .date_created(parse_date("2023-05-01"))
+        .done()
+        .unwrap();
+    // 300 on food in the last 30 days, 10 a day
+    budgeting
+        .new_transaction_to_category("Food").unwrap()
+        .expense(300.)
+        .payee("Market")
+        .note("")
+        .date_created(parse_date("2023-05-20"))
+        .done()
+        .unwrap();
+    // rent has a schedule, so its history is not averaged
+    budgeting
+        .new_transaction_to_category("Rent").unwrap()
+        .expense(500.)
+        .payee("Landlord")
+        .note("")
+        .date_created(parse_date("2023-05-15"))
+        .done()
+        .unwrap();
+    budgeting
+        .new_scheduled_transaction_to_category("Rent").unwrap()
+        .expense(500.)
+        .payee("Landlord")
+        .note("")
+        .starting(date("2023-05-15"))
+        .repeat(Recurrence::Monthly, 1)
+        .done()
+        .unwrap();
+    budgeting
+        .new_scheduled_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(1000.)
+        .payee("Employer")
+        .note("")
+        .starting(date("2023-06-20"))
+        .done()
+        .unwrap();
+    assert!(budgeting
+        .new_scheduled_transaction_to_category("Rent").unwrap()
+        .expense(500.)
+        .payee("Landlord")
+        .note("")
+        .repeat(Recurrence::Yearly, MAX_EVERY + 1)
+        .done()
+        .is_err());
+    assert_eq!(budgeting.scheduled_transactions().unwrap().len(), 2);
+
+    let bid = budgeting.current_budget().unwrap().id();
+    let forecast = CashFlowForecast::build(
+        budgeting.conn.borrow_mut().deref_mut(),
+        bid,
+        date("2023-05-31"),
+        30,
+        30,
+    )
+    .unwrap();
+    assert_eq!(forecast.starting_balance, 200.);
+    assert_eq!(forecast.spending_rates.len(), 1);
+    assert_eq!(forecast.spending_rates[0].daily_average, -10.);
+    assert_eq!(forecast.days.len(), 30);
+    // lowest the day before the scheduled income, after 19 days of food and rent
+    assert_eq!(forecast.lowest_balance, 200. - 190. - 500.);
+    assert_eq!(forecast.lowest_balance_date, date("2023-06-19"));
+    assert!(forecast.overdraft_expected());
+    assert_eq!(forecast.days[19].income, 1000.);
+    assert_eq!(forecast.days.last().unwrap().balance, 200. - 300. - 500. + 1000.);
+
+    let out_of_range = |field: &str| BudgetingErrors::InvalidInput {
+        field: field.to_string(),
+        reason: InvalidReason::OutOfRange,
+    };
+    for (today, days, lookback_days, field) in [
+        (date("2023-05-31"), MAX_FORECAST_DAYS + 1, 30, "days"),
+        (date("2023-05-31"), u32::MAX, 30, "days"),
+        (date("2023-05-31"), 30, u32::MAX, "lookback_days"),
+        (NaiveDate::MAX, 30, 30, "days"),
+        (NaiveDate::MIN, 30, 30, "lookback_days"),
+    ] {
+        assert_eq!(
+            CashFlowForecast::build(budgeting.conn.borrow_mut().deref_mut(), bid, today, days, lookback_days),
+            Err(out_of_range(field))
+        );
+    }
+}
+
+#[test]
+fn income_expense_series() {
+    use crate::budgeting::reports::income_expense::Granularity;
+    use crate::budgeting::transaction_query::TransactionQuery;
+
+    let mut budgeting = budgeting_with_history();
+    let all = TransactionQuery::new();
+    let series = budgeting
+        .income_expense_series(date("2023-01-01"), date("2023-03-31"), Granularity::Month, &all)
+        .unwrap();
+    assert_eq!(series.points.len(), 3);
+    assert_eq!(series.points[0].start, date("2023-01-01"));
+    assert_eq!(series.points[0].expense, -250.);
+    assert_eq!(series.points[0].savings_rate, None);
+    assert_eq!(series.points[1].income, 2000.);
+    assert_eq!(series.points[1].net, 1600.);
+    assert_eq!(series.points[1].savings_rate, Some(80.));
+    assert_eq!(series.points[2].expense, -120.);
+    assert_eq!(series.total_income, 2000.);
+    assert_eq!(series.total_expense, -770.);
+
+    // 2023-02-01 is a wednesday, the week starts on monday the 30th
+    let series = budgeting
+        .income_expense_series(date("2023-02-01"), date("2023-02-05"), Granularity::Week, &all)
+        .unwrap();
+    assert_eq!(series.points.len(), 1);
+    assert_eq!(series.points[0].start, date("2023-01-30"));
+    assert_eq!(series.points[0].net, 1600.);
+
+    let series = budgeting
+        .income_expense_series(date("2023-01-10"), date("2023-01-12"), Granularity::Day, &all)
+        .unwrap();
+    assert_eq!(series.points.len(), 3);
+    assert_eq!(series.points[0].expense, -100.);
+    assert_eq!(series.points[1].expense, 0.);
+
+    let mut bills = TransactionQuery::new();
+    bills.category("Bills");
+    let series = budgeting
+        .income_expense_series(date("2023-01-01"), date("2023-12-31"), Granularity::Year, &bills)
+        .unwrap();
+    assert_eq!(series.points.len(), 1);
+    assert_eq!(series.points[0].expense, -370.);
+    assert_eq!(series.points[0].income, 0.);
+
+    let water = budgeting
+        .find_transactions(budgeting.transaction_query().unwrap().payee_contains("Water"))
+        .unwrap();
+    for t in &water {
+        budgeting.tag_transaction(t.id(), "utilities").unwrap();
+    }
+    let mut utilities = TransactionQuery::new();
+    utilities.tag("utilities");
+    let series = budgeting
+        .income_expense_series(date("2023-01-01"), date("2023-12-31"), Granularity::Year, &utilities)
+        .unwrap();
+    assert_eq!(series.total_expense, -220.);
+
+    let json = serde_json::to_value(&series).unwrap();
+    assert_eq!(json["granularity"], "Year");
+    assert_eq!(json["points"][0]["savings_rate"], serde_json::Value::Null);
+}
+
+#[test]
+fn top_payees_report() {
+    let mut budgeting = budgeting_with_history();
+    let report = budgeting
+        .top_payees_report(date("2023-01-01"), date("2023-03-31"), 2)
+        .unwrap();
+    assert_eq!(report.total_spent, 770.);
+    assert_eq!(report.payees.len(), 2);
+    let airline = &report.payees[0];
+    assert_eq!(airline.payee, "Airline");
+    assert_eq!(airline.spent, 400.);
+    assert_eq!(airline.share, 400. / 770. * 100.);
+    let water = report.payee("water co").unwrap();
+    assert_eq!(water.spent, 220.);
+    assert_eq!(water.transactions, 2);
+    assert_eq!(water.average, 110.);
+    assert_eq!(water.first_seen, parse_date("2023-01-10"));
+    assert_eq!(water.last_seen, parse_date("2023-03-11"));
+    // incomes are not spending
+    assert!(report.payee("Employer").is_none());
+
+    let report = budgeting
+        .top_payees_report(date("2023-03-01"), date("2023-03-31"), 10)
+        .unwrap();
+    assert_eq!(report.payees.len(), 1);
+    assert_eq!(report.payees[0].share, 100.);
+
+    let report = budgeting
+        .top_payees_report(date("2023-01-01"), NaiveDate::MAX, 10)
+        .unwrap();
+    assert_eq!(report.total_spent, 770.);
+}
+
+#[test]
+fn anomaly_report() {
+    use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+    use crate::budgeting::reports::anomalies::{Anomaly, AnomalySettings};
+
+    let mut budgeting = Budgeting::new(memory_db());
+    budgeting.new_budget("main", 0.).unwrap();
+    budgeting.create_category("Food", 0., false).unwrap();
+    let expenses = [
+        (95., "2023-01-05"),
+        (105., "2023-01-20"),
+        (100., "2023-02-05"),
+        (100., "2023-02-20"),
+        (90., "2023-03-05"),
+        (110., "2023-03-20"),
+        (600., "2023-04-05"),
+    ];
+    for (amount, d) in expenses {
+        budgeting
+            .new_transaction_to_category("Food").unwrap()
+            .expense(amount)
+            .payee("Market")
+            .note("")
+            .date_created(parse_date(d))
+            .done()
+            .unwrap();
+    }
+    let report = budgeting
+        .anomaly_report(date("2023-03-01"), date("2023-05-31"), AnomalySettings::default())
+        .unwrap();
+    assert_eq!(report.findings.len(), 3);
+
+    let unusual = report.unusual_transactions();
+    assert_eq!(unusual.len(), 1);
+    match unusual[0] {
+        Anomaly::Transaction { amount, mean, history, z_score, .. } => {
+            assert_eq!(*amount, 600.);
+            assert_eq!(*mean, 100.);
+            assert_eq!(*history, 6);
+            assert!(*z_score > 3.);
+        }
+        _ => panic!("expected a transaction"),
+    }
+
+    // march has no full trailing window, april is above and may below the average
+    let months = report.unusual_months();
+    assert_eq!(months.len(), 2);
+    match months[0] {
+        Anomaly::Month { month, spent, trailing_average, deviation, .. } => {
+            assert_eq!(*month, date("2023-04-01"));
+            assert_eq!(*spent, 600.);
+            assert_eq!(*trailing_average, 200.);
+            assert_eq!(*deviation, 200.);
+        }
+        _ => panic!("expected a month"),
+    }
+    match months[1] {
+        Anomaly::Month { month, deviation, .. } => {
+            assert_eq!(*month, date("2023-05-01"));
+            assert_eq!(*deviation, -100.);
+        }
+        _ => panic!("expected a month"),
+    }
+
+    let strict = AnomalySettings {
+        min_history: 10,
+        month_deviation: 500.,
+        ..AnomalySettings::default()
+    };
+    let report = budgeting
+        .anomaly_report(date("2023-03-01"), date("2023-05-31"), strict)
+        .unwrap();
+    assert!(report.findings.is_empty());
+
+    let json = serde_json::to_value(&budgeting
+        .anomaly_report(date("2023-04-01"), date("2023-04-30"), AnomalySettings::default())
+        .unwrap())
+        .unwrap();
+    assert_eq!(json["findings"][0]["kind"], "Transaction");
+    assert_eq!(json["findings"][1]["kind"], "Month");
+
+    for trailing_months in [u32::MAX as usize, usize::MAX] {
+        let too_long = AnomalySettings {
+            trailing_months,
+            ..AnomalySettings::default()
+        };
+        assert_eq!(
+            budgeting.anomaly_report(date("2023-03-01"), date("2023-05-31"), too_long),
+            Err(BudgetingErrors::InvalidInput {
+                field: "trailing_months".to_string(),
+                reason: InvalidReason::OutOfRange,
+            })
+        );
+    }
+}
+
+#[test]
+fn export_transactions_and_reports() {
+    use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+    use crate::budgeting::export::{
+        write_records, AmountSign, ExportFormat, ExportOptions, TransactionColumn,
+    };
+
+    let mut budgeting = budgeting_with_history();
+    let mut query = budgeting.transaction_query().unwrap();
+    query.category("Bills").before(parse_date("2023-02-01"));
+    let mut out = vec![];
+    let written = budgeting
+        .export_transactions(&query, &ExportOptions::default(), &mut out)
+        .unwrap();
+    assert_eq!(written, 2);
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "date,payee,note,amount,type,category,budget_account\n\
+         2023-01-10,Water Co,,-100.00,Expense,Bills,main\n\
+         2023-01-20,Power Co,,-150.00,Expense,Bills,main\n"
+    );
+
+    let t = budgeting.find_transactions(&query).unwrap()[0].clone();
+    budgeting.tag_transaction(t.id(), "utilities").unwrap();
+    budgeting.tag_transaction(t.id(), "home").unwrap();
+    let options = ExportOptions {
+        format: ExportFormat::Json,
+        columns: vec![
+            TransactionColumn::Id,
+            TransactionColumn::Date,
+            TransactionColumn::Amount,
+            TransactionColumn::Tags,
+        ],
+        date_format: "%d/%m/%Y".to_string(),
+        amount_sign: AmountSign::Inverted,
+    };
+    let mut out = vec![];
+    budgeting.export_transactions(&query, &options, &mut out).unwrap();
+    let json: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(json[0]["id"], t.id());
+    assert_eq!(json[0]["date"], "10/01/2023");
+    assert_eq!(json[0]["amount"], 100.);
+    assert_eq!(json[0]["tags"], serde_json::json!(["home", "utilities"]));
+    assert!(json[0].get("payee").is_none());
+    for date_format in ["%Y-%Q", "%Y-%m-%d %z"] {
+        let mut out = vec![];
+        let bad_format = ExportOptions {
+            date_format: date_format.to_string(),
+            ..ExportOptions::default()
+        };
+        assert!(matches!(
+            budgeting.export_transactions(&query, &bad_format, &mut out),
+            Err(BudgetingErrors::InvalidInput { field, reason: InvalidReason::NotADateFormat }) if field == "date_format"
+        ));
+        assert!(out.is_empty());
+    }
+
+    let summaries = budgeting.category_summaries().unwrap();
+    assert_eq!(summaries.len(), 3);
+    let bills = summaries.iter().find(|s| s.category == "Bills").unwrap();
+    assert_eq!(bills.allocated, BILLS);
+    assert_eq!(bills.expense, -370.);
+    assert_eq!(bills.balance, BILLS - 370.);
+    let mut out = vec![];
+    assert_eq!(write_records(&summaries, ExportFormat::Csv, &mut out).unwrap(), 3);
+    let csv = String::from_utf8(out).unwrap();
+    assert!(csv.starts_with("category,allocated,income,expense,transfer_in,transfer_out,balance\n"));
+
+    let report = budgeting
+        .budget_vs_actual_report(date("2023-01-01"), date("2023-02-28"))
+        .unwrap();
+    let mut out = vec![];
+    assert_eq!(write_records(&report.records(), ExportFormat::Csv, &mut out).unwrap(), 4);
+    let csv = String::from_utf8(out).unwrap();
+    assert_eq!(csv.lines().nth(1).unwrap(), "2023-01-01,Bills,2000.0,0.0,250.0,-250.0,1750.0,87.5");
+}
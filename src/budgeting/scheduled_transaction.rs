@@ -0,0 +1,289 @@
+use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+use crate::budgeting::category::CategoryModel;
+use crate::budgeting::transaction::TransactionType;
+use crate::budgeting::validation;
+use crate::schema::scheduled_transactions;
+use crate::{current_date, DbConnection, DEFAULT_CATEGORY};
+use chrono::{Days, Months, NaiveDate};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::DerefMut;
+use std::str::FromStr;
+
+/// largest `every` of a schedule, a hundred years of months
+pub const MAX_EVERY: i32 = 1200;
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum Recurrence {
+    #[default]
+    Once,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+impl From<Recurrence> for String {
+    fn from(r: Recurrence) -> String {
+        match r {
+            Recurrence::Once => "once".to_string(),
+            Recurrence::Daily => "daily".to_string(),
+            Recurrence::Weekly => "weekly".to_string(),
+            Recurrence::Monthly => "monthly".to_string(),
+            Recurrence::Yearly => "yearly".to_string(),
+        }
+    }
+}
+
+impl FromStr for Recurrence {
+    type Err = BudgetingErrors;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "once" => Ok(Recurrence::Once),
+            "daily" => Ok(Recurrence::Daily),
+            "weekly" => Ok(Recurrence::Weekly),
+            "monthly" => Ok(Recurrence::Monthly),
+            "yearly" => Ok(Recurrence::Yearly),
+            _ => Err(BudgetingErrors::InvalidInput {
+                field: "recurrence".to_string(),
+                reason: InvalidReason::OutOfRange,
+            }),
+        }
+    }
+}
+
+/// An income or expense expected in the future, once or repeating
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable)]
+#[diesel(table_name = scheduled_transactions)]
+pub struct ScheduledTransaction {
+    id: i32,
+    budget_account_id: i32,
+    category_id: i32,
+    transaction_type_id: i32,
+    payee: String,
+    note: String,
+    amount: f64,
+    start_date: NaiveDate,
+    recurrence: String,
+    every: i32,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = scheduled_transactions)]
+pub struct NewScheduledTransaction<'a> {
+    budget_account_id: i32,
+    category_id: i32,
+    transaction_type_id: i32,
+    payee: &'a str,
+    note: &'a str,
+    amount: f64,
+    start_date: NaiveDate,
+    recurrence: String,
+    every: i32,
+}
+
+impl ScheduledTransaction {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn budget_account_id(&self) -> i32 {
+        self.budget_account_id
+    }
+
+    pub fn category_id(&self) -> i32 {
+        self.category_id
+    }
+
+    pub fn transaction_type(&self) -> TransactionType {
+        TransactionType::from(self.transaction_type_id)
+    }
+
+    pub fn payee(&self) -> String {
+        self.payee.clone()
+    }
+
+    pub fn note(&self) -> String {
+        self.note.clone()
+    }
+
+    /// signed the same way as transactions, expenses are negative
+    pub fn amount(&self) -> f64 {
+        self.amount
+    }
+
+    pub fn start_date(&self) -> NaiveDate {
+        self.start_date
+    }
+
+    pub fn recurrence(&self) -> Recurrence {
+        Recurrence::from_str(&self.recurrence).unwrap_or_default()
+    }
+
+    pub fn every(&self) -> i32 {
+        self.every
+    }
+
+    /// date of the nth occurrence, counted from the start date so monthly
+    /// schedules do not drift after short months
+    fn nth(&self, n: u32) -> Option<NaiveDate> {
+        let k = n.checked_mul(self.every.max(1) as u32)?;
+        match self.recurrence() {
+            Recurrence::Once if n == 0 => Some(self.start_date),
+            Recurrence::Once => None,
+            Recurrence::Daily => self.start_date.checked_add_days(Days::new(k as u64)),
+            Recurrence::Weekly => self.start_date.checked_add_days(Days::new(7 * k as u64)),
+            Recurrence::Monthly => self.start_date.checked_add_months(Months::new(k)),
+            Recurrence::Yearly => self.start_date.checked_add_months(Months::new(k.checked_mul(12)?)),
+        }
+    }
+
+    /// dates this transaction is due on, from `from` to `to`, both included
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Vec<NaiveDate> {
+        let mut dates = vec![];
+        let mut n = 0;
+        while let Some(d) = self.nth(n) {
+            if d > to {
+                break;
+            }
+            if d >= from {
+                dates.push(d);
+            }
+            n += 1;
+        }
+        dates
+    }
+}
+
+/// Only way to create a scheduled transaction, like `TransactionBuilder`
+/// scheduled incomes always go to the default category
+pub struct ScheduledTransactionBuilder<'a> {
+    amount: Option<f64>,
+    payee: Option<&'a str>,
+    note: Option<&'a str>,
+    transaction_type: TransactionType,
+    start_date: Option<NaiveDate>,
+    recurrence: Recurrence,
+    every: i32,
+    category_id: i32,
+    budget_account_id: i32,
+    conn: DbConnection,
+}
+
+impl<'a> ScheduledTransactionBuilder<'a> {
+    pub fn new(conn: DbConnection, budget_account_id: i32, category_id: i32) -> Self {
+        ScheduledTransactionBuilder {
+            amount: None,
+            payee: None,
+            note: None,
+            transaction_type: TransactionType::Expense,
+            start_date: None,
+            recurrence: Recurrence::Once,
+            every: 1,
+            category_id,
+            budget_account_id,
+            conn,
+        }
+    }
+
+    pub fn expense(&mut self, amount: f64) -> &mut Self {
+        self.amount = Some(amount);
+        self.transaction_type = TransactionType::Expense;
+        self
+    }
+
+    pub fn income(&mut self, amount: f64) -> &mut Self {
+        self.amount = Some(amount);
+        self.transaction_type = TransactionType::Income;
+        self
+    }
+
+    pub fn payee(&mut self, payee: &'a str) -> &mut Self {
+        self.payee = Some(payee);
+        self
+    }
+
+    pub fn note(&mut self, note: &'a str) -> &mut Self {
+        self.note = Some(note);
+        self
+    }
+
+    /// first date the transaction is due, today if not set
+    pub fn starting(&mut self, start_date: NaiveDate) -> &mut Self {
+        self.start_date = Some(start_date);
+        self
+    }
+
+    /// repeats every `every` days, weeks, months or years, from 1 to `MAX_EVERY`
+    pub fn repeat(&mut self, recurrence: Recurrence, every: i32) -> &mut Self {
+        self.recurrence = recurrence;
+        self.every = every;
+        self
+    }
+
+    pub fn done(&mut self) -> Result<ScheduledTransaction, BudgetingErrors> {
+        if self.note.is_none() || self.payee.is_none() || self.amount.is_none() {
+            return Err(BudgetingErrors::MissingTransactionFields);
+        }
+        let amount = validation::positive("amount", self.amount.unwrap())?;
+        let payee = validation::not_empty("payee", self.payee.unwrap())?;
+        if !(1..=MAX_EVERY).contains(&self.every) {
+            return Err(BudgetingErrors::InvalidInput {
+                field: "every".to_string(),
+                reason: InvalidReason::OutOfRange,
+            });
+        }
+        if TransactionType::Income == self.transaction_type {
+            self.category_id = CategoryModel::find_by_name(gc!(self.conn), DEFAULT_CATEGORY)?.id();
+        }
+        let signed_amount = match self.transaction_type {
+            TransactionType::Income | TransactionType::TransferIn => amount,
+            TransactionType::Expense | TransactionType::TransferOut => -amount,
+        };
+        let new_scheduled = NewScheduledTransaction {
+            budget_account_id: self.budget_account_id,
+            category_id: self.category_id,
+            transaction_type_id: i32::from(self.transaction_type.clone()),
+            payee,
+            note: self.note.unwrap(),
+            amount: signed_amount,
+            start_date: self.start_date.unwrap_or_else(|| current_date().date()),
+            recurrence: String::from(self.recurrence),
+            every: self.every,
+        };
+        let scheduled = save_model!(
+            gc!(self.conn),
+            scheduled_transactions,
+            new_scheduled,
+            ScheduledTransaction
+        )?;
+        Ok(scheduled)
+    }
+}
+
+pub struct ScheduledTransactionModel;
+
+impl ScheduledTransactionModel {
+    pub(crate) fn find_all(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+    ) -> Result<Vec<ScheduledTransaction>, BudgetingErrors> {
+        imp_db!(scheduled_transactions);
+        scheduled_transactions
+            .filter(budget_account_id.eq(_budget_account_id))
+            .order(start_date.asc())
+            .load::<ScheduledTransaction>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn delete(
+        conn: &mut SqliteConnection,
+        scheduled_id: i32,
+    ) -> Result<usize, BudgetingErrors> {
+        imp_db!(scheduled_transactions);
+        diesel::delete(scheduled_transactions.find(scheduled_id))
+            .execute(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::transaction::Transaction;
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Double, Integer, Text};
+use serde::{Deserialize, Serialize};
+
+/// marks placed around the matched words in snippets
+pub const HIGHLIGHT_START: &str = "[";
+pub const HIGHLIGHT_END: &str = "]";
+
+/// A transaction found by full text search. Lower rank is a better match,
+/// snippets contain the matched words wrapped in `HIGHLIGHT_START` and `HIGHLIGHT_END`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, QueryableByName)]
+pub struct SearchMatch {
+    #[diesel(embed)]
+    pub transaction: Transaction,
+    #[diesel(sql_type = Double)]
+    pub rank: f64,
+    #[diesel(sql_type = Text)]
+    pub payee_snippet: String,
+    #[diesel(sql_type = Text)]
+    pub note_snippet: String,
+}
+
+/// Every word of the user's text has to match, as a prefix so "plumb" finds "plumber".
+/// Words are quoted so FTS5 operators in the text are searched literally
+fn fts_query(text: &str) -> String {
+    text.split_whitespace()
+        .map(|w| format!("\"{}\"*", w.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+pub(crate) fn search(
+    conn: &mut SqliteConnection,
+    text: &str,
+    budget_account_id: i32,
+    limit: i64,
+) -> Result<Vec<SearchMatch>, BudgetingErrors> {
+    let query = fts_query(text);
+    if query.is_empty() {
+        return Ok(vec![]);
+    }
+    diesel::sql_query(format!(
+        r#"SELECT t.*,
+                  bm25(transactions_fts) AS rank,
+                  snippet(transactions_fts, 0, '{HIGHLIGHT_START}', '{HIGHLIGHT_END}', '...', 8) AS payee_snippet,
+                  snippet(transactions_fts, 1, '{HIGHLIGHT_START}', '{HIGHLIGHT_END}', '...', 16) AS note_snippet
+           FROM transactions_fts
+                    INNER JOIN transactions t ON t.id = transactions_fts.rowid
+           WHERE transactions_fts MATCH ?
+             AND t.budget_account_id = ?
+           ORDER BY rank, t.id
+           LIMIT ?"#
+    ))
+    .bind::<Text, _>(query)
+    .bind::<Integer, _>(budget_account_id)
+    .bind::<BigInt, _>(limit)
+    .load::<SearchMatch>(conn)
+    .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+}
@@ -0,0 +1,124 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::validation;
+use crate::schema::{tags, transaction_tags, transactions};
+use diesel::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Free form label, a transaction can have any number of tags
+#[derive(Debug, PartialEq, Serialize, Deserialize, Default, Clone, Queryable, Identifiable)]
+#[diesel(table_name = tags)]
+pub struct Tag {
+    id: i32,
+    name: String,
+}
+
+#[derive(Insertable)]
+#[diesel(table_name = tags)]
+pub struct NewTag<'a> {
+    name: &'a str,
+}
+
+#[derive(Insertable, Queryable, Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[diesel(table_name = transaction_tags)]
+pub struct TransactionTag {
+    pub transaction_id: i32,
+    pub tag_id: i32,
+}
+
+impl Tag {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    pub fn name(&self) -> String {
+        self.name.to_owned()
+    }
+}
+
+pub struct TagModel;
+
+impl TagModel {
+    pub(crate) fn find_all(conn: &mut SqliteConnection) -> Result<Vec<Tag>, BudgetingErrors> {
+        imp_db!(tags);
+        tags.order(name.asc())
+            .load::<Tag>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    /// tags are matched case insensitively, a new tag is created if there is none
+    pub(crate) fn find_or_create(
+        conn: &mut SqliteConnection,
+        tag_name: &str,
+    ) -> Result<Tag, BudgetingErrors> {
+        let tag_name = validation::not_empty("tag", tag_name)?.trim();
+        imp_db!(tags);
+        match tags
+            .filter(crate::m::lower(name).eq(tag_name.to_lowercase()))
+            .first::<Tag>(conn)
+        {
+            Ok(t) => Ok(t),
+            Err(diesel::result::Error::NotFound) => {
+                let new_tag = NewTag { name: tag_name };
+                Ok(save_model!(conn, tags, new_tag, Tag)?)
+            }
+            Err(e) => Err(BudgetingErrors::UnspecifiedDatabaseError(e)),
+        }
+    }
+
+    /// fails with `TransactionNotFound` for an id no transaction has, foreign keys are
+    /// not enforced and a dangling row would make backups impossible to restore
+    pub(crate) fn tag(
+        conn: &mut SqliteConnection,
+        _transaction_id: i32,
+        tag_name: &str,
+    ) -> Result<Tag, BudgetingErrors> {
+        transactions::table
+            .find(_transaction_id)
+            .select(transactions::id)
+            .first::<i32>(conn)
+            .map_err(|e| match e {
+                diesel::result::Error::NotFound => BudgetingErrors::TransactionNotFound,
+                e => BudgetingErrors::UnspecifiedDatabaseError(e),
+            })?;
+        let tag = TagModel::find_or_create(conn, tag_name)?;
+        diesel::insert_or_ignore_into(transaction_tags::table)
+            .values(TransactionTag {
+                transaction_id: _transaction_id,
+                tag_id: tag.id,
+            })
+            .execute(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)?;
+        Ok(tag)
+    }
+
+    pub(crate) fn untag(
+        conn: &mut SqliteConnection,
+        _transaction_id: i32,
+        tag_name: &str,
+    ) -> Result<usize, BudgetingErrors> {
+        imp_db!(transaction_tags);
+        let tag_ids = tags::table
+            .filter(crate::m::lower(tags::name).eq(tag_name.trim().to_lowercase()))
+            .select(tags::id);
+        diesel::delete(
+            transaction_tags
+                .filter(transaction_id.eq(_transaction_id))
+                .filter(tag_id.eq_any(tag_ids)),
+        )
+        .execute(conn)
+        .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn tags_of(
+        conn: &mut SqliteConnection,
+        _transaction_id: i32,
+    ) -> Result<Vec<Tag>, BudgetingErrors> {
+        tags::table
+            .inner_join(transaction_tags::table)
+            .filter(transaction_tags::transaction_id.eq(_transaction_id))
+            .select((tags::id, tags::name))
+            .order(tags::name.asc())
+            .load::<Tag>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+}
@@ -281,3 +281,1485 @@ pub fn funding_category_as_much_as_possible() {
         Ok(3700.)
     );
 }
+
+#[test]
+fn applying_funding_template() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting.create_category("Savings", 0., false).unwrap();
+    budgeting
+        .funding_template_builder("payday")
+        .fixed("Bills", 1000.)
+        .percentage("Savings", 50.)
+        .fixed("Travel", 5000.)
+        .done()
+        .unwrap();
+    assert_eq!(
+        budgeting.funding_template_builder("payday").done(),
+        Err(BudgetingErrors::FundingTemplateAlreadyExists("payday".to_string()))
+    );
+    let summary = budgeting.apply_template("payday", DEFAULT_CATEGORY).unwrap();
+    // 50% of unallocated balance before anything was transferred
+    assert_eq!(summary.funded.len(), 2);
+    assert_eq!(summary.funded[1].funded, UNUSED / 2.);
+    // only 4000 left for travel
+    assert_eq!(summary.short.len(), 1);
+    assert_eq!(summary.short[0].category, "Travel");
+    assert_eq!(summary.short[0].shortfall(), 1000.);
+    assert_eq!(summary.total_funded(), UNUSED);
+    assert_eq!(budgeting.uncategorized_balance(), 0.);
+    assert_eq!(budgeting.category_balance("Bills").unwrap(), BILLS + 1000.);
+    assert_eq!(budgeting.category_balance("Travel").unwrap(), TRAVEL + 4000.);
+    assert_eq!(
+        budgeting.apply_template("monthly", DEFAULT_CATEGORY),
+        Err(BudgetingErrors::FundingTemplateNotFound("monthly".to_string()))
+    );
+}
+
+#[test]
+fn failed_funding_template_is_rolled_back() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting.create_category("Savings", 0., false).unwrap();
+    budgeting
+        .funding_template_builder("payday")
+        .fixed("Bills", 1000.)
+        .fixed("Savings", 1000.)
+        .done()
+        .unwrap();
+    let savings = budgeting.find_category("Savings").unwrap();
+    budgeting.delete_category(savings.id()).unwrap();
+    assert!(budgeting.apply_template("payday", DEFAULT_CATEGORY).is_err());
+    assert_eq!(budgeting.uncategorized_balance(), UNUSED);
+    assert_eq!(budgeting.category_balance("Bills").unwrap(), BILLS);
+}
+
+#[test]
+fn income_is_allocated_by_rules() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting.create_category("Savings", 0., false).unwrap();
+    budgeting.set_income_allocation_rule("Savings", 10.).unwrap();
+    budgeting.set_income_allocation_rule("Bills", 20.).unwrap();
+    assert_eq!(
+        budgeting.set_income_allocation_rule("Travel", 80.),
+        Err(BudgetingErrors::AllocationExceedsIncome)
+    );
+    // replaces the existing rule
+    budgeting.set_income_allocation_rule("Bills", 30.).unwrap();
+    assert_eq!(budgeting.income_allocation_rules().unwrap().len(), 2);
+
+    let income = budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(1000.)
+        .payee("Employer")
+        .note("Salary")
+        .done()
+        .unwrap();
+    assert_eq!(budgeting.category_balance("Savings").unwrap(), 100.);
+    assert_eq!(budgeting.category_balance("Bills").unwrap(), BILLS + 300.);
+    assert_eq!(budgeting.uncategorized_balance(), UNUSED + 600.);
+    let linked = budgeting.linked_transactions(income.id());
+    assert_eq!(linked.len(), 4);
+    assert!(linked.iter().all(|t| t.linked_transaction_id() == Some(income.id())));
+
+    // rules are per budget account
+    budgeting.switch_budget_account("wallet").unwrap();
+    budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(1000.)
+        .payee("Employer")
+        .note("Bonus")
+        .done()
+        .unwrap();
+    assert_eq!(budgeting.category_balance("Savings").unwrap(), 100.);
+}
+
+#[test]
+fn overspending_in_strict_and_permissive_mode() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    let t = budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(BILLS)
+        .payee("Electricity")
+        .note("All of it")
+        .done()
+        .unwrap();
+    assert!(!t.overspent());
+    let t = budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(100.)
+        .payee("Water")
+        .note("Nothing left")
+        .done()
+        .unwrap();
+    assert!(t.overspent());
+
+    budgeting.set_overspending_mode(OverspendingMode::Strict);
+    assert_eq!(
+        budgeting
+            .new_transaction_to_category("Travel").unwrap()
+            .expense(TRAVEL + 250.)
+            .payee("Airline")
+            .note("Tickets")
+            .done(),
+        Err(BudgetingErrors::Overspending {
+            category: "Travel".to_string(),
+            shortfall: 250.,
+        })
+    );
+    assert_eq!(budgeting.category_balance("Travel").unwrap(), TRAVEL);
+    // already negative, whole expense is the shortfall
+    assert_eq!(
+        budgeting
+            .new_transaction_to_category("Bills").unwrap()
+            .expense(50.)
+            .payee("Water")
+            .note("Still nothing left")
+            .done(),
+        Err(BudgetingErrors::Overspending {
+            category: "Bills".to_string(),
+            shortfall: 50.,
+        })
+    );
+}
+
+#[test]
+fn covering_overspent_category() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting.create_category("Savings", 500., true).unwrap();
+    budgeting
+        .new_transaction_to_category("Travel").unwrap()
+        .expense(TRAVEL + 800.)
+        .payee("Hotel")
+        .note("Long stay")
+        .done()
+        .unwrap();
+    let today = current_date().date();
+    let overspent = budgeting.overspent_categories(today).unwrap();
+    assert_eq!(overspent.len(), 1);
+    assert_eq!(overspent[0].category.name(), "Travel");
+    assert_eq!(overspent[0].shortfall(), 800.);
+    // the expense is in this month and in the main budget account
+    let last_month = month_start(today).pred_opt().unwrap();
+    assert!(budgeting.overspent_categories(last_month).unwrap().is_empty());
+    budgeting.switch_budget_account("wallet").unwrap();
+    assert!(budgeting.overspent_categories(today).unwrap().is_empty());
+    budgeting.switch_budget_account("main").unwrap();
+    assert_eq!(
+        budgeting.cover_overspending("Bills", &["Savings"]),
+        Err(BudgetingErrors::CategoryNotOverspent("Bills".to_string()))
+    );
+
+    let cover = budgeting
+        .cover_overspending("Travel", &["Savings", DEFAULT_CATEGORY])
+        .unwrap();
+    assert_eq!(
+        cover.covered_from,
+        vec![("Savings".to_string(), 500.), (DEFAULT_CATEGORY.to_string(), 300.)]
+    );
+    assert_eq!(cover.remaining(), 0.);
+    assert_eq!(budgeting.category_balance("Travel").unwrap(), 0.);
+    assert_eq!(budgeting.category_balance("Savings").unwrap(), 0.);
+    assert!(budgeting.overspent_categories(today).unwrap().is_empty());
+
+    let covers = budgeting.overspending_covers().unwrap();
+    assert_eq!(covers.len(), 2);
+    assert!(covers.iter().all(|t| t.overspending_cover() && t.linked_transaction_id().is_some()));
+}
+
+#[test]
+fn invalid_input_is_rejected() {
+    use crate::budgeting::budgeting_errors::InvalidReason;
+    let invalid = |field: &str, reason: InvalidReason| BudgetingErrors::InvalidInput {
+        field: field.to_string(),
+        reason,
+    };
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    let mut bills = budgeting.new_transaction_to_category("Bills").unwrap();
+    assert_eq!(
+        bills.expense(-10.).payee("Someone").note("").done(),
+        Err(invalid("amount", InvalidReason::Negative))
+    );
+    assert_eq!(
+        bills.expense(0.).payee("Someone").note("").done(),
+        Err(invalid("amount", InvalidReason::Zero))
+    );
+    assert_eq!(
+        bills.expense(f64::NAN).payee("Someone").note("").done(),
+        Err(invalid("amount", InvalidReason::NotANumber))
+    );
+    assert_eq!(
+        bills.income(f64::INFINITY).payee("Someone").note("").done(),
+        Err(invalid("amount", InvalidReason::Infinite))
+    );
+    assert_eq!(
+        bills.expense(10.).payee("  ").note("").done(),
+        Err(invalid("payee", InvalidReason::Empty))
+    );
+    assert_eq!(
+        budgeting.category_builder("Home").allocated(-1.).done(),
+        Err(invalid("allocated", InvalidReason::Negative))
+    );
+    assert_eq!(
+        budgeting.update_category(2, Some("".to_string()), None),
+        Err(invalid("name", InvalidReason::Empty))
+    );
+    assert_eq!(
+        budgeting.update_category(2, None, Some(f64::NAN)),
+        Err(invalid("allocated", InvalidReason::NotANumber))
+    );
+    let t = budgeting.transactions(None)[0].clone();
+    assert_eq!(
+        budgeting.update_transaction(
+            t.id(),
+            TransactionForm {
+                note: None,
+                payee: None,
+                date_created: None,
+                amount: Some(0.),
+                category_id: None,
+            }
+        ),
+        Err(invalid("amount", InvalidReason::Zero))
+    );
+    assert_eq!(
+        budgeting.new_budget("cash", -5.).map(|b| b.id()),
+        Err(invalid("amount", InvalidReason::Negative))
+    );
+    assert_eq!(
+        budgeting.set_income_allocation_rule("Bills", 120.).map(|r| r.id()),
+        Err(invalid("percentage", InvalidReason::OutOfRange))
+    );
+    assert_eq!(
+        budgeting.funding_template_builder("payday").fixed("Bills", -5.).done(),
+        Err(invalid("amount", InvalidReason::Negative))
+    );
+    // an empty account does not get an initial balance transaction
+    budgeting.new_budget("cash", 0.).unwrap();
+    assert!(budgeting.transactions(None).is_empty());
+    assert_eq!(budgeting.actual_total_balance(), INITIAL);
+}
+
+#[test]
+fn querying_transactions() {
+    use crate::budgeting::transaction_query::{SortKey, SortOrder};
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    let expenses = [
+        ("Bills", 120., "Water Co", "march water", "2023-03-02"),
+        ("Bills", 80., "Power_Co", "march power", "2023-03-05"),
+        ("Travel", 300., "Airline", "tickets", "2023-03-07"),
+        ("Travel", 45., "Taxi", "airport", "2023-04-01"),
+        ("Bills", 95., "Water Co", "april water", "2023-04-02"),
+    ];
+    for (category, amount, payee, note, date) in expenses {
+        budgeting
+            .new_transaction_to_category(category).unwrap()
+            .expense(amount)
+            .payee(payee)
+            .note(note)
+            .date_created(parse_date(date))
+            .done()
+            .unwrap();
+    }
+    let base = budgeting.transaction_query().unwrap();
+
+    let mut q = base.clone();
+    q.category("bills").payee_contains("water");
+    assert_eq!(budgeting.count_transactions(&q).unwrap(), 2);
+    assert_eq!(budgeting.sum_transactions(&q).unwrap(), -215.);
+
+    // LIKE wildcards are matched literally
+    let mut q = base.clone();
+    q.payee_contains("_");
+    assert_eq!(budgeting.count_transactions(&q).unwrap(), 1);
+
+    let mut q = base.clone();
+    q.transaction_type(TransactionType::Expense)
+        .from(parse_date("2023-03-05"))
+        .before(parse_date("2023-04-02"))
+        .max_amount(-50.);
+    let found = budgeting.find_transactions(&q).unwrap();
+    assert_eq!(
+        found.iter().map(|t| t.payee()).collect::<Vec<String>>(),
+        vec!["Power_Co", "Airline"]
+    );
+
+    let t = found[1].clone();
+    budgeting.tag_transaction(t.id(), "Vacation").unwrap();
+    budgeting.tag_transaction(t.id(), "vacation").unwrap();
+    assert_eq!(budgeting.transaction_tags(t.id()).unwrap().len(), 1);
+    let mut q = base.clone();
+    q.tag("VACATION");
+    assert_eq!(budgeting.find_transactions(&q).unwrap(), vec![t.clone()]);
+    budgeting.untag_transaction(t.id(), "Vacation").unwrap();
+    assert_eq!(budgeting.count_transactions(&q).unwrap(), 0);
+    assert_eq!(
+        budgeting.tag_transaction(9999, "vacation"),
+        Err(BudgetingErrors::TransactionNotFound)
+    );
+
+    // pages of two, biggest expense first
+    let mut q = base.clone();
+    q.transaction_type(TransactionType::Expense)
+        .sort_by(SortKey::Amount, SortOrder::Ascending)
+        .limit(2);
+    let first = budgeting.find_transactions(&q).unwrap();
+    assert_eq!(first[0].amount(), -300.);
+    q.after(first.last().unwrap());
+    let second = budgeting.find_transactions(&q).unwrap();
+    assert_eq!(
+        second.iter().map(|t| t.amount()).collect::<Vec<f64>>(),
+        vec![-95., -80.]
+    );
+    let mut by_offset = base.clone();
+    by_offset
+        .transaction_type(TransactionType::Expense)
+        .sort_by(SortKey::Amount, SortOrder::Ascending)
+        .limit(2)
+        .offset(2);
+    assert_eq!(budgeting.find_transactions(&by_offset).unwrap(), second);
+    assert_eq!(budgeting.count_transactions(&by_offset).unwrap(), 5);
+}
+
+#[test]
+fn searching_notes_and_payees() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    let plumber = budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(250.)
+        .payee("Joe's Plumbing")
+        .note("plumber invoice for the kitchen sink")
+        .done()
+        .unwrap();
+    budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(40.)
+        .payee("City Water")
+        .note("invoice")
+        .done()
+        .unwrap();
+    let found = budgeting.search_transactions("plumb invoice", 10).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].transaction, plumber);
+    assert_eq!(found[0].payee_snippet, "Joe's [Plumbing]");
+    assert!(found[0].note_snippet.starts_with("[plumber] [invoice]"));
+    assert_eq!(budgeting.search_transactions("invoice", 10).unwrap().len(), 2);
+    // operators are searched literally
+    assert!(budgeting.search_transactions("\"NOT AND", 10).unwrap().is_empty());
+
+    // index follows updates
+    budgeting
+        .update_transaction(
+            plumber.id(),
+            TransactionForm {
+                note: Some("leaking pipe".to_string()),
+                payee: None,
+                date_created: None,
+                amount: None,
+                category_id: None,
+            },
+        )
+        .unwrap();
+    assert_eq!(budgeting.search_transactions("invoice", 10).unwrap().len(), 1);
+    assert_eq!(budgeting.search_transactions("pipe", 10).unwrap().len(), 1);
+
+    // scoped to the current budget account
+    budgeting.switch_budget_account("wallet").unwrap();
+    assert!(budgeting.search_transactions("pipe", 10).unwrap().is_empty());
+}
+
+#[test]
+fn running_balance_of_transactions() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    new_budget_using_budgeting(&mut budgeting);
+    // entered out of order, listed by date
+    for (amount, date) in [(300., "2100-01-03"), (200., "2100-01-01"), (100., "2100-01-02")] {
+        budgeting
+            .new_transaction_to_category("Bills").unwrap()
+            .expense(amount)
+            .payee("Someone")
+            .note("")
+            .date_created(parse_date(date))
+            .done()
+            .unwrap();
+    }
+    let bills = budgeting.transactions_with_running_balance(Some("bills")).unwrap();
+    assert_eq!(
+        bills.iter().map(|t| t.running_balance).collect::<Vec<f64>>(),
+        vec![BILLS, BILLS - 200., BILLS - 300., BILLS - 600.]
+    );
+    assert_eq!(bills[1].transaction.amount(), -200.);
+
+    let all = budgeting.transactions_with_running_balance(None).unwrap();
+    // initial balance, two transfers for each category, three expenses
+    assert_eq!(all.len(), 8);
+    assert_eq!(all.last().unwrap().running_balance, 10000. - 600.);
+}
+
+#[test]
+fn balances_as_of_a_date() {
+    let db = memory_db();
+    let mut budgeting = Budgeting::new(db);
+    budgeting.new_budget("main", 0.).unwrap();
+    budgeting.create_category("Travel", 0., false).unwrap();
+    let day = |d: &str| parse_date(d).date();
+    budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(1000.)
+        .payee("Employer")
+        .note("")
+        .date_created(parse_date("2026-01-01"))
+        .done()
+        .unwrap();
+    for (from, to, date) in [(DEFAULT_CATEGORY, "Travel", "2026-01-02"), ("Travel", DEFAULT_CATEGORY, "2026-02-15")] {
+        let amount = 400.;
+        budgeting
+            .new_transaction_to_category(from).unwrap()
+            .transfer_from(amount)
+            .payee(to)
+            .note("Funded")
+            .date_created(parse_date(date))
+            .done()
+            .unwrap();
+        budgeting
+            .new_transaction_to_category(to).unwrap()
+            .transfer_to(amount)
+            .payee(from)
+            .note("Received")
+            .date_created(parse_date(date))
+            .done()
+            .unwrap();
+    }
+    budgeting
+        .new_transaction_to_category("Travel").unwrap()
+        .expense(150.)
+        .payee("Train")
+        .note("")
+        .date_created(parse_date("2026-01-31 18:30:00"))
+        .done()
+        .unwrap();
+
+    // the whole day is included
+    assert_eq!(budgeting.category_balance_as_of("Travel", day("2026-01-31")).unwrap(), 250.);
+    assert_eq!(budgeting.category_balance_as_of("Travel", day("2026-01-30")).unwrap(), 400.);
+    assert_eq!(budgeting.category_balance_as_of("Travel", day("2026-02-15")).unwrap(), -150.);
+    assert_eq!(budgeting.category_balance_as_of("Travel", day("2025-12-31")).unwrap(), 0.);
+    assert_eq!(budgeting.category_balance_as_of("Travel", NaiveDate::MAX).unwrap(), -150.);
+    assert_eq!(budgeting.uncategorized_balance_as_of(day("2026-01-31")), 600.);
+    assert_eq!(budgeting.actual_total_balance_as_of(day("2026-01-30")), 1000.);
+    assert_eq!(budgeting.actual_total_balance_as_of(day("2026-01-31")), 850.);
+    assert_eq!(
+        budgeting.budget_account_balance_as_of("main", day("2026-01-31")).unwrap(),
+        850.
+    );
+    let main = budgeting.current_budget().unwrap();
+    let mut model = budgeting.budget_account_model(main);
+    assert_eq!(model.balance_as_of(day("2025-12-31")), 0.);
+    assert_eq!(model.balance(), 850.);
+}
+
+#[test]
+fn import_csv_with_profile() {
+    use crate::budgeting::import::RowOutcome;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting
+        .import_profile_builder("bank").unwrap()
+        .delimiter(';')
+        .header_rows(2)
+        .date(0, "%d.%m.%Y")
+        .payee(1)
+        .memo(2)
+        .debit_credit(3, 4)
+        .category(5)
+        .decimal_separator(',')
+        .done()
+        .unwrap();
+    assert_eq!(
+        budgeting.import_profile_builder("bank").unwrap().date(0, "%d").payee(1).amount(2).done(),
+        Err(BudgetingErrors::ImportProfileAlreadyExists("bank".to_string()))
+    );
+    assert!(matches!(
+        budgeting.import_profile_builder("no amount").unwrap().date(0, "%d").payee(1).done(),
+        Err(BudgetingErrors::InvalidInput { .. })
+    ));
+    assert_eq!(budgeting.import_profiles().unwrap().len(), 1);
+
+    let statement = "My Bank statement\n\
+        Date;Payee;Memo;Debit;Credit;Category\n\
+        03.01.2023;Water Co;January;1.250,50;;Bills\n\
+        05.01.2023;;Salary January;;2.000,00;\n\
+        \n\
+        07.01.2023;Shop;;12,00;;\n\
+        yesterday;Shop;;1,00;;\n\
+        08.01.2023;Nothing;;;;\n\
+        09.01.2023;Train;;30,00;;Holidays\n";
+    let report = budgeting.import_csv("bank", statement.as_bytes()).unwrap();
+    assert_eq!(report.rows.len(), 6);
+    assert_eq!(report.created().len(), 3);
+    assert_eq!(report.skipped(), 1);
+    assert_eq!(report.failed(), 2);
+    assert_eq!(report.rows[2].line, 6);
+    assert_eq!(report.rows[3].outcome, RowOutcome::Failed("invalid date: yesterday".to_string()));
+    assert_eq!(report.rows[4].outcome, RowOutcome::Skipped("no amount".to_string()));
+    assert!(matches!(&report.rows[5].outcome, RowOutcome::Failed(e) if e.contains("\"Holidays\"")));
+
+    let ids = report.created();
+    let water = budgeting.get_transaction_model_by_id(ids[0]).unwrap().transaction().clone();
+    assert_eq!(water.amount(), -1250.5);
+    assert_eq!(water.note(), "January");
+    assert_eq!(water.date_created(), parse_date("2023-01-03"));
+    assert_eq!(budgeting.category_balance("Bills").unwrap(), BILLS - 1250.5);
+    let salary = budgeting.get_transaction_model_by_id(ids[1]).unwrap().transaction().clone();
+    assert_eq!(salary.payee(), "Salary January");
+    assert_eq!(TransactionType::from(salary.transfer_type_id()), TransactionType::Income);
+    assert_eq!(budgeting.uncategorized_balance(), UNUSED + 2000. - 12.);
+
+    assert_eq!(budgeting.delete_import_profile("bank").unwrap(), 1);
+    assert_eq!(
+        budgeting.import_csv("bank", statement.as_bytes()),
+        Err(BudgetingErrors::ImportProfileNotFound("bank".to_string()))
+    );
+}
+
+#[test]
+fn import_ofx_statements() {
+    use crate::budgeting::import::RowOutcome;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    let sgml = "OFXHEADER:100\n\
+        DATA:OFXSGML\n\
+        VERSION:102\n\
+        \n\
+        <OFX>\n\
+        <BANKMSGSRSV1><STMTTRNRS><STMTRS>\n\
+        <BANKTRANLIST>\n\
+        <STMTTRN>\n\
+        <TRNTYPE>DEBIT\n\
+        <DTPOSTED>20230105120000.000[-5:EST]\n\
+        <TRNAMT>-42.50\n\
+        <FITID>2023010501\n\
+        <NAME>Corner Shop &amp; Deli\n\
+        <MEMO>Card payment\n\
+        </STMTTRN>\n\
+        <STMTTRN>\n\
+        <TRNTYPE>CREDIT\n\
+        <DTPOSTED>20230110\n\
+        <TRNAMT>1500.00\n\
+        <FITID>2023011001\n\
+        <NAME>Employer\n\
+        </STMTTRN>\n\
+        <STMTTRN>\n\
+        <TRNTYPE>DEBIT\n\
+        <DTPOSTED>soon\n\
+        <TRNAMT>-1.00\n\
+        <FITID>2023011101\n\
+        </STMTTRN>\n\
+        </BANKTRANLIST>\n\
+        </STMTRS></STMTTRNRS></BANKMSGSRSV1>\n\
+        </OFX>\n";
+    let report = budgeting.import_ofx(sgml.as_bytes()).unwrap();
+    assert_eq!(report.created().len(), 2);
+    assert_eq!(report.failed(), 1);
+    assert_eq!(report.rows[0].line, 8);
+    assert_eq!(report.rows[2].outcome, RowOutcome::Failed("invalid date: soon".to_string()));
+    let shop = budgeting.get_transaction_model_by_id(report.created()[0]).unwrap().transaction().clone();
+    assert_eq!(shop.payee(), "Corner Shop & Deli");
+    assert_eq!(shop.note(), "Card payment");
+    assert_eq!(shop.amount(), -42.5);
+    assert_eq!(shop.date_created(), parse_date("2023-01-05 12:00:00"));
+    assert_eq!(shop.fitid(), Some("2023010501".to_string()));
+    assert_eq!(budgeting.actual_total_balance(), INITIAL + 1500. - 42.5);
+
+    // the same transactions downloaded again as OFX 2.x, only the new one is imported
+    let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<?OFX OFXHEADER="200" VERSION="211"?>
+<OFX><CREDITCARDMSGSRSV1><CCSTMTTRNRS><CCSTMTRS><BANKTRANLIST>
+<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20230105</DTPOSTED><TRNAMT>-42.50</TRNAMT><FITID>2023010501</FITID><NAME>Corner Shop</NAME></STMTTRN>
+<STMTTRN><TRNTYPE>DEBIT</TRNTYPE><DTPOSTED>20230112</DTPOSTED><TRNAMT>-9,99</TRNAMT><FITID>2023011201</FITID><NAME>Streaming</NAME></STMTTRN>
+</BANKTRANLIST></CCSTMTRS></CCSTMTTRNRS></CREDITCARDMSGSRSV1></OFX>"#;
+    let report = budgeting.import_ofx(xml.as_bytes()).unwrap();
+    assert_eq!(report.created().len(), 1);
+    assert_eq!(
+        report.rows[0].outcome,
+        RowOutcome::Skipped("already imported: 2023010501".to_string())
+    );
+    assert_eq!(report.rows[1].line, 5);
+    let streaming = budgeting.get_transaction_model_by_id(report.created()[0]).unwrap().transaction().clone();
+    assert_eq!(streaming.amount(), -9.99);
+
+    // ids are unique per budget account only
+    budgeting.new_budget("card", 0.).unwrap();
+    assert_eq!(budgeting.import_ofx(xml.as_bytes()).unwrap().created().len(), 2);
+}
+
+#[test]
+fn import_and_export_qif() {
+    use crate::budgeting::import::{ImportRowReport, RowOutcome};
+
+    let mut budgeting = Budgeting::new(memory_db());
+    budgeting.new_budget("main", 0.).unwrap();
+    budgeting.create_category("Bills", 0., false).unwrap();
+    budgeting.create_category("Travel", 0., false).unwrap();
+    let qif = "!Type:Bank\n\
+        D01/05/2023\n\
+        T-42.50\n\
+        PShop\n\
+        LBills\n\
+        MWeekly\n\
+        ^\n\
+        D1/10'23\n\
+        T1,500.00\n\
+        PEmployer\n\
+        ^\n\
+        D01/12/2023\n\
+        T-300.00\n\
+        L[Travel]\n\
+        ^\n\
+        D01/15/2023\n\
+        T-100.00\n\
+        PMarket\n\
+        SGroceries\n\
+        EFood\n\
+        $-60.00\n\
+        SHousehold\n\
+        $-40.00\n\
+        ^\n\
+        D13/45/2023\n\
+        T-1.00\n\
+        PNobody\n\
+        ^\n\
+        !Type:Invst\n\
+        D01/20/2023\n\
+        NBuy\n\
+        T-1000.00\n\
+        ^\n";
+    let report = budgeting.import_qif(qif.as_bytes(), false).unwrap();
+    // the split record fails as a whole, its categories do not exist
+    assert_eq!(report.created().len(), 4);
+    assert_eq!(report.failed(), 2);
+    assert_eq!(report.skipped(), 1);
+    assert_eq!(report.rows[4].line, 16);
+    assert!(matches!(&report.rows[4].outcome, RowOutcome::Failed(e) if e.contains("Groceries")));
+    assert_eq!(budgeting.category_summaries().unwrap().len(), 3);
+
+    let report = budgeting.import_qif(qif.as_bytes(), true).unwrap();
+    assert_eq!(report.created().len(), 6);
+    let rows: Vec<&ImportRowReport> = report.rows.iter().filter(|r| r.line == 16).collect();
+    assert_eq!(rows.len(), 2);
+    let food = budgeting.get_transaction_model_by_id(report.created()[4]).unwrap().transaction().clone();
+    assert_eq!(food.note(), "Food");
+    assert_eq!(food.payee(), "Market");
+    assert_eq!(food.amount(), -60.);
+    assert_eq!(budgeting.category_balance("Household").unwrap(), -40.);
+    let travel = budgeting.get_transaction_model_by_id(report.created()[3]).unwrap().transaction().clone();
+    assert_eq!(travel.date_created(), parse_date("2023-01-12"));
+
+    budgeting.transfer_fund("Travel", "Bills", 50.).unwrap();
+    budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(12.)
+        .payee("Corner\nShop")
+        .note("two\r\nlines")
+        .date_created(parse_date("2023-01-20"))
+        .done()
+        .unwrap();
+    let mut out = vec![];
+    let written = budgeting.export_qif(&mut out).unwrap();
+    let exported = String::from_utf8(out).unwrap();
+    assert_eq!(written, 10);
+    assert!(exported.contains("PCorner Shop\nLBills\nMtwo lines\n^\n"));
+    assert!(exported.starts_with("!Type:Bank\nD01/05/2023\nT-42.50\nPShop\nLBills\nMWeekly\n^\n"));
+    assert!(exported.contains("D01/12/2023\nT-300.00\nL[Travel]\n^\n"));
+    assert!(exported.contains("T0.00\nS[Travel]\n$50.00\nS[Bills]\n$-50.00\n^\n"));
+
+    // importing the export into another account gives the same category balances
+    budgeting.new_budget("copy", 0.).unwrap();
+    let report = budgeting.import_qif(exported.as_bytes(), false).unwrap();
+    assert_eq!(report.failed(), 0);
+    let copy = budgeting.category_summaries().unwrap();
+    budgeting.switch_budget_account("main").unwrap();
+    let main = budgeting.category_summaries().unwrap();
+    let balances = |s: &Vec<crate::budgeting::export::CategorySummary>| {
+        s.iter().map(|c| (c.category.clone(), c.balance, c.income, c.expense)).collect::<Vec<_>>()
+    };
+    assert_eq!(balances(&copy), balances(&main));
+
+    // `/` starts a class and `[x]` is a transfer when read back
+    let drinks = budgeting.create_category("Food/Drinks", 0., false).unwrap();
+    budgeting
+        .new_transaction_to_category("Food/Drinks").unwrap()
+        .expense(5.)
+        .payee("Cafe")
+        .note("")
+        .done()
+        .unwrap();
+    for name in ["Food/Drinks", "[x]"] {
+        budgeting.update_category(drinks.id(), Some(name.to_string()), None).unwrap();
+        let mut out = vec![];
+        assert_eq!(
+            budgeting.export_qif(&mut out),
+            Err(BudgetingErrors::InvalidInput {
+                field: "category".to_string(),
+                reason: crate::budgeting::budgeting_errors::InvalidReason::ReservedCharacter,
+            })
+        );
+        assert!(out.is_empty());
+    }
+}
+
+#[test]
+fn duplicates_on_import_and_entry() {
+    use crate::budgeting::duplicate::{DuplicateMode, DuplicateSettings};
+    use crate::budgeting::import::RowOutcome;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting
+        .import_profile_builder("bank").unwrap()
+        .date(0, "%Y-%m-%d")
+        .payee(1)
+        .amount(2)
+        .category(3)
+        .done()
+        .unwrap();
+    let water = budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(50.)
+        .payee("Water Co")
+        .note("")
+        .date_created(parse_date("2023-01-10"))
+        .done()
+        .unwrap();
+
+    budgeting.set_duplicate_detection(DuplicateMode::Skip, DuplicateSettings::default()).unwrap();
+    // two coffees on the same day in one statement are not duplicates of each other
+    let statement = "Date,Payee,Amount,Category\n\
+        2023-01-11,WATER CO,-50,Bills\n\
+        2023-01-11,Shop,-5,\n\
+        2023-01-11,Shop,-5,\n";
+    let report = budgeting.import_csv("bank", statement.as_bytes()).unwrap();
+    assert_eq!(
+        report.rows[0].outcome,
+        RowOutcome::Skipped(format!("duplicate of transaction {}", water.id()))
+    );
+    assert_eq!(report.created().len(), 2);
+
+    let found = budgeting.find_duplicates(parse_date("2023-01-10"), -50., "Water Co.").unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].transaction.id(), water.id());
+    assert!(found[0].score > 0.9);
+    assert!(budgeting.find_duplicates(parse_date("2023-01-10"), -51., "Water Co").unwrap().is_empty());
+    assert!(budgeting.find_duplicates(parse_date("2023-01-20"), -50., "Water Co").unwrap().is_empty());
+
+    let mut builder = budgeting.new_transaction_to_category("Bills").unwrap();
+    builder.expense(50.).payee("Water Co").note("").date_created(parse_date("2023-01-10"));
+    assert_eq!(builder.done(), Err(BudgetingErrors::DuplicateTransaction(water.id())));
+
+    budgeting.set_duplicate_detection(DuplicateMode::Flag, DuplicateSettings::default()).unwrap();
+    let flagged = budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(50.)
+        .payee("Water Co")
+        .note("")
+        .date_created(parse_date("2023-01-10"))
+        .done()
+        .unwrap();
+    assert_eq!(flagged.duplicate_of_id(), Some(water.id()));
+
+    budgeting.set_duplicate_detection(DuplicateMode::Merge, DuplicateSettings::default()).unwrap();
+    let coffee = budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .expense(5.)
+        .payee("Shop")
+        .note("coffee")
+        .date_created(parse_date("2023-01-12"))
+        .done()
+        .unwrap();
+    assert_eq!(coffee.id(), report.created()[0]);
+    assert_eq!(coffee.note(), "coffee");
+    let statement = "Date,Payee,Amount,Category\n2023-01-10,Water Co,-50,Bills\n";
+    let report = budgeting.import_csv("bank", statement.as_bytes()).unwrap();
+    assert_eq!(report.merged(), vec![water.id()]);
+    assert_eq!(budgeting.category_balance("Bills").unwrap(), BILLS - 100.);
+
+    use crate::budgeting::budgeting_errors::InvalidReason;
+    use crate::budgeting::duplicate::MAX_DUPLICATE_DAYS;
+    let invalid = |field: &str, reason: InvalidReason| BudgetingErrors::InvalidInput {
+        field: field.to_string(),
+        reason,
+    };
+    let settings = DuplicateSettings::default();
+    for (bad, error) in [
+        (
+            DuplicateSettings { max_days: MAX_DUPLICATE_DAYS + 1, ..settings },
+            invalid("max_days", InvalidReason::OutOfRange),
+        ),
+        (
+            DuplicateSettings { amount_tolerance: -0.1, ..settings },
+            invalid("amount_tolerance", InvalidReason::Negative),
+        ),
+        (
+            DuplicateSettings { min_score: f64::NAN, ..settings },
+            invalid("min_score", InvalidReason::NotANumber),
+        ),
+        (
+            DuplicateSettings { min_score: 1.5, ..settings },
+            invalid("min_score", InvalidReason::OutOfRange),
+        ),
+    ] {
+        assert_eq!(budgeting.set_duplicate_detection(DuplicateMode::Skip, bad), Err(error));
+    }
+    assert_eq!(budgeting.duplicate_mode(), DuplicateMode::Merge);
+    // the window of the search stops at the first and the last date chrono knows
+    let wide = DuplicateSettings { max_days: u32::MAX, ..settings };
+    for (date, amount) in [(NaiveDateTime::MIN, 1.), (NaiveDateTime::MAX, 2.)] {
+        let saved = budgeting
+            .new_transaction_to_category("Bills").unwrap()
+            .expense(amount)
+            .payee("Far away")
+            .note("")
+            .date_created(date)
+            .duplicate_detection(DuplicateMode::Flag, wide)
+            .done()
+            .unwrap();
+        assert_eq!(saved.duplicate_of_id(), None);
+    }
+}
+
+#[test]
+fn categorization_rules_on_import_and_retroactively() {
+    use crate::budgeting::budgeting_errors::InvalidReason;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting.create_category("Shopping", 100., false).unwrap();
+    budgeting
+        .categorization_rule_builder("marketplace").unwrap()
+        .payee_matches("(?i)mktp")
+        .priority(5)
+        .set_payee("Amazon Marketplace")
+        .set_tag("marketplace")
+        .done()
+        .unwrap();
+    budgeting
+        .categorization_rule_builder("amazon").unwrap()
+        .payee_matches("^AMZN")
+        .priority(10)
+        .set_payee("Amazon")
+        .set_category("Shopping")
+        .set_tag("online")
+        .done()
+        .unwrap();
+    assert_eq!(
+        budgeting.categorization_rule_builder("broken").unwrap().payee_matches("(").set_tag("x").done(),
+        Err(BudgetingErrors::InvalidInput {
+            field: "payee_pattern".to_string(),
+            reason: InvalidReason::NotAPattern,
+        })
+    );
+    assert_eq!(
+        budgeting.categorization_rule_builder("amazon").unwrap().payee_matches("a").set_tag("x").done(),
+        Err(BudgetingErrors::CategorizationRuleAlreadyExists("amazon".to_string()))
+    );
+    assert_eq!(
+        budgeting.categorization_rule_builder("gone").unwrap().payee_matches("a").set_category("Gone").done(),
+        Err(BudgetingErrors::CategoryNotFound)
+    );
+    let names: Vec<String> = budgeting.categorization_rules().unwrap().iter().map(|r| r.name()).collect();
+    assert_eq!(names, vec!["amazon", "marketplace"]);
+
+    // rules do not run on entry by default
+    let entered = budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .expense(20.)
+        .payee("AMZN MKTP US")
+        .note("")
+        .date_created(parse_date("2023-01-05"))
+        .done()
+        .unwrap();
+    assert_eq!(entered.payee(), "AMZN MKTP US");
+
+    budgeting
+        .import_profile_builder("bank").unwrap()
+        .date(0, "%Y-%m-%d")
+        .payee(1)
+        .amount(2)
+        .category(3)
+        .done()
+        .unwrap();
+    let statement = "Date,Payee,Amount,Category\n\
+        2023-01-06,AMZN MKTP DE,-30,\n\
+        2023-01-07,AMZN Digital,-5,Bills\n";
+    let report = budgeting.import_csv("bank", statement.as_bytes()).unwrap();
+    let shopping = budgeting.find_category("Shopping").unwrap().id();
+    let imported: Vec<Transaction> = report
+        .created()
+        .iter()
+        .map(|id| budgeting.get_transaction_model_by_id(*id).unwrap().transaction().clone())
+        .collect();
+    assert_eq!(imported[0].payee(), "Amazon");
+    assert_eq!(imported[0].category_id(), shopping);
+    let tags = |b: &mut Budgeting, id: i32| {
+        b.transaction_tags(id).unwrap().iter().map(|t| t.name()).collect::<Vec<_>>()
+    };
+    assert_eq!(tags(&mut budgeting, imported[0].id()), vec!["marketplace", "online"]);
+    // a category given by the statement is kept
+    assert_eq!(imported[1].payee(), "Amazon");
+    assert_eq!(imported[1].category_id(), budgeting.find_category("Bills").unwrap().id());
+    assert_eq!(tags(&mut budgeting, imported[1].id()), vec!["online"]);
+
+    let from = NaiveDate::from_ymd_opt(2023, 1, 1).unwrap();
+    let to = NaiveDate::from_ymd_opt(2023, 1, 31).unwrap();
+    let preview = budgeting.preview_categorization_rules(from, to).unwrap();
+    assert_eq!(preview.len(), 1);
+    assert_eq!(preview[0].transaction_id, entered.id());
+    assert_eq!(preview[0].rules, vec!["amazon", "marketplace"]);
+    assert_eq!(preview[0].payee, Some(("AMZN MKTP US".to_string(), "Amazon".to_string())));
+    assert_eq!(preview[0].category, Some((DEFAULT_CATEGORY.to_string(), "Shopping".to_string())));
+    assert_eq!(preview[0].tags, vec!["online", "marketplace"]);
+    let unchanged = budgeting.get_transaction_model_by_id(entered.id()).unwrap().transaction().clone();
+    assert_eq!(unchanged.payee(), "AMZN MKTP US");
+
+    assert_eq!(budgeting.apply_categorization_rules(from, to).unwrap(), preview);
+    let changed = budgeting.get_transaction_model_by_id(entered.id()).unwrap().transaction().clone();
+    assert_eq!(changed.payee(), "Amazon");
+    assert_eq!(changed.category_id(), shopping);
+    assert_eq!(tags(&mut budgeting, entered.id()), vec!["marketplace", "online"]);
+    assert!(budgeting.preview_categorization_rules(from, to).unwrap().is_empty());
+    assert!(budgeting.preview_categorization_rules(from, NaiveDate::MAX).unwrap().is_empty());
+
+    budgeting.set_categorization_rules_on_entry(true);
+    let entered = budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .expense(10.)
+        .payee("AMZN Prime")
+        .note("")
+        .date_created(parse_date("2023-02-01"))
+        .done()
+        .unwrap();
+    assert_eq!(entered.payee(), "Amazon");
+    assert_eq!(entered.category_id(), shopping);
+    assert_eq!(budgeting.delete_categorization_rule("amazon"), Ok(1));
+    assert_eq!(budgeting.categorization_rules().unwrap().len(), 1);
+
+    // a stored pattern that does not compile matches nothing
+    {
+        use crate::schema::categorization_rules::dsl::*;
+        diesel::update(categorization_rules.filter(name.eq("marketplace")))
+            .set(payee_pattern.eq("("))
+            .execute(RefCell::borrow_mut(&budgeting.conn).deref_mut())
+            .unwrap();
+    }
+    let entered = budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .expense(10.)
+        .payee("Corner Shop")
+        .note("")
+        .date_created(parse_date("2023-02-02"))
+        .done()
+        .unwrap();
+    assert_eq!(entered.payee(), "Corner Shop");
+    assert!(tags(&mut budgeting, entered.id()).is_empty());
+}
+
+#[test]
+fn suggesting_categories_from_history() {
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    assert!(budgeting.suggest_category("Water Co", "", 30.).unwrap().is_empty());
+    let history = [
+        ("Bills", "Water Co", "water bill", 30.),
+        ("Bills", "Power Co", "electricity", 80.),
+        ("Bills", "Power Co", "electricity", 85.),
+        ("Travel", "Airline", "flight to Oslo", 400.),
+        ("Travel", "Hotel Central", "room", 150.),
+    ];
+    for (category, payee, note, amount) in history {
+        budgeting
+            .new_transaction_to_category(category).unwrap()
+            .expense(amount)
+            .payee(payee)
+            .note(note)
+            .date_created(parse_date("2023-01-10"))
+            .done()
+            .unwrap();
+    }
+
+    let suggestions = budgeting.suggest_category("Power Co", "", 75.).unwrap();
+    assert_eq!(suggestions.len(), 2);
+    assert_eq!(suggestions[0].category, "Bills");
+    assert!(suggestions[0].confidence > 0.8);
+    assert!((suggestions.iter().map(|s| s.confidence).sum::<f64>() - 1.).abs() < 1e-9);
+    let suggestions = budgeting.suggest_category("Budget Airline", "flight to Rome", 380.).unwrap();
+    assert_eq!(suggestions[0].category, "Travel");
+
+    budgeting
+        .import_profile_builder("bank").unwrap()
+        .date(0, "%Y-%m-%d")
+        .payee(1)
+        .amount(2)
+        .done()
+        .unwrap();
+    let statement = "Date,Payee,Amount\n2023-02-10,POWER CO,-90\n";
+    let report = budgeting.import_csv("bank", statement.as_bytes()).unwrap();
+    let imported = budgeting.get_transaction_model_by_id(report.created()[0]).unwrap().transaction().clone();
+    assert_eq!(imported.category_id(), budgeting.default_category().id());
+
+    budgeting.set_import_category_suggestions(Some(0.8));
+    let statement = "Date,Payee,Amount\n2023-03-10,POWER CO,-90\n2023-03-11,Unknown,-9\n";
+    let report = budgeting.import_csv("bank", statement.as_bytes()).unwrap();
+    let imported: Vec<Transaction> = report
+        .created()
+        .iter()
+        .map(|id| budgeting.get_transaction_model_by_id(*id).unwrap().transaction().clone())
+        .collect();
+    assert_eq!(imported[0].category_id(), budgeting.find_category("Bills").unwrap().id());
+    // not sure enough about it
+    assert_eq!(imported[1].category_id(), budgeting.default_category().id());
+}
+
+#[test]
+fn no_category_suggestion_for_unseen_payees() {
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(80.)
+        .payee("Power Co")
+        .note("electricity")
+        .date_created(parse_date("2023-01-10"))
+        .done()
+        .unwrap();
+    // a single category learned would otherwise be certain of anything
+    assert!(budgeting.suggest_category("Zebra Cafe", "", 80.).unwrap().is_empty());
+    assert_eq!(budgeting.suggest_category("Power Co", "", 80.).unwrap()[0].category, "Bills");
+
+    budgeting
+        .import_profile_builder("bank").unwrap()
+        .date(0, "%Y-%m-%d")
+        .payee(1)
+        .amount(2)
+        .done()
+        .unwrap();
+    budgeting.set_import_category_suggestions(Some(0.9));
+    let statement = "Date,Payee,Amount\n2023-02-10,Zebra Cafe,-80\n";
+    let report = budgeting.import_csv("bank", statement.as_bytes()).unwrap();
+    let imported = budgeting.get_transaction_model_by_id(report.created()[0]).unwrap().transaction().clone();
+    assert_eq!(imported.category_id(), budgeting.default_category().id());
+}
+
+#[test]
+fn backup_and_restore() {
+    use crate::budgeting::backup::Backup;
+    use crate::budgeting::duplicate::{DuplicateMode, DuplicateSettings};
+    use crate::budgeting::scheduled_transaction::Recurrence;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    new_budget_using_budgeting(&mut budgeting);
+    budgeting.create_category("Savings", 0., false).unwrap();
+    budgeting.set_income_allocation_rule("Savings", 10.).unwrap();
+    let salary = budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(1000.)
+        .payee("Employer")
+        .note("salary")
+        .done()
+        .unwrap();
+    let expense = budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(120.)
+        .payee("Water Co")
+        .note("")
+        .done()
+        .unwrap();
+    budgeting.tag_transaction(expense.id(), "home").unwrap();
+    budgeting.transfer_fund("Travel", "Bills", 50.).unwrap();
+    budgeting
+        .funding_template_builder("payday")
+        .fixed("Bills", 100.)
+        .percentage("Savings", 20.)
+        .done()
+        .unwrap();
+    budgeting
+        .set_period_allocation("Travel", NaiveDate::from_ymd_opt(2023, 5, 1).unwrap(), 300.)
+        .unwrap();
+    budgeting
+        .new_scheduled_transaction_to_category("Bills").unwrap()
+        .expense(40.)
+        .payee("Internet")
+        .note("")
+        .starting(NaiveDate::from_ymd_opt(2023, 1, 1).unwrap())
+        .repeat(Recurrence::Monthly, 1)
+        .done()
+        .unwrap();
+    budgeting
+        .categorization_rule_builder("water").unwrap()
+        .payee_matches("(?i)water")
+        .set_category("Bills")
+        .done()
+        .unwrap();
+    budgeting
+        .import_profile_builder("bank").unwrap()
+        .date(0, "%Y-%m-%d")
+        .payee(1)
+        .amount(2)
+        .done()
+        .unwrap();
+    budgeting.set_duplicate_detection(DuplicateMode::Flag, DuplicateSettings::default()).unwrap();
+
+    let mut document = vec![];
+    budgeting.export_all(&mut document).unwrap();
+    let backup: Backup = serde_json::from_slice(&document).unwrap();
+    assert_eq!(backup.version, crate::budgeting::backup::BACKUP_VERSION);
+    assert_eq!(backup.budget_accounts.len(), 2);
+
+    let mut restored = Budgeting::new(memory_db());
+    restored.import_all(document.as_slice()).unwrap();
+    assert_eq!(restored.duplicate_mode(), DuplicateMode::Flag);
+    assert!(restored.current_budget().is_none());
+    for account in ["wallet", "main"] {
+        let date = NaiveDate::from_ymd_opt(2100, 1, 1).unwrap();
+        assert_eq!(
+            restored.budget_account_balance_as_of(account, date).unwrap(),
+            budgeting.budget_account_balance_as_of(account, date).unwrap()
+        );
+    }
+    for category in [DEFAULT_CATEGORY, "Bills", "Travel", "Savings"] {
+        assert_eq!(
+            restored.category_balance(category).unwrap(),
+            budgeting.category_balance(category).unwrap()
+        );
+    }
+    restored.switch_budget_account("main").unwrap();
+    assert_eq!(restored.income_allocation_rules().unwrap().len(), 1);
+    assert_eq!(restored.scheduled_transactions().unwrap().len(), 1);
+    assert_eq!(restored.categorization_rules().unwrap().len(), 1);
+    assert_eq!(restored.import_profiles().unwrap().len(), 1);
+    assert_eq!(restored.funding_templates().unwrap().len(), 1);
+    assert_eq!(restored.period_allocations("Travel").unwrap().len(), 1);
+    let water = restored
+        .find_transactions(restored.transaction_query().unwrap().payee_contains("Water"))
+        .unwrap();
+    assert_eq!(water.len(), 1);
+    let tags: Vec<String> = restored.transaction_tags(water[0].id()).unwrap().iter().map(|t| t.name()).collect();
+    assert_eq!(tags, vec!["home"]);
+    // allocation transfers still point at the income they came from
+    let salary = restored
+        .find_transactions(restored.transaction_query().unwrap().payee_contains("Employer"))
+        .unwrap()
+        .remove(0);
+    assert_eq!(salary.note(), "salary");
+    let linked = restored
+        .backup()
+        .unwrap()
+        .transactions
+        .iter()
+        .filter(|t| t.linked_transaction_id == Some(salary.id()))
+        .count();
+    assert_eq!(linked, 2);
+    assert_eq!(restored.restore(&backup), Err(BudgetingErrors::RestoreTargetNotEmpty));
+
+    let mut newer = backup.clone();
+    newer.version += 1;
+    assert_eq!(
+        Budgeting::new(memory_db()).restore(&newer),
+        Err(BudgetingErrors::UnsupportedBackupVersion(newer.version))
+    );
+    let mut tampered = backup.clone();
+    tampered.transactions[0].amount += 1.;
+    assert!(matches!(
+        Budgeting::new(memory_db()).restore(&tampered),
+        Err(BudgetingErrors::InvalidBackup(_))
+    ));
+    let mut dangling = backup.clone();
+    dangling.transaction_tags[0].tag_id = 999;
+    let mut empty = Budgeting::new(memory_db());
+    assert_eq!(
+        empty.restore(&dangling),
+        Err(BudgetingErrors::InvalidBackup("tag 999 does not exist".to_string()))
+    );
+    assert!(empty.budget_accounts().unwrap().is_empty());
+    // rules the builder would refuse are not restored
+    let mut broken = backup.clone();
+    broken.categorization_rules[0].payee_pattern = Some("(".to_string());
+    assert!(matches!(
+        empty.restore(&broken),
+        Err(BudgetingErrors::InvalidBackup(e)) if e.contains("payee_pattern")
+    ));
+    broken.categorization_rules[0].payee_pattern = None;
+    broken.categorization_rules[0].note_pattern = None;
+    assert!(matches!(
+        empty.restore(&broken),
+        Err(BudgetingErrors::InvalidBackup(e)) if e.contains("no pattern")
+    ));
+    let mut unchecked = backup.clone();
+    unchecked.settings.duplicate_settings.min_score = -1.;
+    assert!(matches!(
+        empty.restore(&unchecked),
+        Err(BudgetingErrors::InvalidBackup(e)) if e.contains("min_score")
+    ));
+    assert!(matches!(
+        empty.import_all("{}".as_bytes()),
+        Err(BudgetingErrors::InvalidBackup(_))
+    ));
+}
+
+#[test]
+fn journal_export_for_plain_text_accounting() {
+    use crate::budgeting::journal::{JournalFormat, JournalOptions};
+
+    let mut budgeting = Budgeting::new(memory_db());
+    budgeting.new_budget("card", 0.).unwrap();
+    budgeting.create_category("Bills", 0., false).unwrap();
+    budgeting.create_category("Eating out", 0., false).unwrap();
+    budgeting
+        .new_transaction_to_category("Eating out").unwrap()
+        .expense(20.)
+        .payee("Cafe")
+        .note("lunch")
+        .date_created(parse_date("2023-02-03"))
+        .done()
+        .unwrap();
+    budgeting.new_budget("Main account", 0.).unwrap();
+    budgeting.set_income_allocation_rule("Bills", 10.).unwrap();
+    budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(1000.)
+        .payee("Employer")
+        .note("January")
+        .date_created(parse_date("2023-01-02"))
+        .done()
+        .unwrap();
+    budgeting
+        .transfer(DEFAULT_CATEGORY, "Eating out", 50., false, Some(parse_date("2023-01-10")))
+        .unwrap();
+    let power = budgeting
+        .new_transaction_to_category("Bills").unwrap()
+        .expense(30.)
+        .payee("Power Co")
+        .note("Power")
+        .date_created(parse_date("2023-01-15"))
+        .done()
+        .unwrap();
+    budgeting.tag_transaction(power.id(), "home").unwrap();
+    budgeting
+        .new_transaction_to_category("Eating out").unwrap()
+        .expense(12.5)
+        .payee("Joe's \"Diner\"")
+        .note("")
+        .date_created(parse_date("2023-02-03"))
+        .done()
+        .unwrap();
+
+    let mut options = JournalOptions {
+        currency: "EUR".to_string(),
+        liabilities: vec!["card".to_string()],
+        ..JournalOptions::default()
+    };
+    let mut out = vec![];
+    // income, its allocation, the transfer and three expenses, each transfer once
+    assert_eq!(budgeting.export_journal(&options, &mut out).unwrap(), 6);
+    let beancount = String::from_utf8(out).unwrap();
+    assert!(beancount.starts_with("option \"operating_currency\" \"EUR\"\n\n2023-01-02 open Assets:Main-Account:Bills\n"));
+    assert!(beancount.contains("2023-01-02 open Liabilities:Card:Eating-Out\n"));
+    assert!(beancount.contains(
+        "2023-01-02 * \"Bills\" \"Funded\"\n  \
+        Assets:Main-Account:Unallocated  -100.00 EUR\n  \
+        Assets:Main-Account:Bills  100.00 EUR\n"
+    ));
+    assert!(beancount.contains(
+        "2023-01-10 * \"Eating out\" \"Funded\"\n  \
+        Assets:Main-Account:Unallocated  -50.00 EUR\n  \
+        Assets:Main-Account:Eating-Out  50.00 EUR\n"
+    ));
+    assert!(beancount.contains(
+        "2023-01-15 * \"Power Co\" \"Power\" #Home\n  \
+        Expenses:Bills  30.00 EUR\n  \
+        Assets:Main-Account:Bills  -30.00 EUR\n"
+    ));
+    assert!(beancount.contains("2023-02-03 * \"Joe's \\\"Diner\\\"\" \"\"\n"));
+    assert!(beancount.contains("2023-02-01 balance Assets:Main-Account:Bills 70.00 EUR\n"));
+    assert!(beancount.contains("2023-02-01 balance Assets:Main-Account:Unallocated 850.00 EUR\n"));
+    assert!(beancount.contains("2023-03-01 balance Assets:Main-Account:Eating-Out 37.50 EUR\n"));
+    assert!(beancount.contains("2023-03-01 balance Liabilities:Card:Eating-Out -20.00 EUR\n"));
+
+    options.format = JournalFormat::Ledger;
+    let mut out = vec![];
+    budgeting.export_journal(&options, &mut out).unwrap();
+    let ledger = String::from_utf8(out).unwrap();
+    assert!(ledger.starts_with("account Assets:Main-Account:Bills\n"));
+    assert!(ledger.contains(
+        "2023-01-15 * Power Co\n    \
+        ; Power\n    \
+        ; :Home:\n    \
+        Expenses:Bills  30.00 EUR\n    \
+        Assets:Main-Account:Bills  -30.00 EUR\n"
+    ));
+    assert!(ledger.contains(
+        "2023-01-31 * Balance assertions\n    \
+        Assets:Main-Account:Bills  0.00 EUR = 70.00 EUR\n"
+    ));
+    // January is asserted before the first entry of February
+    assert!(ledger.find("2023-01-31 * Balance").unwrap() < ledger.find("2023-02-03").unwrap());
+    assert!(ledger.ends_with("    Liabilities:Card:Eating-Out  0.00 EUR = -20.00 EUR\n\n"));
+}
+
+#[test]
+fn journal_pairs_transfers_saved_without_link() {
+    use crate::budgeting::journal::JournalOptions;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    budgeting.new_budget("main", 0.).unwrap();
+    budgeting.create_category("Bills", 0., false).unwrap();
+    let unallocated = budgeting.default_category();
+    budgeting
+        .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+        .income(500.)
+        .payee("Employer")
+        .note("salary")
+        .date_created(parse_date("2023-01-02"))
+        .done()
+        .unwrap();
+    // both sides of a transfer the way they were saved before sides were linked
+    for _ in 0..2 {
+        budgeting
+            .new_transaction_to_category(DEFAULT_CATEGORY).unwrap()
+            .transfer_from(40.)
+            .payee("Bills")
+            .note("Funded")
+            .date_created(parse_date("2023-01-03"))
+            .done()
+            .unwrap();
+        budgeting
+            .new_transaction_to_category("Bills").unwrap()
+            .transfer_to(40.)
+            .transfer_category_id(unallocated.id())
+            .payee(DEFAULT_CATEGORY)
+            .note("Received")
+            .date_created(parse_date("2023-01-03"))
+            .done()
+            .unwrap();
+    }
+    assert_eq!(budgeting.category_balance(DEFAULT_CATEGORY).unwrap(), 420.);
+    assert_eq!(budgeting.category_balance("Bills").unwrap(), 80.);
+
+    let mut out = vec![];
+    assert_eq!(budgeting.export_journal(&JournalOptions::default(), &mut out).unwrap(), 3);
+    let journal = String::from_utf8(out).unwrap();
+    assert!(!journal.contains("Equity:Transfers"));
+    assert!(journal.contains("2023-02-01 balance Assets:Main:Unallocated 420.00 USD\n"));
+    assert!(journal.contains("2023-02-01 balance Assets:Main:Bills 80.00 USD\n"));
+}
+
+#[test]
+fn importing_ynab4_and_actual_budget_exports() {
+    use crate::budgeting::import::budget_app::BudgetAppSettings;
+    use crate::budgeting::journal::JournalOptions;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    let settings = BudgetAppSettings::default();
+    let register = r#""Account","Flag","Check Number","Date","Payee","Category","Master Category","Sub Category","Memo","Outflow","Inflow","Cleared","Running Balance"
+"Checking","","","01/01/2016","Starting Balance","Income: Available this month","Income","Available this month","","$0.00","$1,500.00","R","$1,500.00"
+"Checking","","","01/03/2016","Grocer","Everyday Expenses: Groceries","Everyday Expenses","Groceries","weekly","$45.20","$0.00","C","$1,454.80"
+"Checking","","","01/04/2016","Hardware","Everyday Expenses: Household","Everyday Expenses","Household","(Split 1/2) nails","$10.00","$0.00","C","$1,444.80"
+"Checking","","","01/04/2016","Hardware","Everyday Expenses: Groceries","Everyday Expenses","Groceries","(Split 2/2) snacks","$5.00","$0.00","C","$1,439.80"
+"Savings","","","01/05/2016","Transfer : Checking","","","","rainy day","$0.00","$200.00","C","$200.00"
+"Checking","","","01/05/2016","Transfer : Savings","","","","rainy day","$200.00","$0.00","C","$1,239.80"
+"Checking","","","01/06/2016","Transfer : Mortgage","Monthly Bills: Mortgage","Monthly Bills","Mortgage","","$300.00","$0.00","C","$939.80"
+"Checking","","","13/45/2016","Nobody","","","","","$1.00","$0.00","",""
+"#;
+    let report = budgeting.import_budget_app(register.as_bytes(), &settings).unwrap();
+    // the transfer is created from the side going out, both of its transactions on line 7
+    assert_eq!(report.created().len(), 7);
+    assert_eq!(report.skipped(), 1);
+    assert_eq!(report.failed(), 1);
+    assert!(matches!(&report.rows[4].outcome, RowOutcome::Skipped(r) if r.contains("line 7")));
+    assert_eq!(report.rows.iter().filter(|r| r.line == 7).count(), 2);
+    let day = |d: &str| parse_date(d).date();
+    assert_eq!(budgeting.budget_account_balance_as_of("Checking", day("2016-12-31")).unwrap(), 939.8);
+    assert_eq!(budgeting.budget_account_balance_as_of("Savings", day("2016-12-31")).unwrap(), 200.);
+    assert_eq!(budgeting.category_balance("Everyday Expenses: Groceries").unwrap(), -50.2);
+    // a transfer to an off budget account is spending
+    assert_eq!(budgeting.category_balance("Monthly Bills: Mortgage").unwrap(), -300.);
+    let snacks = budgeting.get_transaction_model_by_id(report.created()[3]).unwrap().transaction().clone();
+    assert_eq!(snacks.note(), "snacks");
+    let received = budgeting.get_transaction_model_by_id(report.created()[5]).unwrap().transaction().clone();
+    assert_eq!(TransactionType::from(received.transfer_type_id()), TransactionType::TransferIn);
+    assert_eq!(received.linked_transaction_id(), Some(report.created()[4]));
+    assert_eq!(received.budget_account_id(), budgeting.find_budget("Savings").unwrap().id());
+
+    let budget = r#""Month","Category","Master Category","Sub Category","Budgeted","Outflows","Category Balance"
+"Jan 2016","Everyday Expenses: Groceries","Everyday Expenses","Groceries","$200.00","-$50.20","$149.80"
+"Jan 2016","Everyday Expenses: Fuel","Everyday Expenses","Fuel","$0.00","$0.00","$0.00"
+"Feb 2016","Everyday Expenses: Groceries","Everyday Expenses","Groceries","$180.00","$0.00","$329.80"
+"#;
+    let report = budgeting.import_budget_app(budget.as_bytes(), &settings).unwrap();
+    assert_eq!(report.created().len(), 2);
+    assert_eq!(report.skipped(), 1);
+    let allocations = budgeting.period_allocations("Everyday Expenses: Groceries").unwrap();
+    assert_eq!(allocations.len(), 2);
+    assert_eq!(allocations[1].month(), day("2016-02-01"));
+    assert_eq!(allocations[1].amount(), 180.);
+
+    budgeting.switch_budget_account("Checking").unwrap();
+    let actual = "Account,Date,Payee,Notes,Category,Amount,Split_Amount,Cleared\n\
+        Card,2016-02-01,Checking,payment,,100.00,0,true\n\
+        Checking,2016-02-01,Card,payment,,-100.00,0,true\n\
+        Card,2016-02-02,Market,,,-30.00,0,false\n\
+        Card,2016-02-02,Market,,Everyday Expenses: Groceries,0,-20.00,false\n\
+        Card,2016-02-02,Market,,Food: Snacks,0,-10.00,false\n\
+        Card,2016-02-03,Employer,,Income: Salary,500.00,0,true\n";
+    let report = budgeting.import_budget_app(actual.as_bytes(), &settings).unwrap();
+    assert_eq!(report.created().len(), 5);
+    assert_eq!(report.skipped(), 2);
+    assert!(matches!(&report.rows[3].outcome, RowOutcome::Skipped(r) if r.contains("split")));
+    assert_eq!(budgeting.budget_account_balance_as_of("Card", day("2016-12-31")).unwrap(), 570.);
+    assert_eq!(budgeting.category_balance("Food: Snacks").unwrap(), -10.);
+    assert_eq!(budgeting.current_budget().unwrap().filed_as(), "Checking");
+
+    // a transfer between budget accounts is one balanced journal entry
+    let mut out = vec![];
+    budgeting.export_journal(&JournalOptions::default(), &mut out).unwrap();
+    let journal = String::from_utf8(out).unwrap();
+    assert!(journal.contains(
+        "2016-02-01 * \"Transfer : Card\" \"payment\"\n  \
+        Assets:Checking:Unallocated  -100.00 USD\n  \
+        Assets:Card:Unallocated  100.00 USD\n"
+    ));
+    assert!(!journal.contains("Equity:Transfers"));
+}
+
+#[test]
+fn failed_budget_app_rows_leave_no_budget_account_selected() {
+    use crate::budgeting::import::budget_app::BudgetAppSettings;
+
+    let mut budgeting = Budgeting::new(memory_db());
+    budgeting.set_overspending_mode(OverspendingMode::Strict);
+    let actual = "Account,Date,Payee,Notes,Category,Amount\n\
+        Card,2016-02-02,Market,,Food: Snacks,-10.00\n";
+    let report = budgeting
+        .import_budget_app(actual.as_bytes(), &BudgetAppSettings::default())
+        .unwrap();
+    assert_eq!(report.failed(), 1);
+    // the new budget account was rolled back with the row
+    assert!(budgeting.current_budget().is_none());
+    assert!(budgeting.find_budget("Card").is_err());
+}
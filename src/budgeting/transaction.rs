@@ -1,15 +1,22 @@
 use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::categorization_rule::{RuleOutcome, RuleSet};
 use crate::budgeting::category::{Category, CategoryModel};
+use crate::budgeting::duplicate::{DuplicateMatcher, DuplicateMode, DuplicateProbe, DuplicateSettings};
+use crate::budgeting::income_allocation_rule::IncomeAllocationRuleModel;
+use crate::budgeting::tag::TagModel;
+use crate::budgeting::validation;
 use crate::schema::transactions;
 use crate::{current_date, parse_date, DbConnection, DEFAULT_CATEGORY};
-use chrono::NaiveDateTime;
+use crate::budgeting::reports::start_of_day;
+use chrono::{NaiveDate, NaiveDateTime};
 use diesel::dsl::sum;
 use diesel::prelude::*;
+use diesel::sql_types::Double;
 use serde::{Deserialize, Serialize};
 use std::ops::DerefMut;
 use tracing::warn;
 
-#[derive(Eq, PartialEq, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone, Serialize, Deserialize)]
 pub enum TransactionType {
     Income,
     Expense,
@@ -17,6 +24,16 @@ pub enum TransactionType {
     TransferOut,
 }
 
+/// What happens when an expense spends more than what is available in the category
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum OverspendingMode {
+    /// expense is saved, but flagged as overspent
+    #[default]
+    Permissive,
+    /// expense is rejected with `BudgetingErrors::Overspending`
+    Strict,
+}
+
 impl From<i32> for TransactionType {
     fn from(t: i32) -> TransactionType {
         match t {
@@ -62,8 +79,10 @@ impl From<TransactionType> for String {
     Queryable,
     Associations,
     Identifiable,
+    QueryableByName,
 )]
 #[diesel(belongs_to(Category))]
+#[diesel(table_name = transactions)]
 pub struct Transaction {
     id: i32,
     note: String,
@@ -72,9 +91,15 @@ pub struct Transaction {
     #[diesel(sql_type = Double)]
     amount: f64,
     category_id: i32,
+    #[diesel(column_name = transaction_type_id)]
     transfer_type_id: i32,
     transfer_category_id: Option<i32>,
     budget_account_id: i32,
+    linked_transaction_id: Option<i32>,
+    overspent: bool,
+    overspending_cover: bool,
+    fitid: Option<String>,
+    duplicate_of_id: Option<i32>,
 }
 
 impl Transaction {
@@ -96,6 +121,11 @@ impl Transaction {
             transfer_type_id: 1,
             transfer_category_id: None,
             budget_account_id,
+            linked_transaction_id: None,
+            overspent: false,
+            overspending_cover: false,
+            fitid: None,
+            duplicate_of_id: None,
         }
     }
 
@@ -175,6 +205,11 @@ impl Transaction {
         self.budget_account_id
     }
 
+    /// id the bank gave the transaction in an OFX statement
+    pub fn fitid(&self) -> Option<String> {
+        self.fitid.clone()
+    }
+
     pub fn set_transfer_category_id(&mut self, transfer_category_id: Option<i32>) {
         self.transfer_category_id = transfer_category_id;
     }
@@ -182,6 +217,37 @@ impl Transaction {
     pub fn set_budget_account_id(&mut self, budget_account_id: i32) {
         self.budget_account_id = budget_account_id;
     }
+
+    /// the transaction this one was created because of, i.e. the income a
+    /// transfer was allocated from
+    pub fn linked_transaction_id(&self) -> Option<i32> {
+        self.linked_transaction_id
+    }
+
+    /// true if this expense spent more than what was available in the category
+    pub fn overspent(&self) -> bool {
+        self.overspent
+    }
+
+    /// true if this transfer was done to cover an overspent category
+    pub fn overspending_cover(&self) -> bool {
+        self.overspending_cover
+    }
+
+    /// the transaction this one probably duplicates, set when it was saved in
+    /// `DuplicateMode::Flag`
+    pub fn duplicate_of_id(&self) -> Option<i32> {
+        self.duplicate_of_id
+    }
+}
+
+/// A transaction with the balance right after it, see `TransactionModel::with_running_balance`
+#[derive(Debug, PartialEq, Serialize, Deserialize, Clone, QueryableByName)]
+pub struct TransactionWithBalance {
+    #[diesel(embed)]
+    pub transaction: Transaction,
+    #[diesel(sql_type = Double)]
+    pub running_balance: f64,
 }
 
 pub struct TransactionModel {
@@ -195,6 +261,7 @@ impl TransactionModel {
         transaction_id: i32,
         change_set: TransactionForm,
     ) -> Result<usize, BudgetingErrors> {
+        validation::transaction_form(&change_set)?;
         imp_db!(transactions);
         let r = diesel::update(transactions.find(transaction_id))
             .set(change_set)
@@ -216,9 +283,24 @@ impl TransactionModel {
         transfer_type: Option<TransactionType>,
         _category_id: Option<i32>,
         _budget_account_id: Option<i32>,
+    ) -> f64 {
+        TransactionModel::total_as_of(conn, transfer_type, _category_id, _budget_account_id, None)
+    }
+
+    /// same as `total`, but only counts transactions created until the end of `as_of` day
+    pub(crate) fn total_as_of(
+        conn: &mut SqliteConnection,
+        transfer_type: Option<TransactionType>,
+        _category_id: Option<i32>,
+        _budget_account_id: Option<i32>,
+        as_of: Option<NaiveDate>,
     ) -> f64 {
         imp_db!(transactions);
         let mut query = transactions.into_boxed();
+        // no upper bound at the end of the last date chrono knows
+        if let Some(d) = as_of.and_then(|d| d.succ_opt()) {
+            query = query.filter(date_created.lt(start_of_day(d)));
+        };
         if let Some(bid) = _budget_account_id {
             query = query.filter(budget_account_id.eq(bid));
         };
@@ -265,6 +347,83 @@ impl TransactionModel {
         query.load::<Transaction>(conn).unwrap()
     }
 
+    /// transactions ordered by date_created (and id for the same date) with the running
+    /// balance after each of them, calculated by sqlite with a window function
+    pub(crate) fn with_running_balance(
+        conn: &mut SqliteConnection,
+        _category_id: Option<i32>,
+        _budget_account_id: Option<i32>,
+    ) -> Result<Vec<TransactionWithBalance>, BudgetingErrors> {
+        use diesel::sql_types::{Integer, Nullable};
+        diesel::sql_query(
+            r#"SELECT t.*,
+                      SUM(t.amount) OVER (
+                          ORDER BY t.date_created, t.id
+                          ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW
+                      ) AS running_balance
+               FROM transactions t
+               WHERE (?1 IS NULL OR t.category_id = ?1)
+                 AND (?2 IS NULL OR t.budget_account_id = ?2)
+               ORDER BY t.date_created, t.id"#,
+        )
+        .bind::<Nullable<Integer>, _>(_category_id)
+        .bind::<Nullable<Integer>, _>(_budget_account_id)
+        .load::<TransactionWithBalance>(conn)
+        .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    pub(crate) fn find_by_fitid(
+        conn: &mut SqliteConnection,
+        _budget_account_id: i32,
+        _fitid: &str,
+    ) -> Option<Transaction> {
+        imp_db!(transactions);
+        transactions
+            .filter(budget_account_id.eq(_budget_account_id))
+            .filter(fitid.eq(_fitid))
+            .first::<Transaction>(conn)
+            .ok()
+    }
+
+    /// transfers into categories done to cover overspending
+    pub(crate) fn find_overspending_covers(
+        conn: &mut SqliteConnection,
+        _budget_account_id: Option<i32>,
+    ) -> Vec<Transaction> {
+        imp_db!(transactions);
+        let mut query = transactions
+            .filter(overspending_cover.eq(true))
+            .filter(transaction_type_id.eq(i32::from(TransactionType::TransferIn)))
+            .into_boxed();
+        if let Some(bid) = _budget_account_id {
+            query = query.filter(budget_account_id.eq(bid));
+        };
+        query.order(id.asc()).load::<Transaction>(conn).unwrap()
+    }
+
+    /// all the transactions that were created because of the given transaction
+    pub(crate) fn find_linked(
+        conn: &mut SqliteConnection,
+        transaction_id: i32,
+    ) -> Vec<Transaction> {
+        imp_db!(transactions);
+        transactions
+            .filter(linked_transaction_id.eq(transaction_id))
+            .order(id.asc())
+            .load::<Transaction>(conn)
+            .unwrap()
+    }
+
+    pub(crate) fn save(
+        conn: &mut SqliteConnection,
+        new_transaction: NewTransaction,
+    ) -> Result<Transaction, BudgetingErrors> {
+        save_model!(conn, transactions, new_transaction, Transaction).map_err(|e| match e {
+            diesel::result::Error::NotFound => BudgetingErrors::TransactionNotFound,
+            e => BudgetingErrors::UnspecifiedDatabaseError(e),
+        })
+    }
+
     pub(crate) fn load(
         conn: DbConnection,
         transaction_id: i32,
@@ -296,6 +455,11 @@ pub struct NewTransaction<'a> {
     pub transaction_type_id: i32,
     pub transfer_category_id: Option<i32>,
     pub budget_account_id: i32,
+    pub linked_transaction_id: Option<i32>,
+    pub overspent: bool,
+    pub overspending_cover: bool,
+    pub fitid: Option<&'a str>,
+    pub duplicate_of_id: Option<i32>,
 }
 
 #[derive(AsChangeset)]
@@ -317,6 +481,14 @@ pub struct TransactionBuilder<'a> {
     category_id: i32,
     transfer_category_id: Option<i32>,
     budget_account_id: i32,
+    linked_transaction_id: Option<i32>,
+    overspending_mode: OverspendingMode,
+    overspending_cover: bool,
+    fitid: Option<&'a str>,
+    duplicate_mode: DuplicateMode,
+    duplicate_settings: DuplicateSettings,
+    duplicate_of_id: Option<i32>,
+    categorization_rules: bool,
     conn: DbConnection,
 }
 
@@ -331,6 +503,14 @@ impl<'a> TransactionBuilder<'a> {
             category_id,
             transfer_category_id: None,
             budget_account_id,
+            linked_transaction_id: None,
+            overspending_mode: OverspendingMode::default(),
+            overspending_cover: false,
+            fitid: None,
+            duplicate_mode: DuplicateMode::default(),
+            duplicate_settings: DuplicateSettings::default(),
+            duplicate_of_id: None,
+            categorization_rules: false,
             conn,
         }
     }
@@ -340,6 +520,10 @@ impl<'a> TransactionBuilder<'a> {
         self.payee = None;
         self.note = None;
         self.date_created = None;
+        self.linked_transaction_id = None;
+        self.overspending_cover = false;
+        self.fitid = None;
+        self.duplicate_of_id = None;
     }
 
     pub fn transfer_from(&mut self, amount: f64) -> &mut Self {
@@ -386,39 +570,151 @@ impl<'a> TransactionBuilder<'a> {
         self
     }
 
+    pub fn linked_transaction_id(&mut self, _linked_transaction_id: i32) -> &mut Self {
+        self.linked_transaction_id = Some(_linked_transaction_id);
+        self
+    }
+
+    pub fn overspending_mode(&mut self, mode: OverspendingMode) -> &mut Self {
+        self.overspending_mode = mode;
+        self
+    }
+
+    /// marks the transfer as done to cover an overspent category
+    pub fn overspending_cover(&mut self) -> &mut Self {
+        self.overspending_cover = true;
+        self
+    }
+
+    /// id of the transaction in the bank statement it was imported from
+    pub fn fitid(&mut self, fitid: &'a str) -> &mut Self {
+        self.fitid = Some(fitid);
+        self
+    }
+
+    /// how incomes and expenses looking like a transaction already saved are handled
+    pub fn duplicate_detection(&mut self, mode: DuplicateMode, settings: DuplicateSettings) -> &mut Self {
+        self.duplicate_mode = mode;
+        self.duplicate_settings = settings;
+        self
+    }
+
+    /// marks the transaction as a probable duplicate of another one
+    pub fn duplicate_of_id(&mut self, _duplicate_of_id: i32) -> &mut Self {
+        self.duplicate_of_id = Some(_duplicate_of_id);
+        self
+    }
+
+    /// runs the categorization rules of the budget account on incomes and expenses, they
+    /// can change the payee, add tags and give an expense of the default category its category
+    pub fn categorization_rules(&mut self, apply: bool) -> &mut Self {
+        self.categorization_rules = apply;
+        self
+    }
+
+    /// saves the transaction, if it is an income, allocation rules of the budget account
+    /// are applied in the same database transaction, so are tags of categorization rules.
+    /// In `DuplicateMode::Merge` an income or expense matching one already saved returns
+    /// that one instead
     pub fn done(&mut self) -> Result<Transaction, BudgetingErrors> {
         if self.note.is_none() || self.payee.is_none() || self.amount.is_none() {
             return Err(BudgetingErrors::MissingTransactionFields);
         }
+        validation::positive("amount", self.amount.unwrap())?;
+        validation::not_empty("payee", self.payee.unwrap())?;
         if TransactionType::Income == self.transaction_type {
             warn!("income moved to DEFAULT CATEGORY.");
             self.category_id = CategoryModel::find_by_name(gc!(self.conn), DEFAULT_CATEGORY)
                 .unwrap()
                 .id();
         }
+        let is_income = TransactionType::Income == self.transaction_type;
+        let is_expense = TransactionType::Expense == self.transaction_type;
+        let mut rules = RuleOutcome::default();
+        if self.categorization_rules && (is_income || is_expense) {
+            rules = RuleSet::load(gc!(self.conn), self.budget_account_id)?
+                .apply(self.payee.unwrap(), self.note.unwrap());
+            if let Some(c) = rules.category_id.filter(|_| is_expense) {
+                let default = CategoryModel::find_by_name(gc!(self.conn), DEFAULT_CATEGORY)?;
+                if self.category_id == default.id() {
+                    self.category_id = c;
+                }
+            }
+        }
         let signed_amount = match self.transaction_type {
             TransactionType::Income | TransactionType::TransferIn => self.amount.unwrap(),
             TransactionType::Expense | TransactionType::TransferOut => -1. * self.amount.unwrap(),
         };
         let new_transaction = NewTransaction {
             note: self.note.as_ref().unwrap(),
-            payee: self.payee.as_ref().unwrap(),
+            payee: rules.payee.as_deref().unwrap_or(self.payee.unwrap()),
             date_created: self.date_created.unwrap_or_else(current_date),
             amount: signed_amount,
             category_id: self.category_id,
             transaction_type_id: i32::from(self.transaction_type.clone()),
             transfer_category_id: self.transfer_category_id,
             budget_account_id: self.budget_account_id,
+            linked_transaction_id: self.linked_transaction_id,
+            overspent: false,
+            overspending_cover: self.overspending_cover,
+            fitid: self.fitid,
+            duplicate_of_id: self.duplicate_of_id,
         };
-        let transaction = save_model!(
-            gc!(self.conn),
-            transactions,
-            new_transaction,
-            Transaction).map_err(|e| {
-            match e {
-                diesel::result::Error::NotFound => BudgetingErrors::TransactionNotFound,
-                e => BudgetingErrors::UnspecifiedDatabaseError(e)
+        let mode = self.overspending_mode;
+        let duplicate_mode = self.duplicate_mode;
+        let duplicate_settings = self.duplicate_settings;
+        let transaction = gc!(self.conn).transaction(|conn| {
+            let mut new_transaction = new_transaction;
+            if (is_income || is_expense) && duplicate_mode != DuplicateMode::Off {
+                let probe = DuplicateProbe {
+                    date_created: new_transaction.date_created,
+                    amount: new_transaction.amount,
+                    payee: new_transaction.payee,
+                    fitid: new_transaction.fitid,
+                };
+                let found = DuplicateMatcher::best_match(
+                    conn, new_transaction.budget_account_id, &probe, &duplicate_settings, &[],
+                )?;
+                if let Some(found) = found {
+                    let existing = found.transaction;
+                    match duplicate_mode {
+                        DuplicateMode::Skip => {
+                            return Err(BudgetingErrors::DuplicateTransaction(existing.id()))
+                        }
+                        DuplicateMode::Merge => {
+                            return DuplicateMatcher::merge(
+                                conn, &existing, new_transaction.note, new_transaction.fitid,
+                            )
+                        }
+                        DuplicateMode::Flag => new_transaction.duplicate_of_id = Some(existing.id()),
+                        DuplicateMode::Off => {}
+                    }
+                }
+            }
+            if is_expense {
+                let available = TransactionModel::total(
+                    conn, None, Some(new_transaction.category_id), None,
+                );
+                let shortfall = -new_transaction.amount - available.max(0.);
+                if shortfall > 0. {
+                    if mode == OverspendingMode::Strict {
+                        let category = CategoryModel::load_by_id(conn, new_transaction.category_id)?;
+                        return Err(BudgetingErrors::Overspending {
+                            category: category.name(),
+                            shortfall,
+                        });
+                    }
+                    new_transaction.overspent = true;
+                }
+            }
+            let t = TransactionModel::save(conn, new_transaction)?;
+            for tag in &rules.tags {
+                TagModel::tag(conn, t.id(), tag)?;
+            }
+            if is_income {
+                IncomeAllocationRuleModel::apply(conn, &t)?;
             }
+            Ok(t)
         });
         self.reset();
         transaction
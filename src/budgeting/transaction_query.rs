@@ -0,0 +1,318 @@
+use crate::budgeting::budgeting_errors::BudgetingErrors;
+use crate::budgeting::transaction::{Transaction, TransactionType};
+use crate::schema::{categories, tags, transaction_tags, transactions};
+use chrono::NaiveDateTime;
+use diesel::dsl::sum;
+use diesel::prelude::*;
+use diesel::sqlite::Sqlite;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum SortKey {
+    #[default]
+    DateCreated,
+    Amount,
+    Payee,
+    Id,
+}
+
+#[derive(Debug, Default, Eq, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum SortOrder {
+    #[default]
+    Ascending,
+    Descending,
+}
+
+/// Position of the last row of a page, the next page starts right after it.
+/// Only the value of the current sort key is used, with id as tie breaker
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct TransactionCursor {
+    pub id: i32,
+    pub date_created: NaiveDateTime,
+    pub amount: f64,
+    pub payee: String,
+}
+
+impl From<&Transaction> for TransactionCursor {
+    fn from(t: &Transaction) -> Self {
+        TransactionCursor {
+            id: t.id(),
+            date_created: t.date_created(),
+            amount: t.amount(),
+            payee: t.payee(),
+        }
+    }
+}
+
+/// Filters, sorting and pagination of transactions, compiled to a single query.
+/// All the filters are combined with AND, multiple values of the same filter
+/// (categories, types, tags) are combined with OR.
+/// Amounts are compared as stored, so expenses and transfers out are negative.
+#[derive(Debug, Default, Clone)]
+pub struct TransactionQuery {
+    budget_account_id: Option<i32>,
+    category_ids: Vec<i32>,
+    category_names: Vec<String>,
+    from: Option<NaiveDateTime>,
+    before: Option<NaiveDateTime>,
+    payee_contains: Option<String>,
+    note_contains: Option<String>,
+    min_amount: Option<f64>,
+    max_amount: Option<f64>,
+    transaction_types: Vec<TransactionType>,
+    tags: Vec<String>,
+    sort_key: SortKey,
+    sort_order: SortOrder,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    after: Option<TransactionCursor>,
+}
+
+fn like_pattern(value: &str) -> String {
+    let escaped = value
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{escaped}%")
+}
+
+impl TransactionQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn budget_account_id(&mut self, budget_account_id: i32) -> &mut Self {
+        self.budget_account_id = Some(budget_account_id);
+        self
+    }
+
+    pub fn category_id(&mut self, category_id: i32) -> &mut Self {
+        self.category_ids.push(category_id);
+        self
+    }
+
+    /// category name, matched case insensitively
+    pub fn category(&mut self, category: &str) -> &mut Self {
+        self.category_names.push(category.to_lowercase());
+        self
+    }
+
+    /// transactions created at or after `date`
+    pub fn from(&mut self, date: NaiveDateTime) -> &mut Self {
+        self.from = Some(date);
+        self
+    }
+
+    /// transactions created strictly before `date`
+    pub fn before(&mut self, date: NaiveDateTime) -> &mut Self {
+        self.before = Some(date);
+        self
+    }
+
+    /// case insensitive substring of payee
+    pub fn payee_contains(&mut self, payee: &str) -> &mut Self {
+        self.payee_contains = Some(payee.to_string());
+        self
+    }
+
+    /// case insensitive substring of note
+    pub fn note_contains(&mut self, note: &str) -> &mut Self {
+        self.note_contains = Some(note.to_string());
+        self
+    }
+
+    pub fn min_amount(&mut self, amount: f64) -> &mut Self {
+        self.min_amount = Some(amount);
+        self
+    }
+
+    pub fn max_amount(&mut self, amount: f64) -> &mut Self {
+        self.max_amount = Some(amount);
+        self
+    }
+
+    pub fn transaction_type(&mut self, transaction_type: TransactionType) -> &mut Self {
+        self.transaction_types.push(transaction_type);
+        self
+    }
+
+    /// replaces the types set with `transaction_type`
+    pub(crate) fn only_transaction_types(&mut self, transaction_types: &[TransactionType]) -> &mut Self {
+        self.transaction_types = transaction_types.to_vec();
+        self
+    }
+
+    /// transactions having the tag, matched case insensitively
+    pub fn tag(&mut self, tag: &str) -> &mut Self {
+        self.tags.push(tag.trim().to_lowercase());
+        self
+    }
+
+    pub fn sort_by(&mut self, sort_key: SortKey, sort_order: SortOrder) -> &mut Self {
+        self.sort_key = sort_key;
+        self.sort_order = sort_order;
+        self
+    }
+
+    pub fn limit(&mut self, limit: i64) -> &mut Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn offset(&mut self, offset: i64) -> &mut Self {
+        self.offset = Some(offset);
+        self
+    }
+
+    /// drops limit, offset and keyset so every matching transaction is loaded
+    pub(crate) fn unpaginated(&mut self) -> &mut Self {
+        self.limit = None;
+        self.offset = None;
+        self.after = None;
+        self
+    }
+
+    /// keyset pagination, continues after the given transaction which must be
+    /// the last row of the previous page loaded with the same sorting
+    pub fn after(&mut self, transaction: &Transaction) -> &mut Self {
+        self.after = Some(TransactionCursor::from(transaction));
+        self
+    }
+
+    /// only the filters, without sorting or pagination
+    fn filtered(&self) -> transactions::BoxedQuery<'static, Sqlite> {
+        use crate::schema::transactions::dsl::*;
+        let mut query = transactions.into_boxed();
+        if let Some(bid) = self.budget_account_id {
+            query = query.filter(budget_account_id.eq(bid));
+        }
+        if !self.category_names.is_empty() {
+            let by_name = categories::table
+                .filter(crate::m::lower(categories::name).eq_any(self.category_names.clone()))
+                .select(categories::id);
+            query = query.filter(
+                category_id
+                    .eq_any(self.category_ids.clone())
+                    .or(category_id.eq_any(by_name)),
+            );
+        } else if !self.category_ids.is_empty() {
+            query = query.filter(category_id.eq_any(self.category_ids.clone()));
+        }
+        if let Some(d) = self.from {
+            query = query.filter(date_created.ge(d));
+        }
+        if let Some(d) = self.before {
+            query = query.filter(date_created.lt(d));
+        }
+        if let Some(p) = &self.payee_contains {
+            query = query.filter(payee.like(like_pattern(p)).escape('\\'));
+        }
+        if let Some(n) = &self.note_contains {
+            query = query.filter(note.like(like_pattern(n)).escape('\\'));
+        }
+        if let Some(a) = self.min_amount {
+            query = query.filter(amount.ge(a));
+        }
+        if let Some(a) = self.max_amount {
+            query = query.filter(amount.le(a));
+        }
+        if !self.transaction_types.is_empty() {
+            let types: Vec<i32> = self
+                .transaction_types
+                .iter()
+                .map(|t| i32::from(t.clone()))
+                .collect();
+            query = query.filter(transaction_type_id.eq_any(types));
+        }
+        if !self.tags.is_empty() {
+            let tagged = transaction_tags::table
+                .inner_join(tags::table)
+                .filter(crate::m::lower(tags::name).eq_any(self.tags.clone()))
+                .select(transaction_tags::transaction_id);
+            query = query.filter(id.eq_any(tagged));
+        }
+        query
+    }
+
+    /// filters with keyset, sorting and pagination
+    fn paginated(&self) -> transactions::BoxedQuery<'static, Sqlite> {
+        use crate::schema::transactions::dsl::*;
+        let mut query = self.filtered();
+        let asc = self.sort_order == SortOrder::Ascending;
+        if let Some(c) = &self.after {
+            query = match (self.sort_key, asc) {
+                (SortKey::DateCreated, true) => query.filter(
+                    date_created
+                        .gt(c.date_created)
+                        .or(date_created.eq(c.date_created).and(id.gt(c.id))),
+                ),
+                (SortKey::DateCreated, false) => query.filter(
+                    date_created
+                        .lt(c.date_created)
+                        .or(date_created.eq(c.date_created).and(id.lt(c.id))),
+                ),
+                (SortKey::Amount, true) => query
+                    .filter(amount.gt(c.amount).or(amount.eq(c.amount).and(id.gt(c.id)))),
+                (SortKey::Amount, false) => query
+                    .filter(amount.lt(c.amount).or(amount.eq(c.amount).and(id.lt(c.id)))),
+                (SortKey::Payee, true) => query.filter(
+                    payee
+                        .gt(c.payee.clone())
+                        .or(payee.eq(c.payee.clone()).and(id.gt(c.id))),
+                ),
+                (SortKey::Payee, false) => query.filter(
+                    payee
+                        .lt(c.payee.clone())
+                        .or(payee.eq(c.payee.clone()).and(id.lt(c.id))),
+                ),
+                (SortKey::Id, true) => query.filter(id.gt(c.id)),
+                (SortKey::Id, false) => query.filter(id.lt(c.id)),
+            };
+        }
+        query = match (self.sort_key, asc) {
+            (SortKey::DateCreated, true) => query.order((date_created.asc(), id.asc())),
+            (SortKey::DateCreated, false) => query.order((date_created.desc(), id.desc())),
+            (SortKey::Amount, true) => query.order((amount.asc(), id.asc())),
+            (SortKey::Amount, false) => query.order((amount.desc(), id.desc())),
+            (SortKey::Payee, true) => query.order((payee.asc(), id.asc())),
+            (SortKey::Payee, false) => query.order((payee.desc(), id.desc())),
+            (SortKey::Id, true) => query.order(id.asc()),
+            (SortKey::Id, false) => query.order(id.desc()),
+        };
+        if let Some(l) = self.limit {
+            query = query.limit(l);
+        }
+        if let Some(o) = self.offset {
+            if self.limit.is_none() {
+                // sqlite does not accept OFFSET without LIMIT
+                query = query.limit(-1);
+            }
+            query = query.offset(o);
+        }
+        query
+    }
+
+    pub(crate) fn load(&self, conn: &mut SqliteConnection) -> Result<Vec<Transaction>, BudgetingErrors> {
+        self.paginated()
+            .load::<Transaction>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    /// number of matching transactions, ignoring pagination
+    pub(crate) fn count(&self, conn: &mut SqliteConnection) -> Result<i64, BudgetingErrors> {
+        self.filtered()
+            .count()
+            .get_result::<i64>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)
+    }
+
+    /// sum of amounts of matching transactions, ignoring pagination
+    pub(crate) fn sum(&self, conn: &mut SqliteConnection) -> Result<f64, BudgetingErrors> {
+        let total = self
+            .filtered()
+            .select(sum(transactions::amount))
+            .first::<Option<f64>>(conn)
+            .map_err(BudgetingErrors::UnspecifiedDatabaseError)?;
+        Ok(total.unwrap_or(0.))
+    }
+}
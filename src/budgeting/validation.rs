@@ -0,0 +1,110 @@
+//! Checks applied to every value coming from the user before anything is written
+//! to the database. All of them return `BudgetingErrors::InvalidInput` naming the
+//! field and the reason it was rejected.
+
+use crate::budgeting::budgeting_errors::{BudgetingErrors, InvalidReason};
+use crate::budgeting::category::CategoryForm;
+use crate::budgeting::duplicate::{DuplicateSettings, MAX_DUPLICATE_DAYS};
+use crate::budgeting::transaction::TransactionForm;
+use std::fmt::Write;
+
+fn invalid(field: &str, reason: InvalidReason) -> BudgetingErrors {
+    BudgetingErrors::InvalidInput {
+        field: field.to_string(),
+        reason,
+    }
+}
+
+/// amount must be a real number, sign is checked by the callers
+pub(crate) fn finite(field: &str, value: f64) -> Result<f64, BudgetingErrors> {
+    if value.is_nan() {
+        return Err(invalid(field, InvalidReason::NotANumber));
+    }
+    if value.is_infinite() {
+        return Err(invalid(field, InvalidReason::Infinite));
+    }
+    Ok(value)
+}
+
+/// amounts of transactions and transfers, sign is decided by the transaction type
+pub(crate) fn positive(field: &str, value: f64) -> Result<f64, BudgetingErrors> {
+    let value = non_negative(field, value)?;
+    if value == 0. {
+        return Err(invalid(field, InvalidReason::Zero));
+    }
+    Ok(value)
+}
+
+/// allocations and opening balances
+pub(crate) fn non_negative(field: &str, value: f64) -> Result<f64, BudgetingErrors> {
+    let value = finite(field, value)?;
+    if value < 0. {
+        return Err(invalid(field, InvalidReason::Negative));
+    }
+    Ok(value)
+}
+
+/// a share of something, more than zero and up to 100
+pub(crate) fn percentage(field: &str, value: f64) -> Result<f64, BudgetingErrors> {
+    let value = positive(field, value)?;
+    if value > 100. {
+        return Err(invalid(field, InvalidReason::OutOfRange));
+    }
+    Ok(value)
+}
+
+pub(crate) fn not_empty<'a>(field: &str, value: &'a str) -> Result<&'a str, BudgetingErrors> {
+    if value.trim().is_empty() {
+        return Err(invalid(field, InvalidReason::Empty));
+    }
+    Ok(value)
+}
+
+/// patterns of categorization rules
+pub(crate) fn pattern(field: &str, value: &str) -> Result<regex::Regex, BudgetingErrors> {
+    not_empty(field, value)?;
+    regex::Regex::new(value).map_err(|_| invalid(field, InvalidReason::NotAPattern))
+}
+
+/// chrono format strings given for exports, checked before anything is written by
+/// rendering a date with it, dates have no time zone so `%z` fails like a bad specifier
+pub(crate) fn date_format<'a>(field: &str, value: &'a str) -> Result<&'a str, BudgetingErrors> {
+    let sample = chrono::NaiveDateTime::default();
+    write!(String::new(), "{}", sample.format(value))
+        .map_err(|_| invalid(field, InvalidReason::NotADateFormat))?;
+    Ok(value)
+}
+
+pub(crate) fn duplicate_settings(settings: &DuplicateSettings) -> Result<(), BudgetingErrors> {
+    if settings.max_days > MAX_DUPLICATE_DAYS {
+        return Err(invalid("max_days", InvalidReason::OutOfRange));
+    }
+    non_negative("amount_tolerance", settings.amount_tolerance)?;
+    if !(0. ..=1.).contains(&finite("min_score", settings.min_score)?) {
+        return Err(invalid("min_score", InvalidReason::OutOfRange));
+    }
+    Ok(())
+}
+
+/// stored amounts are signed, so only zero and non numbers are rejected
+pub(crate) fn transaction_form(form: &TransactionForm) -> Result<(), BudgetingErrors> {
+    if let Some(amount) = form.amount {
+        if finite("amount", amount)? == 0. {
+            return Err(invalid("amount", InvalidReason::Zero));
+        }
+    }
+    if let Some(payee) = &form.payee {
+        not_empty("payee", payee)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn category_form(form: &CategoryForm) -> Result<(), BudgetingErrors> {
+    if let Some(name) = &form.name {
+        not_empty("name", name)?;
+    }
+    if let Some(allocated) = form.allocated {
+        non_negative("allocated", allocated)?;
+    }
+    Ok(())
+}
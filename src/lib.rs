@@ -59,7 +59,8 @@ pub(crate) mod m {
 ///
 /// # Envelope budgeting
 /// * We create categories and have budget for every category
-/// * We can not spend more money then what we have allocated in that category
+/// * We can not spend more money then what we have allocated in that category,
+///   in permissive mode such expenses are accepted but flagged as overspent
 /// * We can transfer money from one category to other
 ///
 pub mod budgeting;
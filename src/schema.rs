@@ -16,6 +16,103 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    categorization_rules (id) {
+        id -> Integer,
+        budget_account_id -> Integer,
+        name -> Text,
+        priority -> Integer,
+        payee_pattern -> Nullable<Text>,
+        note_pattern -> Nullable<Text>,
+        payee -> Nullable<Text>,
+        category_id -> Nullable<Integer>,
+        tag -> Nullable<Text>,
+    }
+}
+
+diesel::table! {
+    funding_template_items (id) {
+        id -> Integer,
+        funding_template_id -> Integer,
+        category_id -> Integer,
+        amount -> Double,
+        is_percentage -> Bool,
+    }
+}
+
+diesel::table! {
+    funding_templates (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    import_profiles (id) {
+        id -> Integer,
+        budget_account_id -> Integer,
+        name -> Text,
+        delimiter -> Text,
+        header_rows -> Integer,
+        date_column -> Integer,
+        date_format -> Text,
+        amount_column -> Nullable<Integer>,
+        debit_column -> Nullable<Integer>,
+        credit_column -> Nullable<Integer>,
+        payee_column -> Integer,
+        memo_column -> Nullable<Integer>,
+        category_column -> Nullable<Integer>,
+        decimal_separator -> Text,
+    }
+}
+
+diesel::table! {
+    income_allocation_rules (id) {
+        id -> Integer,
+        budget_account_id -> Integer,
+        category_id -> Integer,
+        percentage -> Double,
+    }
+}
+
+diesel::table! {
+    period_allocations (id) {
+        id -> Integer,
+        category_id -> Integer,
+        month -> Date,
+        amount -> Double,
+    }
+}
+
+diesel::table! {
+    scheduled_transactions (id) {
+        id -> Integer,
+        budget_account_id -> Integer,
+        category_id -> Integer,
+        transaction_type_id -> Integer,
+        payee -> Text,
+        note -> Text,
+        amount -> Double,
+        start_date -> Date,
+        recurrence -> Text,
+        every -> Integer,
+    }
+}
+
+diesel::table! {
+    tags (id) {
+        id -> Integer,
+        name -> Text,
+    }
+}
+
+diesel::table! {
+    transaction_tags (transaction_id, tag_id) {
+        transaction_id -> Integer,
+        tag_id -> Integer,
+    }
+}
+
 diesel::table! {
     transaction_types (id) {
         id -> Integer,
@@ -34,9 +131,27 @@ diesel::table! {
         transaction_type_id -> Integer,
         transfer_category_id -> Nullable<Integer>,
         budget_account_id -> Integer,
+        linked_transaction_id -> Nullable<Integer>,
+        overspent -> Bool,
+        overspending_cover -> Bool,
+        fitid -> Nullable<Text>,
+        duplicate_of_id -> Nullable<Integer>,
     }
 }
 
+diesel::joinable!(categorization_rules -> budget_accounts (budget_account_id));
+diesel::joinable!(categorization_rules -> categories (category_id));
+diesel::joinable!(funding_template_items -> categories (category_id));
+diesel::joinable!(funding_template_items -> funding_templates (funding_template_id));
+diesel::joinable!(import_profiles -> budget_accounts (budget_account_id));
+diesel::joinable!(income_allocation_rules -> budget_accounts (budget_account_id));
+diesel::joinable!(income_allocation_rules -> categories (category_id));
+diesel::joinable!(period_allocations -> categories (category_id));
+diesel::joinable!(scheduled_transactions -> budget_accounts (budget_account_id));
+diesel::joinable!(scheduled_transactions -> categories (category_id));
+diesel::joinable!(scheduled_transactions -> transaction_types (transaction_type_id));
+diesel::joinable!(transaction_tags -> tags (tag_id));
+diesel::joinable!(transaction_tags -> transactions (transaction_id));
 diesel::joinable!(transactions -> budget_accounts (budget_account_id));
 diesel::joinable!(transactions -> categories (category_id));
 diesel::joinable!(transactions -> transaction_types (transaction_type_id));
@@ -44,6 +159,15 @@ diesel::joinable!(transactions -> transaction_types (transaction_type_id));
 diesel::allow_tables_to_appear_in_same_query!(
     budget_accounts,
     categories,
+    categorization_rules,
+    funding_template_items,
+    funding_templates,
+    import_profiles,
+    income_allocation_rules,
+    period_allocations,
+    scheduled_transactions,
+    tags,
+    transaction_tags,
     transaction_types,
     transactions,
 );
@@ -105,6 +105,46 @@ impl Speller {
 
 }
 
+/// The number of deletions, insertions, alterations or transpositions of adjacent
+/// letters needed to turn `a` into `b`, the same edits `Speller` tries when correcting.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    // rows[i][j] is the distance between the first i letters of a and the first j of b
+    let mut rows = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in rows.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, cell) in rows[0].iter_mut().enumerate() {
+        *cell = j;
+    }
+    for i in 1 ..= a.len() {
+        for j in 1 ..= b.len() {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            let mut d = (rows[i - 1][j] + 1)
+                .min(rows[i][j - 1] + 1)
+                .min(rows[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d = d.min(rows[i - 2][j - 2] + 1);
+            }
+            rows[i][j] = d;
+        }
+    }
+    rows[a.len()][b.len()]
+}
+
+/// How alike two texts are, from 0 (nothing in common) to 1 (the same, ignoring case
+/// and surrounding whitespace).
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a = a.trim().to_lowercase();
+    let b = b.trim().to_lowercase();
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.;
+    }
+    1. - edit_distance(&a, &b) as f64 / longest as f64
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -130,4 +170,16 @@ mod tests {
         assert_eq!(speller.correct("tomarto"), "tomato");
     }
 
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("tomato", "tomato"), 0);
+        assert_eq!(edit_distance("tomato", "tomto"), 1);
+        assert_eq!(edit_distance("tomato", "tomaot"), 1);
+        assert_eq!(edit_distance("tomato", "potato"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+        assert_eq!(similarity("Water Co", "water co "), 1.);
+        assert_eq!(similarity("abcd", "abce"), 0.75);
+        assert_eq!(similarity("", ""), 1.);
+    }
+
 }
\ No newline at end of file